@@ -1,7 +1,8 @@
-use std::{path::PathBuf, str::FromStr, time::Duration};
+use std::{num::NonZeroUsize, path::PathBuf, str::FromStr, time::Duration};
 
 use clap::{ArgAction, Parser, ValueEnum, ValueHint};
-use watchexec::paths::PATH_SEPARATOR;
+use regex::Regex;
+use watchexec::{paths::PATH_SEPARATOR, timer::Schedule};
 use watchexec_signals::Signal;
 
 const OPTSET_FILTERING: &str = "Filtering";
@@ -82,6 +83,23 @@ pub struct Args {
 	/// the shell as described in the help for '--shell'. For 'none', each distinct element the
 	/// command is passed as per the execvp(3) convention: first argument is the program, as a path
 	/// or searched for in the 'PATH' environment variable, rest are arguments.
+	///
+	/// The command can contain placeholders, which are replaced by the paths which changed:
+	///
+	///   - '{paths}' with all of them, as absolute paths
+	///   - '{path}' with the first of them
+	///   - '{relpaths}' with all of them, relative to the current directory
+	///   - '{common}' with their common prefix
+	///
+	/// With a shell, the paths are quoted for that shell, and separated by spaces. With '--shell
+	/// none', a placeholder that forms a whole argument is replaced by one argument per path (or
+	/// none if nothing changed). Quote the placeholders to prevent your own shell from expanding
+	/// them:
+	///
+	///   $ watchexec -e rs -- rustfmt '{paths}'
+	///
+	/// Placeholders are replaced wherever they appear in the command. To pass one through as-is,
+	/// double its braces: '{{path}}' becomes a literal '{path}'.
 	#[arg(
 		trailing_var_arg = true,
 		num_args = 1..,
@@ -97,7 +115,11 @@ pub struct Args {
 	///
 	/// When watching a single file, it's often better to watch the containing directory instead,
 	/// and filter on the filename. Some editors may replace the file with a new one when saving,
-	/// and some platforms may not detect that or further changes.
+	/// and while Watchexec notices that and watches the new file, some platforms may not report it.
+	///
+	/// Paths which don't exist yet are watched for: once they're created, they're watched like any
+	/// other. The same goes for watched paths which are deleted, moved away, or replaced, like with
+	/// 'rm -rf dist && mkdir dist'.
 	///
 	/// Upon starting, Watchexec resolves a "project origin" from the watched paths. See the help
 	/// for '--project-origin' for more information.
@@ -112,6 +134,40 @@ pub struct Args {
 	)]
 	pub paths: Vec<PathBuf>,
 
+	/// Watch a specific directory, non-recursively
+	///
+	/// Unlike '-w', folders watched with this option are not traversed: only changes to the folder
+	/// and its immediate children are reported. This is useful to watch a directory without
+	/// watching everything under it, like a directory of configuration files next to a large tree
+	/// of data.
+	///
+	/// This option can be specified multiple times to watch multiple directories non-recursively,
+	/// and can be combined with '-w'.
+	#[arg(
+		long = "watch-non-recursive",
+		help_heading = OPTSET_FILTERING,
+		value_hint = ValueHint::AnyPath,
+		value_name = "PATH",
+	)]
+	pub paths_non_recursive: Vec<PathBuf>,
+
+	/// Limit how deep directories are watched
+	///
+	/// By default, paths given with '-w' (or the current directory) are watched recursively, at any
+	/// depth. With this, changes are only reported up to this many levels below them: 1 is their
+	/// immediate children (like '--watch-non-recursive'), 2 is their children's children, and so
+	/// on.
+	///
+	/// Note that native watchers can only watch either all levels or just one, so for depths
+	/// beyond 1 the whole tree is still watched, and deeper changes are ignored.
+	#[arg(
+		long,
+		help_heading = OPTSET_FILTERING,
+		value_name = "DEPTH",
+		value_parser = clap::value_parser!(u32).range(1..),
+	)]
+	pub watch_depth: Option<u32>,
+
 	/// Clear screen before running command
 	///
 	/// If this doesn't completely clear the screen, try '--clear=reset'.
@@ -228,6 +284,169 @@ pub struct Args {
 	)]
 	pub stop_timeout: TimeSpan,
 
+	/// Signals to send in turn to stop the command, each with its own timeout
+	///
+	/// This replaces the '--stop-signal' and '--stop-timeout' sequence used when stopping the
+	/// command for a restart or '--timeout' with a sequence of as many steps as needed. Each step
+	/// sends its signal and waits for the command to exit; if it hasn't exited after the timeout,
+	/// the next step is taken. If it's still running after the last step, it is forcefully
+	/// terminated.
+	///
+	/// Steps are given as "SIGNAL:TIMEOUT", with the signal in the same format as '--stop-signal'
+	/// and the timeout in the same format as '--stop-timeout', and in the order they are to be
+	/// taken. The timeout can be omitted, in which case '--stop-timeout' is used. For example, to
+	/// give a server 30 seconds to drain connections after a SIGINT, then 10 seconds more after a
+	/// SIGTERM, before killing it:
+	///
+	///   $ watchexec -r --stop-escalation INT:30s --stop-escalation TERM:10s -- ./server
+	///
+	/// This has no practical effect on Windows as the command is always forcefully terminated; see
+	/// '--stop-signal' for why.
+	#[arg(long, value_name = "SIGNAL:TIMEOUT", conflicts_with = "stop_signal")]
+	pub stop_escalation: Vec<EscalationStep>,
+
+	/// Time limit for each run of the command
+	///
+	/// If the command is still running after this time, it is stopped the same way as it would be
+	/// when restarting: it is sent the '--stop-signal' (default "SIGTERM"), and forcefully
+	/// terminated if it hasn't exited after the '--stop-timeout', or it goes through the
+	/// '--stop-escalation' steps. Watchexec then reports that the command timed out, and carries
+	/// on waiting for events.
+	///
+	/// Takes a unit-less value in seconds, or a time span value such as "5min 20s".
+	///
+	/// By default there is no time limit.
+	#[arg(long, value_name = "TIMEOUT")]
+	pub timeout: Option<TimeSpan>,
+
+	/// Restart the command when it ends on its own
+	///
+	/// By default, when the command ends, Watchexec waits for the next event to run it again. This
+	/// is not very practical for long-running commands like servers: if they crash, they stay down
+	/// until the next change. With this, Watchexec will restart the command after a delay when it
+	/// ends without having been stopped by Watchexec itself.
+	///
+	/// With 'failure', the command is only restarted when it ends unsuccessfully (with a non-zero
+	/// exit status, killed by a signal, etc). With 'exit', it's restarted whenever it ends.
+	///
+	/// The delay starts at '--auto-restart-delay', and doubles with each consecutive restart up to
+	/// '--auto-restart-max-delay'. Once the command has stayed up for '--auto-restart-reset', the
+	/// delay goes back to its initial value. Use '--auto-restart-retries' to give up after some
+	/// consecutive restarts.
+	///
+	/// Changes which happen while waiting to restart run the command immediately, as usual.
+	#[arg(long, value_name = "WHEN")]
+	pub auto_restart: Option<AutoRestart>,
+
+	/// Initial delay before restarting the command
+	///
+	/// See '--auto-restart'. Takes a unit-less value in seconds, or a time span value such as
+	/// "500ms".
+	///
+	/// The default is 1 second.
+	#[arg(
+		long,
+		default_value = "1",
+		hide_default_value = true,
+		value_name = "DELAY",
+		requires = "auto_restart"
+	)]
+	pub auto_restart_delay: TimeSpan,
+
+	/// Maximum delay before restarting the command
+	///
+	/// See '--auto-restart'. Takes a unit-less value in seconds, or a time span value such as
+	/// "5min 20s".
+	///
+	/// The default is 60 seconds.
+	#[arg(
+		long,
+		default_value = "60",
+		hide_default_value = true,
+		value_name = "DELAY",
+		requires = "auto_restart"
+	)]
+	pub auto_restart_max_delay: TimeSpan,
+
+	/// Give up restarting the command after this many consecutive restarts
+	///
+	/// See '--auto-restart'. By default, Watchexec never gives up.
+	#[arg(long, value_name = "COUNT", requires = "auto_restart")]
+	pub auto_restart_retries: Option<u32>,
+
+	/// How long the command must stay up for its restarts to be reset
+	///
+	/// See '--auto-restart'. Takes a unit-less value in seconds, or a time span value such as
+	/// "5min 20s".
+	///
+	/// The default is 60 seconds.
+	#[arg(
+		long,
+		default_value = "60",
+		hide_default_value = true,
+		value_name = "PERIOD",
+		requires = "auto_restart"
+	)]
+	pub auto_restart_reset: TimeSpan,
+
+	/// Wait for the command to be ready before handling further events
+	///
+	/// By default, Watchexec considers the command ready as soon as it starts. For services which
+	/// take a while to start up, this can mean restarting them before they're even up. With this,
+	/// Watchexec holds on to events after starting the command until the probe succeeds, and
+	/// prints a message when it does. Events which come in the meantime are then handled all at
+	/// once. Watchexec stops waiting if the command ends, or after '--ready-timeout'.
+	///
+	/// The probe is one of:
+	///
+	/// - "port:PORT", to wait for a TCP port to accept connections on localhost;
+	/// - "file:PATH", to wait for a file to exist;
+	/// - "output:REGEX", to wait for a line of output of the command to match a regular expression.
+	///
+	/// For example, to wait for a web server to listen on port 8080:
+	///
+	///   $ watchexec -r --ready port:8080 -- ./server
+	#[arg(long, value_name = "PROBE")]
+	pub ready: Option<ReadyProbe>,
+
+	/// How long to wait for the command to be ready
+	///
+	/// If the '--ready' probe hasn't succeeded after this long, Watchexec prints an error and stops
+	/// holding events, handling them as if the command were ready. The command keeps running.
+	///
+	/// Takes a unit-less value in seconds, or a time span value such as "2min 30s". The default is
+	/// 60 seconds. Set to 0 to wait for as long as the command runs.
+	#[arg(
+		long,
+		default_value = "60",
+		hide_default_value = true,
+		value_name = "TIMEOUT",
+		requires = "ready"
+	)]
+	pub ready_timeout: TimeSpan,
+
+	/// Run the command once per changed path
+	///
+	/// By default, the command is run once for all the changes which were debounced together. With
+	/// this, it's instead run once for each changed path, with only that path given to the command:
+	/// in placeholders like '{path}', in the environment, or in the events file, depending on
+	/// '--emit-events-to'.
+	///
+	/// Optionally takes how many of those runs can go at once. If not specified, they run one after
+	/// the other. The command only counts as ended (for '--on-busy-update', '--on-success', etc)
+	/// once every run has ended, and as successful only if every run was.
+	///
+	/// For example, to compile each changed stylesheet, four at a time:
+	///
+	///   $ watchexec -e scss --fan-out 4 -- sass '{path}' '{path}.css'
+	#[arg(
+		long,
+		num_args = 0..=1,
+		default_missing_value = "1",
+		value_name = "CONCURRENCY",
+	)]
+	pub fan_out: Option<NonZeroUsize>,
+
 	/// Time to wait for new events before taking action
 	///
 	/// When an event is received, Watchexec will wait for up to this amount of time before handling
@@ -253,6 +472,17 @@ pub struct Args {
 	)]
 	pub debounce: TimeSpan<1_000_000>,
 
+	/// Reduce the events of each run to their net effect
+	///
+	/// Without this, every event received during the '--debounce' window is given to the command
+	/// (see '--emit-events-to'), so a single save may show up as several events for the same file,
+	/// and a temporary file created then deleted still causes a run. With this, filesystem events
+	/// are reduced to one per path, for the net change to it: created, modified, removed, or
+	/// renamed; files which were only read are left out. If that leaves nothing, the command isn't
+	/// run at all.
+	#[arg(long)]
+	pub coalesce: bool,
+
 	/// Exit when stdin closes
 	///
 	/// This watches the stdin file descriptor for EOF, and exits Watchexec gracefully when it is
@@ -260,6 +490,48 @@ pub struct Args {
 	#[arg(long)]
 	pub stdin_quit: bool,
 
+	/// Enable keyboard shortcuts
+	///
+	/// This reads keys from stdin as they're pressed, putting the terminal in raw mode (on Unix),
+	/// and binds them to:
+	///
+	///   - 'r' to restart the command now, regardless of '--on-busy-update'
+	///   - 'c' to clear the screen
+	///   - 'p' to pause or resume reacting to changes
+	///   - 'q' to stop the command and quit
+	///   - Enter to run the command, as if something had changed
+	///
+	/// Other keys are ignored. Note that the command shares the terminal with Watchexec, so it
+	/// can't read from stdin reliably when this is enabled.
+	#[arg(long)]
+	pub interactive: bool,
+
+	/// Pause or resume reacting to changes when receiving this signal
+	///
+	/// By default, all signals Watchexec receives (other than those which make it quit) are passed
+	/// on to the command. With this, the given signal toggles pausing instead, and isn't passed on.
+	/// This is for example useful to stop reacting to changes during a 'git rebase', without losing
+	/// a running server. SIGUSR1 is a good candidate; see '--stop-signal' for the syntax.
+	///
+	/// While paused, the command is left alone. What happens to changes is controlled by
+	/// '--paused-events'.
+	#[arg(long, value_name = "SIGNAL")]
+	pub pause_signal: Option<Signal>,
+
+	/// What to do with changes while paused
+	///
+	/// Default is to 'drop' them, such that nothing happens when resuming. With 'accumulate', the
+	/// changes are kept, and the command is run once for all of them when resuming.
+	///
+	/// Pausing is done with '--pause-signal', or with the 'p' key with '--interactive'.
+	#[arg(
+		long,
+		default_value = "drop",
+		hide_default_value = true,
+		value_name = "MODE"
+	)]
+	pub paused_events: PausedEvents,
+
 	/// Don't load gitignores
 	///
 	/// Among other VCS exclude files, like for Mercurial, Subversion, Bazaar, DARCS, Fossil. Note
@@ -365,6 +637,32 @@ pub struct Args {
 	#[arg(long, value_name = "DURATION")]
 	pub delay_run: Option<TimeSpan>,
 
+	/// Also run the command periodically
+	///
+	/// This runs the command every DURATION, even if nothing changed. The interval starts when
+	/// Watchexec starts, and runs are treated like those for a change: for example, if the command
+	/// is still running when it's time to run it again, '--on-busy-update' applies.
+	///
+	/// Takes a unit-less value in seconds, or a time span value such as "5m" or "1h 30m".
+	#[arg(long, value_name = "DURATION")]
+	pub interval: Option<TimeSpan>,
+
+	/// Also run the command on a cron schedule
+	///
+	/// This runs the command at the times given by a cron expression, in local time, even if
+	/// nothing changed. The usual five fields (minute, hour, day of month, month, day of week) are
+	/// accepted, as well as six or seven fields with seconds first and the year last, and the
+	/// "@hourly", "@daily", "@weekly", "@monthly", and "@yearly" shorthands. With five fields, days
+	/// of the week are numbered as in crontab, from 0 (or 7) for Sunday to 6 for Saturday; with six
+	/// or seven fields, they're numbered from 1 for Sunday to 7 for Saturday. Names, like
+	/// "MON-FRI", work with either.
+	///
+	/// For example, "*/15 9-17 * * MON-FRI" runs every 15 minutes during office hours.
+	///
+	/// This option can be specified multiple times to use several schedules.
+	#[arg(long, value_name = "EXPRESSION")]
+	pub cron: Vec<Schedule>,
+
 	/// Poll for filesystem changes
 	///
 	/// By default, and where available, Watchexec uses the operating system's native file system
@@ -385,6 +683,45 @@ pub struct Args {
 	)]
 	pub poll: Option<TimeSpan<1_000_000>>,
 
+	/// Fall back to polling if the native watcher runs out of resources
+	///
+	/// On Linux, watching large trees can exceed the system's limits on inotify watches or
+	/// instances. By default, what couldn't be watched is reported as an error and left unwatched.
+	/// With this, it's polled instead, every 30 seconds, and a warning is printed.
+	///
+	/// With 'failed' (the default if no value is given), each watched path ('-w' or '-W') which
+	/// couldn't be watched natively is polled as a whole, and the others are still watched
+	/// natively. With 'all', everything is polled, as with '--poll'.
+	#[arg(
+		long,
+		num_args = 0..=1,
+		default_missing_value = "failed",
+		value_name = "SCOPE",
+	)]
+	pub poll_fallback: Option<PollFallback>,
+
+	/// Ignore writes which don't change the contents of files
+	///
+	/// Formatters, 'touch', and some editors rewrite files with the same contents they had. With
+	/// this, Watchexec remembers a hash of the contents of each file it sees modified, and ignores
+	/// modifications which leave the contents and permissions unchanged. The first modification of
+	/// each file after Watchexec starts always goes through, as there's nothing to compare it to.
+	/// Files larger than 16 MiB are not hashed.
+	///
+	/// To avoid reading files again, the size and modification time of files are trusted: if both
+	/// are unchanged, and the file wasn't modified in the last couple seconds, the contents are
+	/// assumed to be unchanged without hashing them.
+	///
+	/// Optionally takes the maximum number of files to remember. If not specified, the default is
+	/// 10000. The least recently modified files are forgotten first.
+	#[arg(
+		long,
+		num_args = 0..=1,
+		default_missing_value = "10000",
+		value_name = "FILES",
+	)]
+	pub fingerprint: Option<usize>,
+
 	/// Use a different shell
 	///
 	/// By default, Watchexec will use 'sh' on unix and 'cmd' (CMD.EXE) on Windows. With this, you
@@ -473,14 +810,13 @@ pub struct Args {
 	/// Watchexec emits event information when running a command, which can be used by the command
 	/// to target specific changed files.
 	///
-	/// One thing to take care with is assuming inherent behaviour where there is only chance.
-	/// Notably, it could appear as if the `RENAMED` variable contains both the original and the new
-	/// path being renamed. In previous versions, it would even appear on some platforms as if the
-	/// original always came before the new. However, none of this was true. It's impossible to
-	/// reliably and portably know which changed path is the old or new, "half" renames may appear
-	/// (only the original, only the new), "unknown" renames may appear (change was a rename, but
-	/// whether it was the old or new isn't known), rename events might split across two debouncing
-	/// boundaries, and so on.
+	/// One thing to take care with is renames. Where the platform reports both halves of a rename
+	/// (like inotify on Linux, and Windows), Watchexec pairs them up, and says which path is the
+	/// original and which is the new one. Otherwise, the `RENAMED` variable may look like it has
+	/// both the original and the new path, but it's impossible to reliably and portably know which
+	/// is which: "half" renames may appear (only the original, only the new), "unknown" renames
+	/// may appear (change was a rename, but whether it was the old or new isn't known), rename
+	/// events might split across two debouncing boundaries, and so on.
 	///
 	/// This option controls where that information is emitted. It defaults to 'environment', which
 	/// sets environment variables with the paths of the affected files, for filesystem events:
@@ -491,10 +827,18 @@ pub struct Args {
 	///   - $WATCHEXEC_CREATED_PATH is set when files/folders were created
 	///   - $WATCHEXEC_REMOVED_PATH is set when files/folders were removed
 	///   - $WATCHEXEC_RENAMED_PATH is set when files/folders were renamed
+	///   - $WATCHEXEC_RENAMED_FROM_PATH and $WATCHEXEC_RENAMED_TO_PATH are set to the original and
+	///     new paths of renames which were paired up; these are in the same order, rather than
+	///     sorted, and the nth path of one goes with the nth path of the other
 	///   - $WATCHEXEC_WRITTEN_PATH is set when files/folders were modified
 	///   - $WATCHEXEC_META_CHANGED_PATH is set when files/folders' metadata were modified
 	///   - $WATCHEXEC_OTHERWISE_CHANGED_PATH is set for every other kind of pathed event
 	///
+	/// For '--on-success' and '--on-failure' hooks, the completion of the main command is also
+	/// given: $WATCHEXEC_COMPLETION_DISPOSITION is set to how it ended ('success', 'error',
+	/// 'signal', 'stop', 'exception', 'continued'), and $WATCHEXEC_COMPLETION_CODE to the exit,
+	/// signal, stop, or exception code, if any.
+	///
 	/// Multiple paths are separated by the system path separator, ';' on Windows and ':' on unix.
 	/// Within each variable, paths are deduplicated and sorted in binary order (i.e. neither
 	/// Unicode nor locale aware).
@@ -506,8 +850,11 @@ pub struct Args {
 	///
 	/// Two new modes are available: 'stdin' writes absolute paths to the stdin of the command,
 	/// one per line, each prefixed with `create:`, `remove:`, `rename:`, `modify:`, or `other:`,
+	/// with paired renames as a `rename-from:` line followed by a `rename-to:` line,
 	/// then closes the handle; 'file' writes the same thing to a temporary file, and its path is
-	/// given with the $WATCHEXEC_EVENTS_FILE environment variable.
+	/// given with the $WATCHEXEC_EVENTS_FILE environment variable. For hooks, the completion of
+	/// the main command is given as a `completion:` line, with its disposition and code (if any)
+	/// separated by a colon, like `completion:error:1`.
 	///
 	/// There are also two JSON modes, which are based on JSON objects and can represent the full
 	/// set of events Watchexec handles. Here's an example of a folder being created on Linux:
@@ -543,13 +890,17 @@ pub struct Args {
 	///     * 'path', along with:
 	///       + `absolute`, an absolute path.
 	///       + `filetype`, a file type if known ('dir', 'file', 'symlink', 'other').
+	///     * 'rename', for renames where the original and new paths are known, alongside a 'path'
+	///       for each:
+	///       + `from`, the original path.
+	///       + `to`, the new path.
 	///     * 'fs':
 	///       + `simple`, the "simple" event type ('access', 'create', 'modify', 'remove', or 'other').
 	///       + `full`, the "full" event type, which is too complex to fully describe here, but looks like 'General(Precise(Specific))'.
 	///     * 'source', along with:
 	///       + `source`, the source of the event ('filesystem', 'keyboard', 'mouse', 'os', 'time', 'internal').
 	///     * 'keyboard', along with:
-	///       + `keycode`. Currently only the value 'eof' is supported.
+	///       + `keycode`, either 'eof', or a 'key' object for key presses with '--interactive'.
 	///     * 'process', for events caused by processes:
 	///       + `pid`, the process ID.
 	///     * 'signal', for signals sent to Watchexec:
@@ -557,6 +908,22 @@ pub struct Args {
 	///     * 'completion', for when a command ends:
 	///       + `disposition`, the exit disposition ('success', 'error', 'signal', 'stop', 'exception', 'continued').
 	///       + `code`, the exit, signal, stop, or exception code.
+	///     * 'timeout', alongside a 'completion', when the command was stopped by '--timeout'.
+	///     * 'ready', when the command became ready (see '--ready').
+	///     * 'usage', for the resource usage of a command, alongside its 'completion':
+	///       + `wall`, how long it ran for, in seconds.
+	///       + `user`, the CPU time spent in user mode, in seconds (if available).
+	///       + `system`, the CPU time spent in kernel mode, in seconds (if available).
+	///       + `maxrss`, the peak memory usage, in bytes (if available).
+	///     * 'output', for a line of output from a command (not used by the Watchexec CLI itself):
+	///       + `stream`, the stream it was printed to ('stdout' or 'stderr').
+	///       + `line`, the line of output.
+	///     * 'set-completion', for when the last command of a set ends:
+	///       + `step`, the index of the command which ended the set.
+	///     * 'job', for events about a particular job (not used by the Watchexec CLI itself):
+	///       + `job`, the name of the job.
+	///     * 'schedule', for events from '--interval' or '--cron':
+	///       + `schedule`, the schedule which fired, like "every 300s" or the cron expression.
 	///   - `metadata`, additional information about the event.
 	///
 	/// The 'json-stdin' mode will emit JSON events to the standard input of the command, one per
@@ -589,6 +956,27 @@ pub struct Args {
 	)]
 	pub env: Vec<String>,
 
+	/// Add env vars to the command from a dotenv file
+	///
+	/// The file is parsed with dotenv syntax: 'KEY=value' lines, with optional 'export' prefixes,
+	/// single or double quoting, '#' comments, and interpolation of '$VAR' or '${VAR}' from
+	/// earlier in the file or from Watchexec's own environment. Variables from other env files
+	/// can't be interpolated.
+	///
+	/// Multiple files can be given by repeating the option, and later files override earlier
+	/// ones. Variables given with '--env' override those from files.
+	///
+	/// The files are watched, even if they're outside of the watched paths or ignored, and are
+	/// re-read before each run of the command. When one changes, the command is restarted so that
+	/// it picks up the new environment, regardless of '--on-busy-update'.
+	#[arg(
+		long,
+		help_heading = OPTSET_COMMAND,
+		value_hint = ValueHint::FilePath,
+		value_name = "PATH",
+	)]
+	pub env_file: Vec<PathBuf>,
+
 	/// Don't use a process group
 	///
 	/// By default, Watchexec will run the command in a process group, so that signals and
@@ -611,6 +999,67 @@ pub struct Args {
 	#[arg(long, short = 'N')]
 	pub notify: bool,
 
+	/// Print how long commands ran for
+	///
+	/// With this, Watchexec will print a message when a command ends, whether it was successful or
+	/// not, with how long it ran for and, on supported platforms, its peak memory usage.
+	#[arg(long, help_heading = OPTSET_COMMAND)]
+	pub timings: bool,
+
+	/// Print the last lines of output of commands which fail
+	///
+	/// With this, the output of commands is captured as well as being printed as usual, and when a
+	/// command fails, the last LINES lines of its output are printed again after the failure
+	/// message. This is useful when the error has scrolled out of view behind a lot of output.
+	///
+	/// If several commands ran (with '--before' or '--fan-out'), this is the output of the last one
+	/// to start.
+	#[arg(long, help_heading = OPTSET_COMMAND, value_name = "LINES")]
+	pub print_failure_tail: Option<NonZeroUsize>,
+
+	/// Run a command before each run of the main command
+	///
+	/// The hook is run the same way as the main command (with the same shell, environment, events,
+	/// etc), just before it. If it fails, the main command isn't run. This can be given multiple
+	/// times to run several hooks in order.
+	///
+	/// Without a shell ('--shell=none'), hooks are split into words with the quoting rules of
+	/// POSIX shells, like "notify-send 'build ok'".
+	#[arg(
+		long,
+		help_heading = OPTSET_COMMAND,
+		value_name = "COMMAND",
+	)]
+	pub before: Vec<String>,
+
+	/// Run a command after the main command succeeds
+	///
+	/// The hook is run the same way as the main command (with the same shell, environment, etc),
+	/// without replacing it. It's given the events which triggered the main command, along with
+	/// the completion of the main command, through the '--emit-events-to' mechanism. This can be
+	/// given multiple times to run several hooks in order.
+	///
+	/// Options about how the main command runs, like '--auto-restart', '--ready', and '--fan-out',
+	/// don't apply to this hook.
+	#[arg(
+		long,
+		help_heading = OPTSET_COMMAND,
+		value_name = "COMMAND",
+	)]
+	pub on_success: Vec<String>,
+
+	/// Run a command after the main command fails
+	///
+	/// This is like '--on-success', but for when the main command (or a '--before' hook) ends
+	/// unsuccessfully: with a non-zero exit status, killed by a signal, etc. Note that this
+	/// includes when the command is stopped by Watchexec itself, like when restarting it.
+	#[arg(
+		long,
+		help_heading = OPTSET_COMMAND,
+		value_name = "COMMAND",
+	)]
+	pub on_failure: Vec<String>,
+
 	/// Set the project origin
 	///
 	/// Watchexec will attempt to discover the project's "origin" (or "root") by searching for a
@@ -843,6 +1292,25 @@ pub enum OnBusyUpdate {
 	Signal,
 }
 
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum PausedEvents {
+	#[default]
+	Drop,
+	Accumulate,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum PollFallback {
+	Failed,
+	All,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum AutoRestart {
+	Failure,
+	Exit,
+}
+
 #[derive(Clone, Copy, Debug, Default, ValueEnum)]
 pub enum ClearMode {
 	#[default]
@@ -885,6 +1353,56 @@ impl<const UNITLESS_NANOS_MULTIPLIER: u64> FromStr for TimeSpan<UNITLESS_NANOS_M
 	}
 }
 
+#[derive(Clone, Debug)]
+pub enum ReadyProbe {
+	Port(u16),
+	File(PathBuf),
+	Output(Regex),
+}
+
+impl FromStr for ReadyProbe {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.split_once(':') {
+			Some(("port", port)) => port
+				.parse()
+				.map(Self::Port)
+				.map_err(|err| format!("invalid port: {err}")),
+			Some(("file", path)) if !path.is_empty() => Ok(Self::File(path.into())),
+			Some(("output", pattern)) => Regex::new(pattern)
+				.map(Self::Output)
+				.map_err(|err| format!("invalid pattern: {err}")),
+			_ => Err("expected 'port:PORT', 'file:PATH', or 'output:REGEX'".into()),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct EscalationStep {
+	pub signal: Signal,
+	pub timeout: Option<TimeSpan>,
+}
+
+impl FromStr for EscalationStep {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (signal, timeout) = match s.split_once(':') {
+			Some((signal, timeout)) => (signal, Some(timeout)),
+			None => (s, None),
+		};
+
+		Ok(Self {
+			signal: signal.trim().parse().map_err(|err| format!("{err}"))?,
+			timeout: timeout
+				.map(|t| t.trim().parse())
+				.transpose()
+				.map_err(|err| format!("{err}"))?,
+		})
+	}
+}
+
 #[inline]
 pub fn get_args() -> Args {
 	use tracing::{debug, warn};
@@ -38,6 +38,15 @@ pub fn init(_args: &Args) -> InitConfig {
 				return Ok(());
 			}
 
+			if let RuntimeError::FsWatcher {
+				err: err @ FsWatcherError::PollFallback { .. },
+				..
+			} = &err.error
+			{
+				eprintln!("[[Warning: {err}]]");
+				return Ok(());
+			}
+
 			if cfg!(debug_assertions) {
 				eprintln!("[[{:?}]]", err.error);
 			}
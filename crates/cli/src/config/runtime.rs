@@ -1,6 +1,14 @@
 use std::{
-	collections::HashMap, convert::Infallible, env::current_dir, ffi::OsString, fs::File,
+	cell::Cell,
+	collections::HashMap,
+	convert::Infallible,
+	env::current_dir,
+	ffi::OsString,
+	fs::File,
+	path::PathBuf,
 	process::Stdio,
+	sync::{Arc, Mutex},
+	time::Duration,
 };
 
 use miette::{miette, IntoDiagnostic, Result};
@@ -8,49 +16,152 @@ use notify_rust::Notification;
 use tracing::{debug, debug_span, error};
 use watchexec::{
 	action::{Action, Outcome, PostSpawn, PreSpawn},
-	command::{Command, Shell},
+	command::{Command, OutputBuffer, Readiness, RestartOn, RestartPolicy, Shell, Step},
 	config::RuntimeConfig,
 	error::RuntimeError,
-	fs::Watcher,
+	fs::{Fallback, WatchedPath, Watcher},
 	handler::SyncFnHandler,
+	timer::Schedule,
 };
-use watchexec_events::{Event, Keyboard, ProcessEnd, Tag};
+use watchexec_events::{Event, Key, Keyboard, ProcessEnd, Tag};
 use watchexec_signals::Signal;
 
-use crate::args::{Args, ClearMode, EmitEvents, OnBusyUpdate};
+use crate::args::{
+	Args, AutoRestart, ClearMode, EmitEvents, OnBusyUpdate, PausedEvents, PollFallback, ReadyProbe,
+	TimeSpan,
+};
 use crate::state::State;
 
+const HOOK_ON_SUCCESS: &str = "on-success";
+const HOOK_ON_FAILURE: &str = "on-failure";
+
 pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 	let _span = debug_span!("args-runtime").entered();
 	let mut config = RuntimeConfig::default();
 
-	config.command(interpret_command_args(args)?);
-
-	config.pathset(if args.paths.is_empty() {
-		vec![current_dir().into_diagnostic()?]
+	let mut commands: Vec<Step> = args
+		.before
+		.iter()
+		.map(|hook| interpret_hook(args, hook).map(Step::on_success))
+		.collect::<Result<_>>()?;
+	commands.push(Step::on_success(interpret_command_args(args)?));
+	config.commands(commands);
+
+	for (job, hooks) in [
+		(HOOK_ON_SUCCESS, &args.on_success),
+		(HOOK_ON_FAILURE, &args.on_failure),
+	] {
+		if !hooks.is_empty() {
+			config.job(
+				job,
+				hooks
+					.iter()
+					.map(|hook| interpret_hook(args, hook).map(Step::always))
+					.collect::<Result<Vec<_>>>()?,
+			);
+		}
+	}
+	let on_success = !args.on_success.is_empty();
+	let on_failure = !args.on_failure.is_empty();
+
+	let watched = |path: PathBuf| match args.watch_depth {
+		Some(depth) => WatchedPath::max_depth(path, depth as usize),
+		None => WatchedPath::recursive(path),
+	};
+	let mut pathset = if args.paths.is_empty() && args.paths_non_recursive.is_empty() {
+		vec![watched(current_dir().into_diagnostic()?)]
 	} else {
-		args.paths.clone()
-	});
+		args.paths.iter().cloned().map(watched).collect()
+	};
+	pathset.extend(
+		args.paths_non_recursive
+			.iter()
+			.cloned()
+			.map(WatchedPath::non_recursive),
+	);
+	pathset.extend(state.env_files.paths().iter().map(WatchedPath::from));
+	config.pathset(pathset);
 
 	config.action_throttle(args.debounce.0);
+	config.action_coalesce(args.coalesce);
 	config.command_grouped(!args.no_process_group);
+	config.command_restart(args.auto_restart.map(|when| {
+		let policy = RestartPolicy::new(match when {
+			AutoRestart::Failure => RestartOn::Failure,
+			AutoRestart::Exit => RestartOn::Exit,
+		})
+		.backoff(args.auto_restart_delay.0, args.auto_restart_max_delay.0)
+		.reset_after(args.auto_restart_reset.0);
+		if let Some(retries) = args.auto_restart_retries {
+			policy.max_retries(retries)
+		} else {
+			policy
+		}
+	}));
+	config.command_readiness(args.ready.clone().map(|probe| match probe {
+		ReadyProbe::Port(port) => Readiness::Port(port),
+		ReadyProbe::File(path) => Readiness::File(path),
+		ReadyProbe::Output(pattern) => Readiness::Output(pattern),
+	}));
+	config.command_ready_timeout(Some(args.ready_timeout.0).filter(|timeout| !timeout.is_zero()));
+	config.command_fan_out(args.fan_out);
+	config.command_capture(args.print_failure_tail);
+	config.paused_events(match args.paused_events {
+		PausedEvents::Drop => watchexec::action::PausedEvents::Drop,
+		PausedEvents::Accumulate => watchexec::action::PausedEvents::Accumulate,
+	});
 	config.keyboard_emit_eof(args.stdin_quit);
+	config.keyboard_emit_keys(args.interactive);
+
+	let mut schedules = args.cron.clone();
+	if let Some(TimeSpan(interval)) = args.interval {
+		if interval.is_zero() {
+			return Err(miette!("--interval must not be zero"));
+		}
+		schedules.push(Schedule::interval(interval));
+	}
+	config.timer_schedules(schedules);
 
 	if let Some(interval) = args.poll {
 		config.file_watcher(Watcher::Poll(interval.0));
 	}
 
+	// the same as the default of --poll
+	let fallback_interval = Duration::from_secs(30);
+	config.file_watcher_fallback(match args.poll_fallback {
+		None => Fallback::Off,
+		Some(PollFallback::Failed) => Fallback::PollFailed(fallback_interval),
+		Some(PollFallback::All) => Fallback::PollAll(fallback_interval),
+	});
+
+	config.file_fingerprint(args.fingerprint);
+
 	let clear = args.screen_clear;
 	let notif = args.notify;
+	let timings = args.timings;
+	// the output of the last process of the command to start, for --print-failure-tail
+	let tail: Arc<Mutex<Option<OutputBuffer>>> = Arc::default();
+	let tail_c = tail.clone();
 	let on_busy = args.on_busy_update;
 
 	let signal = args.signal;
 	let stop_signal = args.stop_signal;
 	let stop_timeout = args.stop_timeout.0;
+	let timeout = args.timeout.map(|ts| ts.0);
+	let escalation: Vec<(Signal, Duration)> = if args.stop_escalation.is_empty() {
+		vec![(stop_signal.unwrap_or(Signal::Terminate), stop_timeout)]
+	} else {
+		args.stop_escalation
+			.iter()
+			.map(|step| (step.signal, step.timeout.map_or(stop_timeout, |ts| ts.0)))
+			.collect()
+	};
 
 	let print_events = args.print_events;
 	let once = args.once;
 	let delay_run = args.delay_run.map(|ts| ts.0);
+	let env_files = state.env_files.clone();
+	let pause_signal = args.pause_signal;
 
 	config.on_action(move |action: Action| {
 		let fut = async { Ok::<(), Infallible>(()) };
@@ -61,13 +172,21 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 			}
 		}
 
+		let with_timeout = |start: Outcome| {
+			if let Some(after) = timeout {
+				Outcome::both(start, Outcome::timeout(after, escalation.clone()))
+			} else {
+				start
+			}
+		};
+
 		if once {
 			action.outcome(Outcome::both(
-				if let Some(delay) = &delay_run {
+				with_timeout(if let Some(delay) = &delay_run {
 					Outcome::both(Outcome::Sleep(*delay), Outcome::Start)
 				} else {
 					Outcome::Start
-				},
+				}),
 				Outcome::wait(Outcome::Exit),
 			));
 			return fut;
@@ -96,41 +215,138 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 			return fut;
 		}
 
+		let start = if let Some(mode) = clear {
+			Outcome::both(
+				match mode {
+					ClearMode::Clear => Outcome::Clear,
+					ClearMode::Reset => Outcome::Reset,
+				},
+				Outcome::Start,
+			)
+		} else {
+			Outcome::Start
+		};
+
+		let start = with_timeout(if let Some(delay) = &delay_run {
+			Outcome::both(Outcome::Sleep(*delay), start)
+		} else {
+			start
+		});
+
+		let paused = Cell::new(action.is_paused());
+		let toggle_pause = || {
+			if paused.replace(!paused.get()) {
+				eprintln!("[[Resumed]]");
+				Outcome::Resume
+			} else {
+				eprintln!("[[Paused]]");
+				Outcome::Pause
+			}
+		};
+
+		let stop = if cfg!(windows) {
+			Outcome::Stop
+		} else {
+			Outcome::escalate(escalation.clone())
+		};
+
 		if !has_paths {
 			if !signals.is_empty() {
 				let mut out = Outcome::DoNothing;
 				for sig in signals {
-					out = Outcome::both(out, Outcome::Signal(sig));
+					out = Outcome::both(
+						out,
+						if Some(sig) == pause_signal {
+							toggle_pause()
+						} else {
+							Outcome::Signal(sig)
+						},
+					);
 				}
 
 				action.outcome(out);
 				return fut;
 			}
 
-			let completion = action.events.iter().flat_map(Event::completions).next();
-			if let Some(status) = completion {
-				let (msg, printit) = match status {
-					Some(ProcessEnd::ExitError(code)) => {
-						(format!("Command exited with {code}"), true)
-					}
-					Some(ProcessEnd::ExitSignal(sig)) => {
-						(format!("Command killed by {sig:?}"), true)
-					}
-					Some(ProcessEnd::ExitStop(sig)) => {
-						(format!("Command stopped by {sig:?}"), true)
+			let keys: Vec<Key> = action.events.iter().flat_map(Event::keys).collect();
+			if keys.contains(&Key::Char('q')) {
+				action.outcome(Outcome::both(Outcome::Stop, Outcome::Exit));
+				return fut;
+			}
+
+			// Enter runs the command as for a change, other keys are handled here, along with
+			// whatever else came in with them
+			let keyed = !keys.is_empty() && !keys.contains(&Key::Enter);
+			let mut key_outcome = Outcome::DoNothing;
+			for key in keys.into_iter().filter(|_| keyed) {
+				key_outcome = match key {
+					Key::Char('r') => Outcome::both(
+						key_outcome,
+						Outcome::if_running(
+							Outcome::both(stop.clone(), start.clone()),
+							start.clone(),
+						),
+					),
+					Key::Char('c') => Outcome::both(key_outcome, Outcome::Clear),
+					Key::Char('p') => Outcome::both(key_outcome, toggle_pause()),
+					_ => key_outcome,
+				};
+			}
+
+			let mut hook_ended = false;
+			for event in action.events.iter() {
+				if let Some(job) = event.jobs().next() {
+					for status in event.completions() {
+						hook_ended = true;
+						if let (msg, true) = completion_message(status) {
+							eprintln!("[[{job} hook: {msg}]]");
+						}
 					}
-					Some(ProcessEnd::Continued) => ("Command continued".to_string(), true),
-					Some(ProcessEnd::Exception(ex)) => {
-						(format!("Command ended by exception {ex:#x}"), true)
+				}
+			}
+
+			// with --before hooks, the end of the set is what matters, not that of its first step
+			let main_events = || action.events.iter().filter(|e| e.jobs().next().is_none());
+			let set_end = main_events().find(|e| {
+				e.tags
+					.iter()
+					.any(|t| matches!(t, Tag::SetCompletion { .. }))
+			});
+			let end_event =
+				set_end.or_else(|| main_events().find(|e| e.completions().next().is_some()));
+			if let Some(end_event) = end_event {
+				let status = end_event.completions().next().flatten();
+				let (mut msg, mut printit) = completion_message(status);
+
+				if main_events().any(|e| e.tags.contains(&Tag::Timeout)) {
+					msg = "Command timed out".to_string();
+					printit = true;
+				}
+
+				let usage = end_event.usages().next();
+				if let (true, Some(usage)) = (timings, usage) {
+					msg.push_str(&format!(" in {:.1?}", usage.wall_time));
+					if let Some(rss) = usage.max_rss {
+						msg.push_str(&format!(", {}MB max RSS", rss / 1_000_000));
 					}
-					Some(ProcessEnd::Success) => ("Command was successful".to_string(), false),
-					None => ("Command completed".to_string(), false),
-				};
+				}
 
-				if printit {
+				if printit || timings {
 					eprintln!("[[{msg}]]");
 				}
 
+				let failed = status.map_or(false, |end| end != ProcessEnd::Success);
+				let output = tail.lock().unwrap_or_else(|err| err.into_inner()).take();
+				if let (true, Some(output)) = (failed, output) {
+					let lines = output.lines();
+					if !lines.is_empty() {
+						eprintln!("[[Last {} lines of output:]]", lines.len());
+						for (_, line) in lines {
+							eprintln!("{line}");
+						}
+					}
+				}
+
 				if notif {
 					Notification::new()
 						.summary("Watchexec: command ended")
@@ -144,39 +360,55 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 						);
 				}
 
-				action.outcome(Outcome::DoNothing);
+				let set_end = set_end.map(|_| status);
+				action.outcome(Outcome::both(
+					key_outcome,
+					match set_end {
+						Some(Some(ProcessEnd::Success)) if on_success => {
+							Outcome::job(HOOK_ON_SUCCESS, Outcome::Start)
+						}
+						Some(Some(end)) if on_failure && end != ProcessEnd::Success => {
+							Outcome::job(HOOK_ON_FAILURE, Outcome::Start)
+						}
+						_ => Outcome::DoNothing,
+					},
+				));
 				return fut;
 			}
-		}
 
-		let start = if let Some(mode) = clear {
-			Outcome::both(
-				match mode {
-					ClearMode::Clear => Outcome::Clear,
-					ClearMode::Reset => Outcome::Reset,
-				},
-				Outcome::Start,
-			)
-		} else {
-			Outcome::Start
-		};
+			if action
+				.events
+				.iter()
+				.any(|e| e.tags.contains(&Tag::ProcessReady))
+			{
+				eprintln!("[[Command ready]]");
+				action.outcome(key_outcome);
+				return fut;
+			}
 
-		let start = if let Some(delay) = &delay_run {
-			Outcome::both(Outcome::Sleep(*delay), start)
+			// captured output is only kept for --print-failure-tail, it's not a change
+			let only_output = action.events.iter().all(|e| e.output().next().is_some());
+			if keyed || hook_ended || only_output {
+				action.outcome(key_outcome);
+				return fut;
+			}
+		}
+
+		// the environment is only applied to new processes, so running ones must be restarted
+		let env_changed = action
+			.events
+			.iter()
+			.flat_map(Event::paths)
+			.any(|(path, _)| env_files.contains(path));
+		let on_busy = if env_changed {
+			OnBusyUpdate::Restart
 		} else {
-			start
+			on_busy
 		};
 
 		let when_idle = start.clone();
 		let when_running = match on_busy {
-			OnBusyUpdate::Restart if cfg!(windows) => Outcome::both(Outcome::Stop, start),
-			OnBusyUpdate::Restart => Outcome::both(
-				Outcome::both(
-					Outcome::Signal(stop_signal.unwrap_or(Signal::Terminate)),
-					Outcome::wait_timeout(stop_timeout, Outcome::Stop),
-				),
-				start,
-			),
+			OnBusyUpdate::Restart => Outcome::both(stop, start),
 			OnBusyUpdate::Signal if cfg!(windows) => Outcome::Stop,
 			OnBusyUpdate::Signal => {
 				Outcome::Signal(stop_signal.or(signal).unwrap_or(Signal::Terminate))
@@ -208,18 +440,40 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 
 	let emit_events_to = args.emit_events_to;
 	let emit_file = state.emit_file.clone();
+	let env_files = state.env_files.clone();
+	let main_events: Arc<Mutex<Arc<[Event]>>> = Arc::new(Mutex::new(Arc::new([])));
 	config.on_pre_spawn(move |prespawn: PreSpawn| {
 		use crate::emits::*;
 
 		let workdir = workdir.clone();
-		let mut add_envs = add_envs.clone();
+		// variables from --env override those from --env-file
+		let mut add_envs: HashMap<_, _> = env_files
+			.load()
+			.into_iter()
+			.chain(add_envs.clone())
+			.collect();
 		let mut stdin = None;
 
+		// hooks are given the events which triggered the main command along with its completion
+		let events: Arc<[Event]> = {
+			let mut main_events = main_events.lock().unwrap_or_else(|err| err.into_inner());
+			if prespawn.job.is_default() {
+				*main_events = prespawn.events.clone();
+				prespawn.events.clone()
+			} else {
+				main_events
+					.iter()
+					.chain(prespawn.events.iter())
+					.cloned()
+					.collect()
+			}
+		};
+
 		match emit_events_to {
 			EmitEvents::Environment => {
-				add_envs.extend(emits_to_environment(&prespawn.events));
+				add_envs.extend(emits_to_environment(&events));
 			}
-			EmitEvents::Stdin => match emits_to_file(&emit_file, &prespawn.events)
+			EmitEvents::Stdin => match emits_to_file(&emit_file, &events)
 				.and_then(|path| File::open(path).into_diagnostic())
 			{
 				Ok(file) => {
@@ -229,7 +483,7 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 					error!("Failed to write events to stdin, continuing without it: {err}");
 				}
 			},
-			EmitEvents::File => match emits_to_file(&emit_file, &prespawn.events) {
+			EmitEvents::File => match emits_to_file(&emit_file, &events) {
 				Ok(path) => {
 					add_envs.insert("WATCHEXEC_EVENTS_FILE".into(), path.into());
 				}
@@ -237,7 +491,7 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 					error!("Failed to write WATCHEXEC_EVENTS_FILE, continuing without it: {err}");
 				}
 			},
-			EmitEvents::JsonStdin => match emits_to_json_file(&emit_file, &prespawn.events)
+			EmitEvents::JsonStdin => match emits_to_json_file(&emit_file, &events)
 				.and_then(|path| File::open(path).into_diagnostic())
 			{
 				Ok(file) => {
@@ -247,7 +501,7 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 					error!("Failed to write events to stdin, continuing without it: {err}");
 				}
 			},
-			EmitEvents::JsonFile => match emits_to_json_file(&emit_file, &prespawn.events) {
+			EmitEvents::JsonFile => match emits_to_json_file(&emit_file, &events) {
 				Ok(path) => {
 					add_envs.insert("WATCHEXEC_EVENTS_FILE".into(), path.into());
 				}
@@ -283,6 +537,10 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 	});
 
 	config.on_post_spawn(SyncFnHandler::from(move |postspawn: PostSpawn| {
+		if postspawn.job.is_default() && postspawn.output.is_some() {
+			*tail_c.lock().unwrap_or_else(|err| err.into_inner()) = postspawn.output.clone();
+		}
+
 		if notif {
 			Notification::new()
 				.summary("Watchexec: change detected")
@@ -302,12 +560,102 @@ pub fn runtime(args: &Args, state: &State) -> Result<RuntimeConfig> {
 	Ok(config)
 }
 
+fn completion_message(status: Option<ProcessEnd>) -> (String, bool) {
+	match status {
+		Some(ProcessEnd::ExitError(code)) => (format!("Command exited with {code}"), true),
+		Some(ProcessEnd::ExitSignal(sig)) => (format!("Command killed by {sig:?}"), true),
+		Some(ProcessEnd::ExitStop(sig)) => (format!("Command stopped by {sig:?}"), true),
+		Some(ProcessEnd::Continued) => ("Command continued".to_string(), true),
+		Some(ProcessEnd::Exception(ex)) => (format!("Command ended by exception {ex:#x}"), true),
+		Some(ProcessEnd::Success) => ("Command was successful".to_string(), false),
+		None => ("Command completed".to_string(), false),
+	}
+}
+
 fn interpret_command_args(args: &Args) -> Result<Command> {
-	let mut cmd = args.command.clone();
+	let cmd = args.command.clone();
 	if cmd.is_empty() {
 		panic!("(clap) Bug: command is not present");
 	}
 
+	interpret_command(args, cmd)
+}
+
+fn interpret_hook(args: &Args, hook: &str) -> Result<Command> {
+	let exec = args.no_shell
+		|| args.no_shell_long
+		|| args
+			.shell
+			.as_ref()
+			.map_or(false, |s| s.eq_ignore_ascii_case("none"));
+
+	// shelled hooks are passed as-is, but without a shell they have to be split into words
+	let cmd: Vec<String> = if exec {
+		split_words(hook)?
+	} else {
+		vec![hook.to_string()]
+	};
+
+	if cmd.is_empty() {
+		return Err(miette!("hook command is empty"));
+	}
+
+	interpret_command(args, cmd)
+}
+
+/// Splits a command line into words, with the quoting rules of POSIX shells.
+///
+/// Single quotes keep everything between them as-is, double quotes let backslashes escape `"`, `\`,
+/// `$`, and `` ` ``, and elsewhere backslashes escape any character. There are no expansions.
+fn split_words(line: &str) -> Result<Vec<String>> {
+	let mut words = Vec::new();
+	let mut word: Option<String> = None;
+	let mut chars = line.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\'' => {
+				let word = word.get_or_insert_with(String::new);
+				loop {
+					match chars.next() {
+						Some('\'') => break,
+						Some(c) => word.push(c),
+						None => return Err(miette!("unterminated single quote in {line:?}")),
+					}
+				}
+			}
+			'"' => {
+				let word = word.get_or_insert_with(String::new);
+				loop {
+					match chars.next() {
+						Some('"') => break,
+						Some('\\') => match chars.next() {
+							Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+							Some('\n') => {}
+							Some(c) => {
+								word.push('\\');
+								word.push(c);
+							}
+							None => return Err(miette!("unterminated double quote in {line:?}")),
+						},
+						Some(c) => word.push(c),
+						None => return Err(miette!("unterminated double quote in {line:?}")),
+					}
+				}
+			}
+			'\\' => match chars.next() {
+				Some('\n') => {}
+				Some(c) => word.get_or_insert_with(String::new).push(c),
+				None => return Err(miette!("trailing backslash in {line:?}")),
+			},
+			c if c.is_ascii_whitespace() => words.extend(word.take()),
+			c => word.get_or_insert_with(String::new).push(c),
+		}
+	}
+	words.extend(word);
+	Ok(words)
+}
+
+fn interpret_command(args: &Args, mut cmd: Vec<String>) -> Result<Command> {
 	Ok(if args.no_shell || args.no_shell_long {
 		Command::Exec {
 			prog: cmd.remove(0),
@@ -0,0 +1,91 @@
+use std::{
+	collections::HashMap,
+	ffi::OsString,
+	fs::canonicalize,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+};
+
+use miette::{IntoDiagnostic, Result, WrapErr};
+use tracing::{debug, error, trace};
+
+/// The environment files given with `--env-file`.
+///
+/// These are re-read before each run of the command, so that changes are picked up. If a file
+/// can't be read or parsed at that point, the error is logged and the environment from the last
+/// successful read is used.
+///
+/// Interpolation within a file only sees the variables from earlier in that file and from
+/// Watchexec's own environment, not those from the other files.
+#[derive(Clone, Debug)]
+pub struct EnvFiles {
+	paths: Arc<[PathBuf]>,
+	last: Arc<Mutex<HashMap<String, OsString>>>,
+}
+
+impl EnvFiles {
+	/// Resolves the paths to the files and reads them a first time, which must succeed.
+	pub fn new(paths: &[PathBuf]) -> Result<Self> {
+		let paths = paths
+			.iter()
+			.map(|path| {
+				canonicalize(path)
+					.into_diagnostic()
+					.wrap_err_with(|| format!("env file {}", path.display()))
+			})
+			.collect::<Result<Vec<_>>>()?;
+
+		let files = Self {
+			paths: paths.into(),
+			last: Default::default(),
+		};
+
+		let env = files.read()?;
+		*files.last.lock().unwrap() = env;
+		Ok(files)
+	}
+
+	/// The (canonical) paths to the files.
+	pub fn paths(&self) -> &[PathBuf] {
+		&self.paths
+	}
+
+	/// Whether this path is one of the files.
+	pub fn contains(&self, path: &Path) -> bool {
+		self.paths.iter().any(|p| p == path)
+	}
+
+	/// Re-reads the files, falling back to the last environment read if that fails.
+	pub fn load(&self) -> HashMap<String, OsString> {
+		let mut last = self.last.lock().unwrap();
+		match self.read() {
+			Ok(env) => {
+				*last = env;
+			}
+			Err(err) => {
+				error!("Failed to reload env files, using the previous environment: {err:?}");
+			}
+		}
+
+		last.clone()
+	}
+
+	fn read(&self) -> Result<HashMap<String, OsString>> {
+		let mut env = HashMap::new();
+		// later files override earlier ones
+		for path in self.paths.iter() {
+			debug!(?path, "reading env file");
+			let context = || format!("env file {}", path.display());
+			for item in dotenvy::from_path_iter(path)
+				.into_diagnostic()
+				.wrap_err_with(context)?
+			{
+				let (k, v) = item.into_diagnostic().wrap_err_with(context)?;
+				trace!(?path, ?k, ?v, "read environment variable");
+				env.insert(k, OsString::from(v));
+			}
+		}
+
+		Ok(env)
+	}
+}
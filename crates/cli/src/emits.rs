@@ -2,19 +2,71 @@ use std::{ffi::OsString, fmt::Write, path::PathBuf};
 
 use miette::{IntoDiagnostic, Result};
 use watchexec::paths::summarise_events_to_env;
-use watchexec_events::{filekind::FileEventKind, Event, Tag};
+use watchexec_events::{filekind::FileEventKind, Event, ProcessEnd, Tag};
 
 use crate::state::RotatingTempFile;
 
 pub fn emits_to_environment(events: &[Event]) -> impl Iterator<Item = (String, OsString)> {
+	// the end of the set as a whole, rather than that of its first step
+	let completion = events
+		.iter()
+		.filter(|event| {
+			event
+				.tags
+				.iter()
+				.any(|tag| matches!(tag, Tag::SetCompletion { .. }))
+		})
+		.flat_map(Event::completions)
+		.flatten()
+		.last()
+		.map(|end| {
+			let (disposition, code) = completion_parts(end);
+			[("DISPOSITION", disposition.to_string())]
+				.into_iter()
+				.chain(code.map(|code| ("CODE", code)))
+				.map(|(k, v)| (format!("WATCHEXEC_COMPLETION_{k}"), OsString::from(v)))
+				.collect::<Vec<_>>()
+		});
+
 	summarise_events_to_env(events.iter())
 		.into_iter()
 		.map(|(k, v)| (format!("WATCHEXEC_{k}_PATH"), v))
+		.chain(completion.into_iter().flatten())
+}
+
+/// The disposition of a process end, and its exit, signal, stop, or exception code if any.
+fn completion_parts(end: ProcessEnd) -> (&'static str, Option<String>) {
+	match end {
+		ProcessEnd::Success => ("success", None),
+		ProcessEnd::ExitError(code) => ("error", Some(code.to_string())),
+		ProcessEnd::ExitSignal(sig) => ("signal", Some(sig.to_string())),
+		ProcessEnd::ExitStop(code) => ("stop", Some(code.to_string())),
+		ProcessEnd::Exception(ex) => ("exception", Some(format!("{ex:#x}"))),
+		ProcessEnd::Continued => ("continued", None),
+	}
 }
 
 fn events_to_simple_format(events: &[Event]) -> Result<String> {
 	let mut buf = String::new();
 	for event in events {
+		for end in event.completions().flatten() {
+			match completion_parts(end) {
+				(disposition, Some(code)) => writeln!(&mut buf, "completion:{disposition}:{code}"),
+				(disposition, None) => writeln!(&mut buf, "completion:{disposition}"),
+			}
+			.into_diagnostic()?;
+		}
+
+		// paired renames say which path is which, instead of listing both as renamed
+		let mut renames = event.renames().peekable();
+		if renames.peek().is_some() {
+			for (from, to) in renames {
+				writeln!(&mut buf, "rename-from:{}", from.to_string_lossy()).into_diagnostic()?;
+				writeln!(&mut buf, "rename-to:{}", to.to_string_lossy()).into_diagnostic()?;
+			}
+			continue;
+		}
+
 		let feks = event
 			.tags
 			.iter()
@@ -29,8 +29,13 @@ pub async fn dirs(args: &Args) -> Result<(PathBuf, PathBuf)> {
 		debug!(?homedir, "home directory");
 
 		let mut paths = HashSet::new();
-		for path in &args.paths {
-			paths.insert(canonicalize(path).await.into_diagnostic()?);
+		for path in args.paths.iter().chain(&args.paths_non_recursive) {
+			// paths which don't exist yet are watched for, and resolved as they are
+			paths.insert(match canonicalize(path).await {
+				Ok(path) => path,
+				Err(err) if err.kind() == std::io::ErrorKind::NotFound => curdir.join(path),
+				Err(err) => return Err(err).into_diagnostic(),
+			});
 		}
 
 		let homedir_requested = homedir.as_ref().map_or(false, |home| paths.contains(home));
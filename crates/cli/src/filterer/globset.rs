@@ -17,9 +17,13 @@ use watchexec::{
 };
 use watchexec_filterer_globset::GlobsetFilterer;
 
-use crate::args::{Args, FsEvent};
+use crate::{
+	args::{Args, FsEvent},
+	dotenv::EnvFiles,
+	state::State,
+};
 
-pub async fn globset(args: &Args) -> Result<Arc<WatchexecFilterer>> {
+pub async fn globset(args: &Args, state: &State) -> Result<Arc<WatchexecFilterer>> {
 	let (project_origin, workdir) = super::common::dirs(args).await?;
 	let ignore_files = if args.no_discover_ignore {
 		Vec::new()
@@ -80,6 +84,7 @@ pub async fn globset(args: &Args) -> Result<Arc<WatchexecFilterer>> {
 			.await
 			.into_diagnostic()?,
 		fs_events: args.filter_fs_events.clone(),
+		env_files: state.env_files.clone(),
 	}))
 }
 
@@ -107,10 +112,13 @@ async fn read_filter_file(path: &Path) -> Result<Vec<(String, Option<PathBuf>)>>
 }
 
 /// A custom filterer that combines the library's Globset filterer and a switch for --no-meta
+///
+/// Events for the --env-file files are always let through the globset filters.
 #[derive(Debug)]
 pub struct WatchexecFilterer {
 	inner: GlobsetFilterer,
 	fs_events: Vec<FsEvent>,
+	env_files: EnvFiles,
 }
 
 impl Filterer for WatchexecFilterer {
@@ -133,6 +141,11 @@ impl Filterer for WatchexecFilterer {
 			}
 		}
 
+		if event.paths().any(|(path, _)| self.env_files.contains(path)) {
+			trace!("event is for an env file, passing");
+			return Ok(true);
+		}
+
 		trace!("check against original event");
 		if !self.inner.check_event(event, priority)? {
 			return Ok(false);
@@ -19,6 +19,7 @@ use watchexec::{
 
 pub mod args;
 mod config;
+mod dotenv;
 mod emits;
 mod filterer;
 mod state;
@@ -104,9 +105,9 @@ async fn run_watchexec(args: Args) -> Result<()> {
 
 	let init = config::init(&args);
 
-	let state = state::State::new()?;
+	let state = state::State::new(&args)?;
 	let mut runtime = config::runtime(&args, &state)?;
-	runtime.filterer(filterer::globset(&args).await?);
+	runtime.filterer(filterer::globset(&args, &state).await?);
 
 	info!("initialising Watchexec runtime");
 	let wx = Watchexec::new(init, runtime)?;
@@ -7,15 +7,22 @@ use std::{
 use miette::{IntoDiagnostic, Result};
 use tempfile::NamedTempFile;
 
+use crate::{args::Args, dotenv::EnvFiles};
+
 #[derive(Clone, Debug)]
 pub struct State {
 	pub emit_file: RotatingTempFile,
+	pub env_files: EnvFiles,
 }
 
 impl State {
-	pub fn new() -> Result<Self> {
+	pub fn new(args: &Args) -> Result<Self> {
 		let emit_file = RotatingTempFile::new()?;
-		Ok(Self { emit_file })
+		let env_files = EnvFiles::new(&args.env_file)?;
+		Ok(Self {
+			emit_file,
+			env_files,
+		})
 	}
 }
 
@@ -9,7 +9,9 @@ use watchexec_signals::Signal;
 #[cfg(feature = "serde")]
 use crate::serde_formats::{SerdeEvent, SerdeTag};
 
-use crate::{filekind::FileEventKind, FileType, Keyboard, ProcessEnd};
+use crate::{
+	filekind::FileEventKind, FileType, Key, Keyboard, OutputStream, ProcessEnd, ProcessUsage,
+};
 
 /// An event, as far as watchexec cares about.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -38,6 +40,18 @@ pub enum Tag {
 		file_type: Option<FileType>,
 	},
 
+	/// The event is about a file or folder being renamed (or moved) from one path to another.
+	///
+	/// This is added to rename events when it's known which of their paths is the original and
+	/// which is the new one. Both paths are also present as [`Tag::Path`]s.
+	Rename {
+		/// The original path.
+		from: PathBuf,
+
+		/// The new path.
+		to: PathBuf,
+	},
+
 	/// Kind of a filesystem event (create, remove, modify, etc).
 	FileEventKind(FileEventKind),
 
@@ -56,6 +70,52 @@ pub enum Tag {
 	/// The event is about the subprocess ending.
 	ProcessCompletion(Option<ProcessEnd>),
 
+	/// The event is about a subprocess which was stopped because it ran for too long.
+	///
+	/// This is added to process completion events.
+	Timeout,
+
+	/// The event is about a command set which became ready, as determined by its readiness probe.
+	ProcessReady,
+
+	/// The event is about the resource usage and timing of the subprocess.
+	///
+	/// This is added to process completion events.
+	ProcessUsage(ProcessUsage),
+
+	/// The event is about a line of output from the subprocess.
+	///
+	/// This is only emitted when output capture is enabled. The line is without its terminator,
+	/// and lossily converted to UTF-8.
+	ProcessOutput {
+		/// The stream the line was printed to.
+		stream: OutputStream,
+
+		/// The line of output.
+		line: String,
+	},
+
+	/// The event marks the end of a command set.
+	///
+	/// This is added to the process completion event of the step which ended the set: the last
+	/// step which ran, either because it was the last step, because all following steps were
+	/// skipped, or because the set was stopped.
+	SetCompletion {
+		/// The index of the step which ended the set.
+		step: usize,
+	},
+
+	/// The event is about a particular job.
+	///
+	/// This is the name of the job, as given when it was defined in Watchexec.
+	Job(String),
+
+	/// The event was emitted by a timer, on this schedule.
+	///
+	/// This is the schedule as it was given to Watchexec, like an interval or a cron expression.
+	/// It's added to events with a [`Source::Time`].
+	Schedule(String),
+
 	#[cfg(feature = "serde")]
 	/// The event is unknown (or not yet implemented).
 	Unknown,
@@ -67,12 +127,20 @@ impl Tag {
 	pub const fn discriminant_name(&self) -> &'static str {
 		match self {
 			Self::Path { .. } => "Path",
+			Self::Rename { .. } => "Rename",
 			Self::FileEventKind(_) => "FileEventKind",
 			Self::Source(_) => "Source",
 			Self::Keyboard(_) => "Keyboard",
 			Self::Process(_) => "Process",
 			Self::Signal(_) => "Signal",
 			Self::ProcessCompletion(_) => "ProcessCompletion",
+			Self::Timeout => "Timeout",
+			Self::ProcessReady => "ProcessReady",
+			Self::ProcessUsage(_) => "ProcessUsage",
+			Self::ProcessOutput { .. } => "ProcessOutput",
+			Self::SetCompletion { .. } => "SetCompletion",
+			Self::Job(_) => "Job",
+			Self::Schedule(_) => "Schedule",
 			#[cfg(feature = "serde")]
 			Self::Unknown => "Unknown",
 		}
@@ -187,6 +255,14 @@ impl Event {
 		})
 	}
 
+	/// Return all renames in the event's tags, as pairs of original and new paths.
+	pub fn renames(&self) -> impl Iterator<Item = (&Path, &Path)> {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::Rename { from, to } => Some((from.as_path(), to.as_path())),
+			_ => None,
+		})
+	}
+
 	/// Return all signals in the event's tags.
 	pub fn signals(&self) -> impl Iterator<Item = Signal> + '_ {
 		self.tags.iter().filter_map(|p| match p {
@@ -195,6 +271,14 @@ impl Event {
 		})
 	}
 
+	/// Return all key presses in the event's tags.
+	pub fn keys(&self) -> impl Iterator<Item = Key> + '_ {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::Keyboard(Keyboard::Key(k)) => Some(*k),
+			_ => None,
+		})
+	}
+
 	/// Return all process completions in the event's tags.
 	pub fn completions(&self) -> impl Iterator<Item = Option<ProcessEnd>> + '_ {
 		self.tags.iter().filter_map(|p| match p {
@@ -202,6 +286,38 @@ impl Event {
 			_ => None,
 		})
 	}
+
+	/// Return all process resource usages in the event's tags.
+	pub fn usages(&self) -> impl Iterator<Item = &ProcessUsage> {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::ProcessUsage(u) => Some(u),
+			_ => None,
+		})
+	}
+
+	/// Return all lines of process output in the event's tags.
+	pub fn output(&self) -> impl Iterator<Item = (OutputStream, &str)> {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::ProcessOutput { stream, line } => Some((*stream, line.as_str())),
+			_ => None,
+		})
+	}
+
+	/// Return all job names in the event's tags.
+	pub fn jobs(&self) -> impl Iterator<Item = &str> {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::Job(j) => Some(j.as_str()),
+			_ => None,
+		})
+	}
+
+	/// Return all timer schedules in the event's tags.
+	pub fn schedules(&self) -> impl Iterator<Item = &str> {
+		self.tags.iter().filter_map(|p| match p {
+			Tag::Schedule(s) => Some(s.as_str()),
+			_ => None,
+		})
+	}
 }
 
 impl fmt::Display for Event {
@@ -215,6 +331,9 @@ impl fmt::Display for Event {
 						write!(f, " filetype={ft}")?;
 					}
 				}
+				Tag::Rename { from, to } => {
+					write!(f, " rename={} -> {}", from.display(), to.display())?;
+				}
 				Tag::FileEventKind(kind) => write!(f, " kind={kind:?}")?,
 				Tag::Source(s) => write!(f, " source={s:?}")?,
 				Tag::Keyboard(k) => write!(f, " keyboard={k:?}")?,
@@ -222,6 +341,13 @@ impl fmt::Display for Event {
 				Tag::Signal(s) => write!(f, " signal={s:?}")?,
 				Tag::ProcessCompletion(None) => write!(f, " command-completed")?,
 				Tag::ProcessCompletion(Some(c)) => write!(f, " command-completed({c:?})")?,
+				Tag::Timeout => write!(f, " timed-out")?,
+				Tag::ProcessReady => write!(f, " ready")?,
+				Tag::ProcessUsage(u) => write!(f, " usage({u})")?,
+				Tag::ProcessOutput { stream, line } => write!(f, " output({stream:?})={line:?}")?,
+				Tag::SetCompletion { step } => write!(f, " set-completed(step={step})")?,
+				Tag::Job(j) => write!(f, " job={j}")?,
+				Tag::Schedule(s) => write!(f, " schedule={s:?}")?,
 				#[cfg(feature = "serde")]
 				Tag::Unknown => write!(f, " unknown")?,
 			}
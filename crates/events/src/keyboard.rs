@@ -6,4 +6,44 @@
 pub enum Keyboard {
 	/// Event representing an 'end of file' on stdin
 	Eof,
+
+	/// Event representing a key press, when reading keys from stdin
+	Key(Key),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+/// A key, as read from stdin.
+pub enum Key {
+	/// A character, as typed (so uppercase if Shift was held)
+	Char(char),
+
+	/// A letter pressed with Ctrl held, in lowercase
+	Ctrl(char),
+
+	/// The Enter or Return key
+	Enter,
+
+	/// The Tab key
+	Tab,
+
+	/// The Backspace key
+	Backspace,
+
+	/// The Escape key
+	Escape,
+
+	/// The Up arrow key
+	Up,
+
+	/// The Down arrow key
+	Down,
+
+	/// The Left arrow key
+	Left,
+
+	/// The Right arrow key
+	Right,
 }
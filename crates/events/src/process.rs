@@ -1,6 +1,8 @@
 use std::{
+	fmt,
 	num::{NonZeroI32, NonZeroI64},
 	process::ExitStatus,
+	time::Duration,
 };
 
 use watchexec_signals::Signal;
@@ -48,6 +50,54 @@ pub enum ProcessEnd {
 	Continued,
 }
 
+/// A standard output stream of a process.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum OutputStream {
+	/// The standard output.
+	Stdout,
+
+	/// The standard error.
+	Stderr,
+}
+
+/// The resource usage and timing of a process.
+///
+/// The wall-clock time is always available. The CPU times and peak memory usage depend on platform
+/// support: currently they're only measured on Linux, and include the process's own children
+/// which it waited for, but not children it left behind.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ProcessUsage {
+	/// How long the process ran for.
+	pub wall_time: Duration,
+
+	/// How much CPU time was spent in user mode.
+	pub user_time: Option<Duration>,
+
+	/// How much CPU time was spent in kernel mode.
+	pub system_time: Option<Duration>,
+
+	/// The peak resident set size, in bytes.
+	pub max_rss: Option<u64>,
+}
+
+impl fmt::Display for ProcessUsage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:.1?}", self.wall_time)?;
+		if let Some(user) = self.user_time {
+			write!(f, ", {user:.1?} user")?;
+		}
+		if let Some(system) = self.system_time {
+			write!(f, ", {system:.1?} system")?;
+		}
+		if let Some(rss) = self.max_rss {
+			write!(f, ", {}MB max RSS", rss / 1_000_000)?;
+		}
+		Ok(())
+	}
+}
+
 impl From<ExitStatus> for ProcessEnd {
 	#[cfg(unix)]
 	fn from(es: ExitStatus) -> Self {
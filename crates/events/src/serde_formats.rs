@@ -2,6 +2,7 @@ use std::{
 	collections::BTreeMap,
 	num::{NonZeroI32, NonZeroI64},
 	path::PathBuf,
+	time::Duration,
 };
 
 use serde::{Deserialize, Serialize};
@@ -12,7 +13,7 @@ use crate::{
 		AccessKind, AccessMode, CreateKind, DataChange, FileEventKind as EventKind, MetadataKind,
 		ModifyKind, RemoveKind, RenameMode,
 	},
-	Event, FileType, Keyboard, ProcessEnd, Source, Tag,
+	Event, FileType, Keyboard, OutputStream, ProcessEnd, ProcessUsage, Source, Tag,
 };
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -25,6 +26,12 @@ pub struct SerdeTag {
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	filetype: Option<FileType>,
 
+	// rename
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	from: Option<PathBuf>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	to: Option<PathBuf>,
+
 	// fs
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	simple: Option<FsEventKind>,
@@ -52,6 +59,34 @@ pub struct SerdeTag {
 	disposition: Option<ProcessDisposition>,
 	#[serde(default, skip_serializing_if = "Option::is_none")]
 	code: Option<i64>,
+
+	// usage
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	wall: Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	user: Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	system: Option<f64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	maxrss: Option<u64>,
+
+	// output
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	stream: Option<OutputStream>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	line: Option<String>,
+
+	// set completion
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	step: Option<usize>,
+
+	// job
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	job: Option<String>,
+
+	// schedule
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	schedule: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
@@ -60,12 +95,20 @@ pub enum TagKind {
 	#[default]
 	None,
 	Path,
+	Rename,
 	Fs,
 	Source,
 	Keyboard,
 	Process,
 	Signal,
 	Completion,
+	Timeout,
+	Ready,
+	Usage,
+	Output,
+	SetCompletion,
+	Job,
+	Schedule,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -111,6 +154,12 @@ impl From<Tag> for SerdeTag {
 				filetype: file_type,
 				..Default::default()
 			},
+			Tag::Rename { from, to } => Self {
+				kind: TagKind::Rename,
+				from: Some(from),
+				to: Some(to),
+				..Default::default()
+			},
 			Tag::FileEventKind(fek) => Self {
 				kind: TagKind::Fs,
 				full: Some(format!("{:?}", fek)),
@@ -167,6 +216,43 @@ impl From<Tag> for SerdeTag {
 				}),
 				..Default::default()
 			},
+			Tag::Timeout => Self {
+				kind: TagKind::Timeout,
+				..Default::default()
+			},
+			Tag::ProcessReady => Self {
+				kind: TagKind::Ready,
+				..Default::default()
+			},
+			Tag::ProcessUsage(usage) => Self {
+				kind: TagKind::Usage,
+				wall: Some(usage.wall_time.as_secs_f64()),
+				user: usage.user_time.map(|t| t.as_secs_f64()),
+				system: usage.system_time.map(|t| t.as_secs_f64()),
+				maxrss: usage.max_rss,
+				..Default::default()
+			},
+			Tag::ProcessOutput { stream, line } => Self {
+				kind: TagKind::Output,
+				stream: Some(stream),
+				line: Some(line),
+				..Default::default()
+			},
+			Tag::SetCompletion { step } => Self {
+				kind: TagKind::SetCompletion,
+				step: Some(step),
+				..Default::default()
+			},
+			Tag::Job(job) => Self {
+				kind: TagKind::Job,
+				job: Some(job),
+				..Default::default()
+			},
+			Tag::Schedule(schedule) => Self {
+				kind: TagKind::Schedule,
+				schedule: Some(schedule),
+				..Default::default()
+			},
 			Tag::Unknown => Self::default(),
 		}
 	}
@@ -184,6 +270,12 @@ impl From<SerdeTag> for Tag {
 				path,
 				file_type: filetype,
 			},
+			SerdeTag {
+				kind: TagKind::Rename,
+				from: Some(from),
+				to: Some(to),
+				..
+			} => Self::Rename { from, to },
 			SerdeTag {
 				kind: TagKind::Fs,
 				full: Some(full),
@@ -327,6 +419,50 @@ impl From<SerdeTag> for Tag {
 					NonZeroI32::new_unchecked(exc.try_into().unwrap())
 				})))
 			}
+			SerdeTag {
+				kind: TagKind::Timeout,
+				..
+			} => Self::Timeout,
+			SerdeTag {
+				kind: TagKind::Ready,
+				..
+			} => Self::ProcessReady,
+			SerdeTag {
+				kind: TagKind::Usage,
+				wall: Some(wall),
+				user,
+				system,
+				maxrss,
+				..
+			} => Duration::try_from_secs_f64(wall).map_or(Self::Unknown, |wall_time| {
+				Self::ProcessUsage(ProcessUsage {
+					wall_time,
+					user_time: user.and_then(|t| Duration::try_from_secs_f64(t).ok()),
+					system_time: system.and_then(|t| Duration::try_from_secs_f64(t).ok()),
+					max_rss: maxrss,
+				})
+			}),
+			SerdeTag {
+				kind: TagKind::Output,
+				stream: Some(stream),
+				line: Some(line),
+				..
+			} => Self::ProcessOutput { stream, line },
+			SerdeTag {
+				kind: TagKind::SetCompletion,
+				step: Some(step),
+				..
+			} => Self::SetCompletion { step },
+			SerdeTag {
+				kind: TagKind::Job,
+				job: Some(job),
+				..
+			} => Self::Job(job),
+			SerdeTag {
+				kind: TagKind::Schedule,
+				schedule: Some(schedule),
+				..
+			} => Self::Schedule(schedule),
 			_ => Self::Unknown,
 		}
 	}
@@ -1,9 +1,12 @@
-use std::num::{NonZeroI32, NonZeroI64};
+use std::{
+	num::{NonZeroI32, NonZeroI64},
+	time::Duration,
+};
 
 use snapbox::assert_eq_path;
 use watchexec_events::{
 	filekind::{CreateKind, FileEventKind as EventKind, ModifyKind, RemoveKind, RenameMode},
-	Event, FileType, Keyboard, ProcessEnd, Source, Tag,
+	Event, FileType, Key, Keyboard, OutputStream, ProcessEnd, ProcessUsage, Source, Tag,
 };
 use watchexec_signals::Signal;
 
@@ -201,6 +204,171 @@ fn completions() {
 	assert_eq!(parse_file("tests/snapshots/completions.json"), completions);
 }
 
+#[test]
+fn keys() {
+	let keys = vec![
+		Event {
+			tags: vec![
+				Tag::Source(Source::Keyboard),
+				Tag::Keyboard(Keyboard::Key(Key::Char('r'))),
+			],
+			metadata: Default::default(),
+		},
+		Event {
+			tags: vec![
+				Tag::Source(Source::Keyboard),
+				Tag::Keyboard(Keyboard::Key(Key::Ctrl('d'))),
+				Tag::Keyboard(Keyboard::Key(Key::Enter)),
+				Tag::Keyboard(Keyboard::Key(Key::Up)),
+			],
+			metadata: Default::default(),
+		},
+	];
+
+	assert_eq_path(
+		"tests/snapshots/keys.json",
+		serde_json::to_string_pretty(&keys).unwrap(),
+	);
+
+	assert_eq!(parse_file("tests/snapshots/keys.json"), keys);
+}
+
+#[test]
+fn readies() {
+	let readies = vec![
+		Event {
+			tags: vec![Tag::Source(Source::Internal), Tag::ProcessReady],
+			metadata: Default::default(),
+		},
+		Event {
+			tags: vec![
+				Tag::Source(Source::Internal),
+				Tag::ProcessReady,
+				Tag::Job("server".into()),
+			],
+			metadata: Default::default(),
+		},
+	];
+
+	assert_eq_path(
+		"tests/snapshots/readies.json",
+		serde_json::to_string_pretty(&readies).unwrap(),
+	);
+
+	assert_eq!(parse_file("tests/snapshots/readies.json"), readies);
+}
+
+#[test]
+fn timeouts() {
+	let timeouts = vec![Event {
+		tags: vec![
+			Tag::Source(Source::Internal),
+			Tag::ProcessCompletion(Some(ProcessEnd::ExitSignal(Signal::Terminate))),
+			Tag::Timeout,
+		],
+		metadata: Default::default(),
+	}];
+
+	assert_eq_path(
+		"tests/snapshots/timeouts.json",
+		serde_json::to_string_pretty(&timeouts).unwrap(),
+	);
+
+	assert_eq!(parse_file("tests/snapshots/timeouts.json"), timeouts);
+}
+
+#[test]
+fn set_completions() {
+	let set_completions = vec![Event {
+		tags: vec![
+			Tag::Source(Source::Internal),
+			Tag::ProcessCompletion(Some(ProcessEnd::ExitError(NonZeroI64::new(1).unwrap()))),
+			Tag::SetCompletion { step: 2 },
+		],
+		metadata: Default::default(),
+	}];
+
+	assert_eq_path(
+		"tests/snapshots/set-completions.json",
+		serde_json::to_string_pretty(&set_completions).unwrap(),
+	);
+
+	assert_eq!(
+		parse_file("tests/snapshots/set-completions.json"),
+		set_completions
+	);
+}
+
+#[test]
+fn usages() {
+	let usages = vec![
+		Event {
+			tags: vec![
+				Tag::Source(Source::Internal),
+				Tag::ProcessCompletion(Some(ProcessEnd::Success)),
+				Tag::ProcessUsage(ProcessUsage {
+					wall_time: Duration::from_millis(3250),
+					user_time: Some(Duration::from_millis(2500)),
+					system_time: Some(Duration::from_millis(125)),
+					max_rss: Some(412_000_000),
+				}),
+			],
+			metadata: Default::default(),
+		},
+		Event {
+			tags: vec![
+				Tag::Source(Source::Internal),
+				Tag::ProcessCompletion(None),
+				Tag::ProcessUsage(ProcessUsage {
+					wall_time: Duration::from_millis(500),
+					..Default::default()
+				}),
+			],
+			metadata: Default::default(),
+		},
+	];
+
+	assert_eq_path(
+		"tests/snapshots/usages.json",
+		serde_json::to_string_pretty(&usages).unwrap(),
+	);
+
+	assert_eq!(parse_file("tests/snapshots/usages.json"), usages);
+}
+
+#[test]
+fn outputs() {
+	let outputs = vec![
+		Event {
+			tags: vec![
+				Tag::Source(Source::Internal),
+				Tag::ProcessOutput {
+					stream: OutputStream::Stdout,
+					line: "Listening on :8080".into(),
+				},
+			],
+			metadata: Default::default(),
+		},
+		Event {
+			tags: vec![
+				Tag::Source(Source::Internal),
+				Tag::ProcessOutput {
+					stream: OutputStream::Stderr,
+					line: "error[E0425]: cannot find value `x` in this scope".into(),
+				},
+			],
+			metadata: Default::default(),
+		},
+	];
+
+	assert_eq_path(
+		"tests/snapshots/outputs.json",
+		serde_json::to_string_pretty(&outputs).unwrap(),
+	);
+
+	assert_eq!(parse_file("tests/snapshots/outputs.json"), outputs);
+}
+
 #[test]
 fn paths() {
 	let paths = vec![
@@ -251,3 +419,76 @@ fn paths() {
 
 	assert_eq!(parse_file("tests/snapshots/paths.json"), paths);
 }
+
+#[test]
+fn jobs() {
+	let jobs = vec![Event {
+		tags: vec![
+			Tag::Source(Source::Internal),
+			Tag::ProcessCompletion(Some(ProcessEnd::Success)),
+			Tag::Job("frontend".into()),
+		],
+		metadata: Default::default(),
+	}];
+
+	assert_eq_path(
+		"tests/snapshots/jobs.json",
+		serde_json::to_string_pretty(&jobs).unwrap(),
+	);
+
+	assert_eq!(parse_file("tests/snapshots/jobs.json"), jobs);
+}
+
+#[test]
+fn schedules() {
+	let schedules = vec![
+		Event {
+			tags: vec![Tag::Source(Source::Time), Tag::Schedule("every 5m".into())],
+			metadata: Default::default(),
+		},
+		Event {
+			tags: vec![
+				Tag::Source(Source::Time),
+				Tag::Schedule("0 */2 * * *".into()),
+			],
+			metadata: Default::default(),
+		},
+	];
+
+	assert_eq_path(
+		"tests/snapshots/schedules.json",
+		serde_json::to_string_pretty(&schedules).unwrap(),
+	);
+
+	assert_eq!(parse_file("tests/snapshots/schedules.json"), schedules);
+}
+
+#[test]
+fn renames() {
+	let renames = vec![Event {
+		tags: vec![
+			Tag::Source(Source::Filesystem),
+			Tag::FileEventKind(EventKind::Modify(ModifyKind::Name(RenameMode::Both))),
+			Tag::Path {
+				path: "/code/watchexec/src/old.rs".into(),
+				file_type: None,
+			},
+			Tag::Path {
+				path: "/code/watchexec/src/new.rs".into(),
+				file_type: Some(FileType::File),
+			},
+			Tag::Rename {
+				from: "/code/watchexec/src/old.rs".into(),
+				to: "/code/watchexec/src/new.rs".into(),
+			},
+		],
+		metadata: Default::default(),
+	}];
+
+	assert_eq_path(
+		"tests/snapshots/renames.json",
+		serde_json::to_string_pretty(&renames).unwrap(),
+	);
+
+	assert_eq!(parse_file("tests/snapshots/renames.json"), renames);
+}
@@ -1,5 +1,7 @@
 //! Processor responsible for receiving events, filtering them, and scheduling actions in response.
 
+#[doc(inline)]
+pub use job::JobId;
 #[doc(inline)]
 pub use outcome::Outcome;
 #[doc(inline)]
@@ -7,6 +9,8 @@ pub use worker::worker;
 #[doc(inline)]
 pub use workingdata::*;
 
+mod coalesce;
+mod job;
 mod outcome;
 mod outcome_worker;
 mod process_holder;
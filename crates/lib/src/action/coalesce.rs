@@ -0,0 +1,457 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+};
+
+use tracing::trace;
+
+use crate::event::{
+	filekind::{
+		AccessKind, AccessMode, CreateKind, FileEventKind, ModifyKind, RemoveKind, RenameMode,
+	},
+	Event, FileType, Source, Tag,
+};
+
+/// Reduces a set of events to their net effect.
+///
+/// Filesystem events are replaced by one event per path, for the net change to that path over the
+/// whole set: created, modified, removed, or renamed. These carry the other tags and the metadata
+/// of the last event seen for their path. Paths which end up as they started (like a file created
+/// then removed) or which were only accessed get no event at all. Other events are kept as they
+/// are, in order, but without duplicates.
+///
+/// This may return an empty set, if nothing changed on the whole.
+pub(crate) fn coalesce(events: Vec<Event>) -> Vec<Event> {
+	let before = events.len();
+	let mut others: Vec<Event> = Vec::with_capacity(events.len());
+	let mut nets = Nets::default();
+
+	for event in events {
+		let kinds = event
+			.tags
+			.iter()
+			.filter_map(|tag| match tag {
+				Tag::FileEventKind(kind) => Some(*kind),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+
+		if kinds.is_empty() || event.paths().next().is_none() {
+			if !others.contains(&event) {
+				others.push(event);
+			}
+			continue;
+		}
+
+		let context = Context {
+			tags: event
+				.tags
+				.iter()
+				.filter(|tag| {
+					!matches!(
+						tag,
+						Tag::Source(_)
+							| Tag::FileEventKind(_)
+							| Tag::Path { .. } | Tag::Rename { .. }
+					)
+				})
+				.cloned()
+				.collect(),
+			metadata: event.metadata.clone(),
+		};
+
+		let renamed = event
+			.renames()
+			.map(|(from, to)| (from.to_owned(), to.to_owned()))
+			.collect::<Vec<_>>();
+		for (from, to) in &renamed {
+			nets.rename(from, to);
+		}
+
+		for (path, file_type) in event.paths() {
+			if renamed.iter().any(|(from, to)| from == path || to == path) {
+				nets.file_type(path, file_type);
+			} else {
+				for kind in &kinds {
+					nets.apply(path, file_type, *kind);
+				}
+			}
+
+			nets.context(path, &context);
+		}
+	}
+
+	let mut coalesced = others;
+	coalesced.extend(nets.into_events());
+	trace!(%before, after=%coalesced.len(), "coalesced events");
+	coalesced
+}
+
+/// The net change to each path, in the order the paths were first seen.
+#[derive(Debug, Default)]
+struct Nets {
+	order: Vec<PathBuf>,
+	paths: HashMap<PathBuf, Net>,
+}
+
+#[derive(Debug)]
+struct Net {
+	/// Whether the path existed before the first event.
+	existed: bool,
+
+	/// Whether the path exists after the last event.
+	exists: bool,
+
+	/// The last event kind seen which changes the path, or else the last kind seen at all.
+	kind: FileEventKind,
+
+	/// Whether the contents or metadata changed, as opposed to only being accessed.
+	changed: bool,
+
+	/// The last creation and removal kinds seen.
+	created: Option<FileEventKind>,
+	removed: Option<FileEventKind>,
+
+	/// If the path was renamed to another, which one.
+	renamed_to: Option<PathBuf>,
+
+	file_type: Option<FileType>,
+
+	/// What else was in the last event seen for the path.
+	context: Context,
+}
+
+/// The tags other than the source, kind, paths, and renames of an event, and its metadata.
+#[derive(Clone, Debug, Default)]
+struct Context {
+	tags: Vec<Tag>,
+	metadata: HashMap<String, Vec<String>>,
+}
+
+impl Context {
+	fn into_event(self, mut tags: Vec<Tag>) -> Event {
+		tags.extend(self.tags);
+		Event {
+			tags,
+			metadata: self.metadata,
+		}
+	}
+}
+
+impl Nets {
+	fn get(&mut self, path: &Path, existed: bool, kind: FileEventKind) -> &mut Net {
+		if !self.paths.contains_key(path) {
+			self.order.push(path.to_owned());
+		}
+
+		self.paths.entry(path.to_owned()).or_insert(Net {
+			existed,
+			exists: existed,
+			kind,
+			changed: false,
+			created: None,
+			removed: None,
+			renamed_to: None,
+			file_type: None,
+			context: Context::default(),
+		})
+	}
+
+	fn context(&mut self, path: &Path, context: &Context) {
+		if let Some(net) = self.paths.get_mut(path) {
+			net.context = context.clone();
+		}
+	}
+
+	fn file_type(&mut self, path: &Path, file_type: Option<&FileType>) {
+		if let (Some(net), Some(ft)) = (self.paths.get_mut(path), file_type) {
+			net.file_type = Some(*ft);
+		}
+	}
+
+	fn apply(&mut self, path: &Path, file_type: Option<&FileType>, kind: FileEventKind) {
+		let net = self.get(path, !matches!(kind, FileEventKind::Create(_)), kind);
+		if let Some(ft) = file_type {
+			net.file_type = Some(*ft);
+		}
+
+		match kind {
+			FileEventKind::Create(_) => {
+				net.exists = true;
+				net.changed = true;
+				net.created = Some(kind);
+				net.kind = kind;
+			}
+			FileEventKind::Remove(_) => {
+				net.exists = false;
+				net.changed = true;
+				net.removed = Some(kind);
+				net.renamed_to = None;
+				net.kind = kind;
+			}
+			FileEventKind::Access(AccessKind::Close(AccessMode::Write))
+			| FileEventKind::Modify(_)
+			| FileEventKind::Any
+			| FileEventKind::Other => {
+				net.exists = true;
+				net.changed = true;
+				net.kind = kind;
+			}
+			FileEventKind::Access(_) => {
+				net.exists = true;
+				if !net.changed {
+					net.kind = kind;
+				}
+			}
+		}
+	}
+
+	fn rename(&mut self, from: &Path, to: &Path) {
+		let kind = FileEventKind::Modify(ModifyKind::Name(RenameMode::Both));
+
+		let source = self.get(from, true, kind);
+		let file_type = source.file_type;
+		source.exists = false;
+		source.changed = true;
+		source.kind = kind;
+		source.renamed_to = Some(to.to_owned());
+
+		let target = self.get(to, false, kind);
+		target.exists = true;
+		target.changed = true;
+		target.kind = kind;
+		target.renamed_to = None;
+		if target.file_type.is_none() {
+			target.file_type = file_type;
+		}
+	}
+
+	fn into_events(self) -> Vec<Event> {
+		let mut events = Vec::with_capacity(self.order.len());
+		for path in &self.order {
+			let net = match self.paths.get(path) {
+				Some(net) => net,
+				None => continue,
+			};
+
+			if !net.existed && !net.exists {
+				trace!(?path, "path came and went, dropping");
+				continue;
+			}
+
+			// a rename of a path which existed, to one which still exists and hadn't before
+			let rename_target = net
+				.renamed_to
+				.as_ref()
+				.filter(|_| net.existed && !net.exists)
+				.and_then(|to| self.paths.get(to).map(|target| (to, target)))
+				.filter(|(_, target)| !target.existed && target.exists);
+			if let Some((to, target)) = rename_target {
+				events.push(target.context.clone().into_event(vec![
+					Tag::Source(Source::Filesystem),
+					Tag::FileEventKind(FileEventKind::Modify(ModifyKind::Name(RenameMode::Both))),
+					Tag::Path {
+						path: path.clone(),
+						file_type: net.file_type,
+					},
+					Tag::Path {
+						path: to.clone(),
+						file_type: target.file_type,
+					},
+					Tag::Rename {
+						from: path.clone(),
+						to: to.clone(),
+					},
+				]));
+				continue;
+			}
+
+			if !net.changed {
+				trace!(?path, "path was only accessed, dropping");
+				continue;
+			}
+
+			let kind = match (net.existed, net.exists) {
+				(false, true) => net
+					.created
+					.unwrap_or(FileEventKind::Create(CreateKind::Any)),
+				(true, false) => net
+					.removed
+					.unwrap_or(FileEventKind::Remove(RemoveKind::Any)),
+				_ if net.created.is_some()
+					|| net.removed.is_some()
+					|| matches!(net.kind, FileEventKind::Modify(ModifyKind::Name(_))) =>
+				{
+					// replaced, moved over, or recreated
+					FileEventKind::Modify(ModifyKind::Any)
+				}
+				_ => net.kind,
+			};
+
+			events.push(net.context.clone().into_event(vec![
+				Tag::Source(Source::Filesystem),
+				Tag::FileEventKind(kind),
+				Tag::Path {
+					path: path.clone(),
+					file_type: net.file_type,
+				},
+			]));
+		}
+
+		// renamed paths which are also part of a rename event are covered by it
+		let renamed = events
+			.iter()
+			.flat_map(|event| event.renames().map(|(_, to)| to.to_owned()))
+			.collect::<Vec<_>>();
+		events.retain(|event| {
+			event.renames().next().is_some()
+				|| event
+					.paths()
+					.all(|(path, _)| !renamed.iter().any(|to| to == path))
+		});
+
+		events
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::event::filekind::DataChange;
+
+	use super::*;
+
+	fn fs(path: &str, kind: FileEventKind) -> Event {
+		Event {
+			tags: vec![
+				Tag::Source(Source::Filesystem),
+				Tag::FileEventKind(kind),
+				Tag::Path {
+					path: path.into(),
+					file_type: None,
+				},
+			],
+			metadata: Default::default(),
+		}
+	}
+
+	fn rename(from: &str, to: &str) -> Event {
+		Event {
+			tags: vec![
+				Tag::Source(Source::Filesystem),
+				Tag::FileEventKind(FileEventKind::Modify(ModifyKind::Name(RenameMode::Both))),
+				Tag::Path {
+					path: from.into(),
+					file_type: None,
+				},
+				Tag::Path {
+					path: to.into(),
+					file_type: None,
+				},
+				Tag::Rename {
+					from: from.into(),
+					to: to.into(),
+				},
+			],
+			metadata: Default::default(),
+		}
+	}
+
+	const CREATE: FileEventKind = FileEventKind::Create(CreateKind::File);
+	const WRITE: FileEventKind = FileEventKind::Modify(ModifyKind::Data(DataChange::Content));
+	const CLOSE: FileEventKind = FileEventKind::Access(AccessKind::Close(AccessMode::Write));
+	const REMOVE: FileEventKind = FileEventKind::Remove(RemoveKind::File);
+
+	#[test]
+	fn save_is_one_event() {
+		let events = vec![
+			fs("/a", CREATE),
+			fs("/a", WRITE),
+			fs("/a", WRITE),
+			fs("/a", CLOSE),
+		];
+		assert_eq!(coalesce(events), vec![fs("/a", CREATE)]);
+	}
+
+	#[test]
+	fn modifications_are_one_event() {
+		let events = vec![fs("/a", WRITE), fs("/a", CLOSE), fs("/b", WRITE)];
+		assert_eq!(coalesce(events), vec![fs("/a", CLOSE), fs("/b", WRITE)]);
+	}
+
+	#[test]
+	fn created_then_removed_is_nothing() {
+		let events = vec![fs("/a", CREATE), fs("/a", WRITE), fs("/a", REMOVE)];
+		assert_eq!(coalesce(events), Vec::new());
+	}
+
+	#[test]
+	fn removed_then_created_is_modified() {
+		let events = vec![fs("/a", REMOVE), fs("/a", CREATE)];
+		assert_eq!(
+			coalesce(events),
+			vec![fs("/a", FileEventKind::Modify(ModifyKind::Any))]
+		);
+	}
+
+	#[test]
+	fn renames_are_kept() {
+		let events = vec![fs("/a", WRITE), rename("/a", "/b")];
+		assert_eq!(coalesce(events), vec![rename("/a", "/b")]);
+	}
+
+	#[test]
+	fn created_then_renamed_is_created() {
+		let events = vec![
+			fs("/a.tmp", CREATE),
+			fs("/a.tmp", WRITE),
+			rename("/a.tmp", "/a"),
+		];
+		assert_eq!(
+			coalesce(events),
+			vec![fs("/a", FileEventKind::Create(CreateKind::Any))]
+		);
+	}
+
+	#[test]
+	fn accesses_are_nothing() {
+		let open = FileEventKind::Access(AccessKind::Open(AccessMode::Read));
+		let events = vec![fs("/a", open), fs("/b", WRITE), fs("/a", open)];
+		assert_eq!(coalesce(events), vec![fs("/b", WRITE)]);
+	}
+
+	#[test]
+	fn last_tags_and_metadata_are_kept() {
+		let with_context = |mut event: Event, pid: u32| {
+			event.tags.push(Tag::Process(pid));
+			event
+				.metadata
+				.insert("watched-root".into(), vec![pid.to_string()]);
+			event
+		};
+
+		let events = vec![
+			with_context(fs("/a", CREATE), 1),
+			with_context(fs("/a", WRITE), 2),
+			with_context(rename("/b", "/c"), 3),
+		];
+		assert_eq!(
+			coalesce(events),
+			vec![
+				with_context(fs("/a", CREATE), 2),
+				with_context(rename("/b", "/c"), 3)
+			]
+		);
+	}
+
+	#[test]
+	fn other_events_are_deduped() {
+		let empty = Event::default();
+		let events = vec![
+			empty.clone(),
+			fs("/a", WRITE),
+			empty.clone(),
+			fs("/a", WRITE),
+		];
+		assert_eq!(coalesce(events), vec![empty, fs("/a", WRITE)]);
+	}
+}
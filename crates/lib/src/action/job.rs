@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// The identifier of a job.
+///
+/// Jobs are independent sets of commands supervised by the same Watchexec instance, each with its
+/// own process(es), and driven from the same event stream and action handler.
+///
+/// The default job (`JobId::default()`) runs the [`commands`][super::WorkingData::commands] of the
+/// working data. Other jobs are named, and their commands are defined in
+/// [`jobs`][super::WorkingData::jobs]. Use [`Outcome::Job`][super::Outcome::Job] to address them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(Option<String>);
+
+impl JobId {
+	/// The name of the job, or `None` for the default job.
+	#[must_use]
+	pub fn name(&self) -> Option<&str> {
+		self.0.as_deref()
+	}
+
+	/// Whether this is the default job.
+	#[must_use]
+	pub const fn is_default(&self) -> bool {
+		self.0.is_none()
+	}
+}
+
+impl From<String> for JobId {
+	fn from(name: String) -> Self {
+		Self(Some(name))
+	}
+}
+
+impl From<&str> for JobId {
+	fn from(name: &str) -> Self {
+		Self(Some(name.into()))
+	}
+}
+
+impl fmt::Display for JobId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.name().unwrap_or("default"))
+	}
+}
@@ -2,6 +2,8 @@ use std::time::Duration;
 
 use watchexec_signals::Signal;
 
+use super::JobId;
+
 /// The outcome to execute when an action is triggered.
 ///
 /// Logic against the state of the command should be expressed using these variants, rather than
@@ -28,6 +30,12 @@ pub enum Outcome {
 	/// Does nothing if the command isn't running.
 	Wait,
 
+	/// Wait for the command to be ready.
+	///
+	/// Returns immediately if the command doesn't have a readiness probe, and when it ends. Does
+	/// nothing if the command isn't running.
+	WaitReady,
+
 	/// Sleep for some duration.
 	Sleep(Duration),
 
@@ -36,6 +44,37 @@ pub enum Outcome {
 	/// This does not wait for the command to complete.
 	Signal(Signal),
 
+	/// Set a time limit on the command, starting now.
+	///
+	/// When the limit is reached, the command is sent each signal of the `escalation` in turn,
+	/// waiting for its duration before moving on to the next, and killed if it's still running at
+	/// the end. Its completion event is tagged with [`Tag::Timeout`][crate::event::Tag::Timeout].
+	///
+	/// This does not wait for the command to complete. It should be used after a `Start`.
+	Timeout {
+		/// How long the command may run for.
+		after: Duration,
+
+		/// The signals to stop the command with, and how long to wait after each.
+		escalation: Vec<(Signal, Duration)>,
+	},
+
+	/// Pause reacting to changes.
+	///
+	/// See [`Watchexec::pause()`][crate::Watchexec::pause] for details.
+	///
+	/// Unless it comes after an outcome which waits (like [`Sleep`](Outcome::Sleep) or
+	/// [`Wait`](Outcome::Wait)), this takes effect as soon as the action handler returns, and
+	/// can't be cancelled by a later action.
+	Pause,
+
+	/// Resume reacting to changes.
+	///
+	/// See [`Watchexec::resume()`][crate::Watchexec::resume] for details.
+	///
+	/// This takes effect as soon as the action handler returns, like [`Pause`](Outcome::Pause).
+	Resume,
+
 	/// Clear the (terminal) screen.
 	Clear,
 
@@ -59,6 +98,13 @@ pub enum Outcome {
 
 	/// Race both outcomes: run both at once, and when one finishes, cancel the other.
 	Race(Box<Outcome>, Box<Outcome>),
+
+	/// Apply the outcome to a particular job.
+	///
+	/// Outcomes which concern the command (like `Start`, `Stop`, `Signal`, `Wait`, `IfRunning`)
+	/// apply to the default job unless they are nested in this variant, in which case they apply
+	/// to the given job. Other outcomes behave as normal.
+	Job(JobId, Box<Outcome>),
 }
 
 impl Default for Outcome {
@@ -96,6 +142,24 @@ impl Outcome {
 		Self::Race(Box::new(one), Box::new(two))
 	}
 
+	/// Convenience function to apply an outcome to a particular job.
+	#[must_use]
+	pub fn job(id: impl Into<JobId>, outcome: Self) -> Self {
+		Self::Job(id.into(), Box::new(outcome))
+	}
+
+	/// Convenience function to set a time limit on the subprocess.
+	#[must_use]
+	pub fn timeout(
+		after: Duration,
+		escalation: impl IntoIterator<Item = (Signal, Duration)>,
+	) -> Self {
+		Self::Timeout {
+			after,
+			escalation: escalation.into_iter().collect(),
+		}
+	}
+
 	/// Pattern that waits for the subprocess to complete before executing the outcome.
 	#[must_use]
 	pub fn wait(and_then: Self) -> Self {
@@ -108,7 +172,31 @@ impl Outcome {
 		Self::both(Self::race(Self::Sleep(timeout), Self::Wait), and_then)
 	}
 
+	/// Pattern that stops the subprocess by escalating through signals.
+	///
+	/// Each signal is sent in turn, and the subprocess is given its duration to exit before moving
+	/// on to the next. If it's still running at the end, it's forcefully stopped. For example,
+	/// `[(Interrupt, 30s), (Terminate, 10s)]` gives it 30 seconds to drain after a SIGINT, then 10
+	/// more after a SIGTERM, before killing it.
+	#[must_use]
+	pub fn escalate(escalation: impl IntoIterator<Item = (Signal, Duration)>) -> Self {
+		Self::sequence(
+			escalation
+				.into_iter()
+				.flat_map(|(signal, grace)| {
+					[
+						Self::Signal(signal),
+						Self::race(Self::Sleep(grace), Self::Wait),
+					]
+				})
+				.chain(std::iter::once(Self::Stop)),
+		)
+	}
+
 	/// Resolves the outcome given the current state of the subprocess.
+	///
+	/// This only resolves for the default job: outcomes nested in a [`Job`](Outcome::Job) are left
+	/// as-is, and are instead resolved when applied.
 	#[must_use]
 	pub fn resolve(self, is_running: bool) -> Self {
 		match (is_running, self) {
@@ -153,4 +241,43 @@ mod test {
 			Outcome::Both(Box::new(Outcome::Stop), Box::new(Outcome::Wait))
 		);
 	}
+
+	#[test]
+	fn escalate_to_stop() {
+		assert_eq!(
+			Outcome::escalate([
+				(Signal::Interrupt, Duration::from_secs(30)),
+				(Signal::Terminate, Duration::from_secs(10)),
+			]),
+			Outcome::sequence(
+				[
+					Outcome::Signal(Signal::Interrupt),
+					Outcome::race(Outcome::Sleep(Duration::from_secs(30)), Outcome::Wait),
+					Outcome::Signal(Signal::Terminate),
+					Outcome::race(Outcome::Sleep(Duration::from_secs(10)), Outcome::Wait),
+					Outcome::Stop,
+				]
+				.into_iter()
+			)
+		);
+		assert_eq!(Outcome::escalate([]), Outcome::Stop);
+	}
+
+	#[test]
+	fn job_if_runnings_are_not_resolved() {
+		assert_eq!(
+			Outcome::both(
+				Outcome::if_running(Outcome::Stop, Outcome::Start),
+				Outcome::job("other", Outcome::if_running(Outcome::Wait, Outcome::Start))
+			)
+			.resolve(true),
+			Outcome::Both(
+				Box::new(Outcome::Stop),
+				Box::new(Outcome::job(
+					"other",
+					Outcome::if_running(Outcome::Wait, Outcome::Start)
+				))
+			)
+		);
+	}
 }
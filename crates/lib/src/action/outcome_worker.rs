@@ -11,28 +11,32 @@ use futures::{
 };
 use tokio::{
 	spawn,
-	sync::{mpsc, watch::Receiver},
+	sync::{
+		mpsc,
+		watch::{self, Receiver},
+	},
 	time::sleep,
 };
 use tracing::{debug, error, info, trace, warn};
 
 use crate::{
-	command::Supervisor,
+	command::{Supervisor, SupervisorOptions},
 	error::RuntimeError,
 	event::{Event, Priority},
 };
 
-use super::{process_holder::ProcessHolder, Outcome, WorkingData};
+use super::{process_holder::Jobs, JobId, Outcome, WorkingData};
 
 #[derive(Clone)]
 pub struct OutcomeWorker {
 	events: Arc<[Event]>,
 	working: Receiver<WorkingData>,
-	process: ProcessHolder,
+	jobs: Jobs,
 	gen: usize,
 	gencheck: Arc<AtomicUsize>,
 	errors_c: mpsc::Sender<RuntimeError>,
 	events_c: priority::Sender<Event, Priority>,
+	paused: Arc<watch::Sender<bool>>,
 }
 
 impl OutcomeWorker {
@@ -40,30 +44,33 @@ impl OutcomeWorker {
 		Default::default()
 	}
 
+	#[allow(clippy::too_many_arguments)]
 	pub fn spawn(
 		outcome: Outcome,
 		events: Arc<[Event]>,
 		working: Receiver<WorkingData>,
-		process: ProcessHolder,
+		jobs: Jobs,
 		gencheck: Arc<AtomicUsize>,
 		errors_c: mpsc::Sender<RuntimeError>,
 		events_c: priority::Sender<Event, Priority>,
+		paused: Arc<watch::Sender<bool>>,
 	) {
 		let gen = gencheck.fetch_add(1, Ordering::SeqCst).wrapping_add(1);
 		let this = Self {
 			events,
 			working,
-			process,
+			jobs,
 			gen,
 			gencheck,
 			errors_c,
 			events_c,
+			paused,
 		};
 
 		debug!(?outcome, %gen, "spawning outcome worker");
 		spawn(async move {
 			let errors_c = this.errors_c.clone();
-			match this.apply(outcome.clone()).await {
+			match this.apply(JobId::default(), outcome.clone()).await {
 				Err(err) => {
 					if matches!(err, RuntimeError::Exit) {
 						info!(%gen, "propagating graceful exit");
@@ -97,7 +104,7 @@ impl OutcomeWorker {
 	}
 
 	#[async_recursion::async_recursion]
-	async fn apply(&self, outcome: Outcome) -> Result<(), RuntimeError> {
+	async fn apply(&self, job: JobId, outcome: Outcome) -> Result<(), RuntimeError> {
 		macro_rules! notry {
 			($e:expr) => {
 				match self.check_gen($e).await {
@@ -106,53 +113,92 @@ impl OutcomeWorker {
 				}
 			};
 		}
-		match (notry!(self.process.is_some()), outcome) {
+
+		let process = notry!(self.jobs.get(&job));
+		match (notry!(process.is_some()), outcome) {
 			(_, Outcome::DoNothing) => {}
 			(_, Outcome::Exit) => {
 				return Err(RuntimeError::Exit);
 			}
 			(true, Outcome::Stop) => {
-				notry!(self.process.kill());
-				notry!(self.process.wait())?;
-				notry!(self.process.drop_inner());
+				notry!(process.kill());
+				notry!(process.wait())?;
+				notry!(process.drop_inner());
 			}
-			(false, o @ (Outcome::Stop | Outcome::Wait | Outcome::Signal(_))) => {
-				debug!(outcome=?o, "meaningless without a process, not doing anything");
+			(
+				false,
+				o @ (Outcome::Stop
+				| Outcome::Wait
+				| Outcome::WaitReady
+				| Outcome::Signal(_)
+				| Outcome::Timeout { .. }),
+			) => {
+				debug!(outcome=?o, %job, "meaningless without a process, not doing anything");
 			}
 			(_, Outcome::Start) => {
-				let (cmds, grouped, pre_spawn_handler, post_spawn_handler) = {
+				let (cmds, options, pre_spawn_handler, post_spawn_handler) = {
 					let wrk = self.working.borrow();
 					(
-						wrk.commands.clone(),
-						wrk.grouped,
+						if job.is_default() {
+							wrk.commands.clone()
+						} else {
+							wrk.jobs.get(&job).cloned().unwrap_or_default()
+						},
+						if job.is_default() {
+							SupervisorOptions {
+								job: job.clone(),
+								grouped: wrk.grouped,
+								capture: wrk.capture,
+								pty: wrk.pty,
+								restart: wrk.restart,
+								readiness: wrk.readiness.clone(),
+								ready_timeout: wrk.ready_timeout,
+								fan_out: wrk.fan_out,
+							}
+						} else {
+							// the other settings are about the main command, not auxiliary jobs
+							SupervisorOptions {
+								job: job.clone(),
+								grouped: wrk.grouped,
+								..Default::default()
+							}
+						},
 						wrk.pre_spawn_handler.clone(),
 						wrk.post_spawn_handler.clone(),
 					)
 				};
 
 				if cmds.is_empty() {
-					warn!("tried to start commands without anything to run");
+					warn!(%job, "tried to start commands without anything to run");
 				} else {
-					trace!("spawning supervisor for command");
+					trace!(%job, "spawning supervisor for command");
 					let sup = Supervisor::spawn(
 						self.errors_c.clone(),
 						self.events_c.clone(),
 						cmds,
-						grouped,
+						options,
 						self.events.clone(),
 						pre_spawn_handler,
 						post_spawn_handler,
 					)?;
-					notry!(self.process.replace(sup));
+					notry!(process.replace(sup));
 				}
 			}
 
 			(true, Outcome::Signal(sig)) => {
-				notry!(self.process.signal(sig));
+				notry!(process.signal(sig));
+			}
+
+			(true, Outcome::Timeout { after, escalation }) => {
+				notry!(process.timeout(after, escalation));
 			}
 
 			(true, Outcome::Wait) => {
-				notry!(self.process.wait())?;
+				notry!(process.wait())?;
+			}
+
+			(true, Outcome::WaitReady) => {
+				notry!(process.wait_ready())?;
 			}
 
 			(_, Outcome::Sleep(time)) => {
@@ -161,6 +207,16 @@ impl OutcomeWorker {
 				trace!(?time, "done sleeping");
 			}
 
+			(_, Outcome::Pause) => {
+				debug!("pausing");
+				self.paused.send_replace(true);
+			}
+
+			(_, Outcome::Resume) => {
+				debug!("resuming");
+				self.paused.send_replace(false);
+			}
+
 			(_, Outcome::Clear) => {
 				clearscreen::clear()?;
 			}
@@ -178,30 +234,34 @@ impl OutcomeWorker {
 			}
 
 			(true, Outcome::IfRunning(then, _)) => {
-				notry!(self.apply(*then))?;
+				notry!(self.apply(job, *then))?;
 			}
 			(false, Outcome::IfRunning(_, otherwise)) => {
-				notry!(self.apply(*otherwise))?;
+				notry!(self.apply(job, *otherwise))?;
 			}
 
 			(_, Outcome::Both(one, two)) => {
-				if let Err(err) = notry!(self.apply(*one)) {
+				if let Err(err) = notry!(self.apply(job.clone(), *one)) {
 					debug!(
 						"first outcome failed, sending an error but proceeding to the second anyway"
 					);
 					notry!(self.errors_c.send(err)).ok();
 				}
 
-				notry!(self.apply(*two))?;
+				notry!(self.apply(job, *two))?;
 			}
 
 			(_, Outcome::Race(one, two)) => {
 				if let Either::Left((Err(err), _)) | Either::Right((Err(err), _)) =
-					select(self.apply(*one), self.apply(*two)).await
+					select(self.apply(job.clone(), *one), self.apply(job, *two)).await
 				{
 					return Err(err);
 				}
 			}
+
+			(_, Outcome::Job(id, outcome)) => {
+				notry!(self.apply(id, *outcome))?;
+			}
 		}
 
 		Ok(())
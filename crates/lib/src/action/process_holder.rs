@@ -1,11 +1,31 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use tokio::sync::RwLock;
+use tokio::sync::{watch, Mutex, RwLock};
 use tracing::trace;
 use watchexec_signals::Signal;
 
 use crate::{command::Supervisor, error::RuntimeError};
 
+use super::JobId;
+
+#[derive(Clone, Debug, Default)]
+pub struct Jobs(Arc<Mutex<HashMap<JobId, ProcessHolder>>>);
+impl Jobs {
+	pub async fn get(&self, id: &JobId) -> ProcessHolder {
+		self.0.lock().await.entry(id.clone()).or_default().clone()
+	}
+
+	/// Watches on the readiness of the jobs which aren't ready.
+	pub async fn unready(&self) -> Vec<watch::Receiver<bool>> {
+		let holders: Vec<ProcessHolder> = self.0.lock().await.values().cloned().collect();
+		let mut unready = Vec::new();
+		for holder in holders {
+			unready.extend(holder.unready().await);
+		}
+		unready
+	}
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct ProcessHolder(Arc<RwLock<Option<Supervisor>>>);
 impl ProcessHolder {
@@ -17,6 +37,22 @@ impl ProcessHolder {
 			.map_or(false, Supervisor::is_running)
 	}
 
+	/// A watch on the readiness of the supervisor, if it's running and not ready.
+	///
+	/// The current value of the watch is marked as seen, so that it changes when the supervisor
+	/// becomes ready.
+	pub async fn unready(&self) -> Option<watch::Receiver<bool>> {
+		let sup = self.0.read().await;
+		let sup = sup.as_ref().filter(|sup| sup.is_running())?;
+		let mut ready = sup.ready_watch();
+		let is_ready = *ready.borrow_and_update();
+		if is_ready {
+			None
+		} else {
+			Some(ready)
+		}
+	}
+
 	pub async fn is_some(&self) -> bool {
 		self.0.read().await.is_some()
 	}
@@ -47,6 +83,16 @@ impl ProcessHolder {
 		}
 	}
 
+	pub async fn timeout(&self, after: Duration, escalation: Vec<(Signal, Duration)>) {
+		if let Some(p) = self.0.read().await.as_ref() {
+			trace!("setting supervisor time limit");
+			p.timeout(after, escalation).await;
+			trace!("set supervisor time limit");
+		} else {
+			trace!("not setting time limit: no supervisor");
+		}
+	}
+
 	pub async fn kill(&self) {
 		if let Some(p) = self.0.read().await.as_ref() {
 			trace!("killing supervisor");
@@ -57,6 +103,18 @@ impl ProcessHolder {
 		}
 	}
 
+	pub async fn wait_ready(&self) -> Result<(), RuntimeError> {
+		if let Some(p) = self.0.read().await.as_ref() {
+			trace!("waiting on supervisor readiness");
+			p.wait_ready().await?;
+			trace!("supervisor is ready");
+		} else {
+			trace!("not waiting: no supervisor");
+		}
+
+		Ok(())
+	}
+
 	pub async fn wait(&self) -> Result<(), RuntimeError> {
 		if let Some(p) = self.0.read().await.as_ref() {
 			trace!("waiting on supervisor");
@@ -5,7 +5,9 @@ use std::{
 };
 
 use async_priority_channel as priority;
+use futures::future::select_all;
 use tokio::{
+	select,
 	sync::{
 		mpsc,
 		watch::{self},
@@ -16,26 +18,40 @@ use tracing::{debug, info, trace};
 
 use crate::{
 	error::{CriticalError, RuntimeError},
-	event::{Event, Priority},
+	event::{Event, Priority, Source, Tag},
 	handler::rte,
 };
 
-use super::{outcome_worker::OutcomeWorker, process_holder::ProcessHolder, Action, WorkingData};
+use super::{
+	coalesce::coalesce, outcome_worker::OutcomeWorker, process_holder::Jobs, Action, JobId,
+	Outcome, PausedEvents, WorkingData,
+};
 
 /// The main worker of a Watchexec process.
 ///
 /// This is the main loop of the process. It receives events from the event channel, filters them,
 /// debounces them, obtains the desired outcome of an actioned event, calls the appropriate handlers
 /// and schedules processes as needed.
+///
+/// While any command set is not yet [ready](crate::command::Readiness), events are held instead of
+/// being actioned, unless an urgent event comes in.
+///
+/// While `paused` is true, changes are dropped or held until it's false again, as configured by
+/// [`WorkingData::paused_events`].
 pub async fn worker(
 	working: watch::Receiver<WorkingData>,
 	errors: mpsc::Sender<RuntimeError>,
 	events_tx: priority::Sender<Event, Priority>,
 	events: priority::Receiver<Event, Priority>,
+	paused: Arc<watch::Sender<bool>>,
 ) -> Result<(), CriticalError> {
 	let mut last = Instant::now();
 	let mut set = Vec::new();
-	let process = ProcessHolder::default();
+	let mut held = Vec::new();
+	let mut is_paused = paused.subscribe();
+	let mut urgent = false;
+	let mut unready: Vec<watch::Receiver<bool>> = Vec::new();
+	let jobs = Jobs::default();
 	let outcome_gen = OutcomeWorker::newgen();
 
 	loop {
@@ -47,6 +63,9 @@ pub async fn worker(
 		let maxtime = if set.is_empty() {
 			trace!("nothing in set, waiting forever for next event");
 			Duration::from_secs(u64::MAX)
+		} else if !unready.is_empty() {
+			trace!("commands not ready yet, waiting for them or for the next event");
+			Duration::from_secs(u64::MAX)
 		} else {
 			working.borrow().throttle.saturating_sub(last.elapsed())
 		};
@@ -61,7 +80,14 @@ pub async fn worker(
 			trace!("out of throttle on recycle");
 		} else {
 			trace!(?maxtime, "waiting for event");
-			let maybe_event = timeout(maxtime, events.recv()).await;
+			let maybe_event = timeout(maxtime, async {
+				select! {
+					event = events.recv() => Some(event),
+					Ok(()) = is_paused.changed() => None,
+					() = readiness_changed(&mut unready), if !unready.is_empty() => None,
+				}
+			})
+			.await;
 			if events.is_closed() {
 				trace!("events channel closed during timeout, stopping");
 				break;
@@ -72,8 +98,20 @@ pub async fn worker(
 					trace!("timed out, cycling");
 					continue;
 				}
-				Ok(Err(_empty)) => break,
-				Ok(Ok((event, priority))) => {
+				Ok(None) => {
+					// whichever it was, readiness is looked at again before actioning
+					unready.clear();
+					if !*is_paused.borrow() && !held.is_empty() {
+						debug!(events=%held.len(), "resumed, actioning held events");
+						if set.is_empty() {
+							last = Instant::now();
+						}
+						set.append(&mut held);
+					}
+					continue;
+				}
+				Ok(Some(Err(_empty))) => break,
+				Ok(Some(Ok((event, priority)))) => {
 					trace!(?event, ?priority, "got event");
 
 					if priority == Priority::Urgent {
@@ -98,6 +136,19 @@ pub async fn worker(
 						}
 					}
 
+					if priority != Priority::Urgent && *is_paused.borrow() && is_change(&event) {
+						match working.borrow().paused_events {
+							PausedEvents::Drop => {
+								trace!("paused, dropping event");
+							}
+							PausedEvents::Accumulate => {
+								trace!("paused, holding event");
+								held.push(event);
+							}
+						}
+						continue;
+					}
+
 					if set.is_empty() {
 						trace!("event is the first, resetting throttle window");
 						last = Instant::now();
@@ -107,6 +158,7 @@ pub async fn worker(
 
 					if priority == Priority::Urgent {
 						trace!("urgent event, by-passing throttle");
+						urgent = true;
 					} else {
 						let elapsed = last.elapsed();
 						if elapsed < working.borrow().throttle {
@@ -118,12 +170,30 @@ pub async fn worker(
 			}
 		}
 
+		if !urgent {
+			unready = jobs.unready().await;
+			if !unready.is_empty() {
+				trace!(jobs=%unready.len(), "commands not ready yet, holding events");
+				continue;
+			}
+		}
+		unready.clear();
+
 		trace!("out of throttle, starting action process");
 		last = Instant::now();
+		urgent = false;
+
+		if working.borrow().coalesce {
+			set = coalesce(take(&mut set));
+			if set.is_empty() {
+				debug!("events had no net effect, skipping action");
+				continue;
+			}
+		}
 
 		#[allow(clippy::iter_with_drain)]
 		let events = Arc::from(take(&mut set).into_boxed_slice());
-		let action = Action::new(Arc::clone(&events));
+		let action = Action::new(Arc::clone(&events), paused.subscribe());
 		info!(?action, "action constructed");
 
 		debug!("running action handler");
@@ -146,17 +216,21 @@ pub async fn worker(
 		let outcome = outcome.get().cloned().unwrap_or_default();
 		debug!(?outcome, "action handler finished");
 
-		let outcome = outcome.resolve(process.is_running().await);
+		let mut outcome = outcome.resolve(jobs.get(&JobId::default()).await.is_running().await);
 		info!(?outcome, "outcome resolved");
 
+		// applied here so that a later action can't cancel them before they take effect
+		apply_pause(&mut outcome, &paused);
+
 		OutcomeWorker::spawn(
 			outcome,
 			events,
 			working.clone(),
-			process.clone(),
+			jobs.clone(),
 			outcome_gen.clone(),
 			errors.clone(),
 			events_tx.clone(),
+			paused.clone(),
 		);
 		debug!("action process done");
 	}
@@ -164,3 +238,39 @@ pub async fn worker(
 	debug!("action worker finished");
 	Ok(())
 }
+
+/// Returns when the readiness of any of the watched jobs changes.
+async fn readiness_changed(watches: &mut [watch::Receiver<bool>]) {
+	// a closed watch counts as a change too: its supervisor is gone
+	let (_closed, _, _) =
+		select_all(watches.iter_mut().map(|ready| Box::pin(ready.changed()))).await;
+}
+
+/// Applies the pauses and resumes at the start of an outcome, replacing them with `DoNothing`.
+///
+/// Returns whether the outcome worker would have got past this outcome without waiting, that is
+/// whether the pauses and resumes after it can be applied now too.
+fn apply_pause(outcome: &mut Outcome, paused: &watch::Sender<bool>) -> bool {
+	match outcome {
+		Outcome::Pause | Outcome::Resume => {
+			let pause = *outcome == Outcome::Pause;
+			trace!(pause, "applying pause outcome");
+			paused.send_replace(pause);
+			*outcome = Outcome::DoNothing;
+			true
+		}
+		Outcome::Both(one, two) => apply_pause(one, paused) && apply_pause(two, paused),
+		Outcome::Job(_, inner) => apply_pause(inner, paused),
+		Outcome::DoNothing | Outcome::Clear | Outcome::Reset | Outcome::Signal(_) => true,
+		_ => false,
+	}
+}
+
+/// Whether an event is about a change, which pausing applies to.
+fn is_change(event: &Event) -> bool {
+	event.paths().next().is_some()
+		|| event
+			.tags
+			.iter()
+			.any(|tag| matches!(tag, Tag::Source(Source::Filesystem | Source::Time)))
+}
@@ -1,5 +1,7 @@
 use std::{
+	collections::HashMap,
 	fmt,
+	num::NonZeroUsize,
 	sync::{Arc, Weak},
 	time::Duration,
 };
@@ -7,12 +9,17 @@ use std::{
 use once_cell::sync::OnceCell;
 use tokio::{
 	process::Command as TokioCommand,
-	sync::{Mutex, OwnedMutexGuard},
+	sync::{watch, Mutex, OwnedMutexGuard},
 };
 
-use crate::{command::Command, event::Event, filter::Filterer, handler::HandlerLock};
+use crate::{
+	command::{Command, OutputBuffer, Readiness, RestartPolicy, Step, READY_TIMEOUT},
+	event::Event,
+	filter::Filterer,
+	handler::HandlerLock,
+};
 
-use super::Outcome;
+use super::{JobId, Outcome};
 
 /// The configuration of the [action][crate::action] worker.
 ///
@@ -66,8 +73,28 @@ pub struct WorkingData {
 
 	/// Commands to execute.
 	///
-	/// These will be run in order, and an error will stop early.
-	pub commands: Vec<Command>,
+	/// These will be run in order, or as a graph if steps depend on others with
+	/// [`Step::after()`], skipping those whose [`Continuation`][crate::command::Continuation]
+	/// doesn't match how the previous commands ended. An error (e.g. failing to spawn) will stop
+	/// early.
+	///
+	/// These are the commands of the default job. See [`jobs`](WorkingData::jobs) for more.
+	pub commands: Vec<Step>,
+
+	/// Commands to execute for named jobs.
+	///
+	/// Each job is supervised independently from the others and from the default job, which runs
+	/// the [`commands`](WorkingData::commands). Use [`Outcome::Job`] to start, stop, signal, etc
+	/// a named job; completion events for named jobs are tagged with
+	/// [`Tag::Job`](crate::event::Tag::Job).
+	///
+	/// Named jobs are run in process groups as set by [`grouped`](WorkingData::grouped), but the
+	/// other command settings ([`capture`](WorkingData::capture), [`pty`](WorkingData::pty),
+	/// [`restart`](WorkingData::restart), [`readiness`](WorkingData::readiness), and
+	/// [`fan_out`](WorkingData::fan_out)) only apply to the default job.
+	///
+	/// An entry for the default job in this map is ignored.
+	pub jobs: HashMap<JobId, Vec<Step>>,
 
 	/// Whether to use process groups (on Unix) or job control (on Windows) to run the command.
 	///
@@ -78,6 +105,94 @@ pub struct WorkingData {
 	/// meantime.
 	pub grouped: bool,
 
+	/// Whether to capture the output of commands, and how many lines of it to keep per run.
+	///
+	/// By default, commands inherit Watchexec's standard output and error. When this is set, they
+	/// are instead piped through Watchexec: the output is still written to Watchexec's own
+	/// standard output and error, but each line is also emitted as an event with a
+	/// [`Tag::ProcessOutput`](crate::event::Tag::ProcessOutput), and the last lines of each run
+	/// are kept in an [`OutputBuffer`], available from [`PostSpawn::output`].
+	///
+	/// Note that output events go through filtering and the action handler like any other, so
+	/// the action handler should be prepared to handle them.
+	///
+	/// This only applies to the default job.
+	pub capture: Option<NonZeroUsize>,
+
+	/// Whether to run commands in a pseudo-terminal.
+	///
+	/// Many programs change their output (like disabling colours and progress bars) when they're
+	/// not writing to a terminal. When this is enabled, each command gets its own pseudo-terminal
+	/// as its standard input, output, and error, and as its controlling terminal. Its output is
+	/// forwarded to Watchexec's standard output, and changes to the size of Watchexec's terminal
+	/// are forwarded to it. If output capture is also enabled, the output is captured from the
+	/// terminal, as standard output.
+	///
+	/// Commands in a pseudo-terminal are always run in their own session and process group,
+	/// regardless of [`grouped`](WorkingData::grouped).
+	///
+	/// This only applies to the default job. It's only supported on Unix, and ignored elsewhere.
+	pub pty: bool,
+
+	/// Whether and how to restart command sets which end on their own.
+	///
+	/// By default, when a set ends, nothing happens until the next action. With a policy, sets
+	/// which end without having been stopped (crashing servers, for example) are run again after
+	/// a delay which grows with each consecutive restart. See [`RestartPolicy`] for the details.
+	///
+	/// The policy only applies to the default job, and is read when the set is started.
+	pub restart: Option<RestartPolicy>,
+
+	/// How to determine when command sets are ready, after they're started.
+	///
+	/// By default, sets are considered ready as soon as they start. With a probe, they are only
+	/// ready once it succeeds: until then, events are held, and actions aren't taken. This is
+	/// useful to avoid restarting a service while it's still starting up. See [`Readiness`] for
+	/// the available probes.
+	///
+	/// The probe only applies to the default job, and is read when the set is started.
+	pub readiness: Option<Readiness>,
+
+	/// How long to wait for command sets to be ready, before giving up on it.
+	///
+	/// When a set with a [readiness probe](WorkingData::readiness) isn't ready within this time,
+	/// a [`RuntimeError::ReadinessTimeout`](crate::error::RuntimeError::ReadinessTimeout) is
+	/// emitted, and events stop being held for it: the set keeps running, but it's handled as if
+	/// it were ready. `None` waits for as long as the set runs. Defaults to 60 seconds.
+	///
+	/// This only applies to the default job, and is read when the set is started.
+	pub ready_timeout: Option<Duration>,
+
+	/// Whether to run command sets once per changed path, and how many at once.
+	///
+	/// By default, a set is run once per action, with all the events of the action. In fan-out
+	/// mode, it's instead run once for each path of the action's events, with only the events of
+	/// that path, such that [placeholders](crate::command::Command::substitute) and the events
+	/// given to the [pre-spawn handler](WorkingData::pre_spawn_handler) are about that one path.
+	/// Up to this many runs go at once, and the set only ends once they all have.
+	///
+	/// Actions without paths still run the set once. This only applies to the default job.
+	pub fan_out: Option<NonZeroUsize>,
+
+	/// What to do with changes while [paused](crate::Watchexec::pause).
+	///
+	/// Only events which are about changes (from the filesystem, or with paths) are affected by
+	/// pausing. Other events, like signals, key presses, or process completions, are actioned as
+	/// usual, so that Watchexec can still be controlled and stopped while paused.
+	pub paused_events: PausedEvents,
+
+	/// Whether to reduce the events of an action to their net effect.
+	///
+	/// By default, every event which passes the filter goes into the action, so a single save in
+	/// an editor may give several events for the same file (create, modify, modify, close), and a
+	/// file created then removed before the throttle expires still causes an action. With this,
+	/// filesystem events are instead reduced to one per path, for the net change to that path:
+	/// created, modified, removed, or renamed; paths which end up as they started, or were only
+	/// accessed, are left out. Other events are kept, without duplicates.
+	///
+	/// If nothing is left, the action is skipped entirely, and the action handler isn't called.
+	pub coalesce: bool,
+
 	/// The filterer implementation to use when filtering events.
 	///
 	/// The default is a no-op, which will always pass every event.
@@ -89,7 +204,16 @@ impl fmt::Debug for WorkingData {
 		f.debug_struct("WorkingData")
 			.field("throttle", &self.throttle)
 			.field("commands", &self.commands)
+			.field("jobs", &self.jobs)
 			.field("grouped", &self.grouped)
+			.field("capture", &self.capture)
+			.field("pty", &self.pty)
+			.field("restart", &self.restart)
+			.field("readiness", &self.readiness)
+			.field("ready_timeout", &self.ready_timeout)
+			.field("fan_out", &self.fan_out)
+			.field("paused_events", &self.paused_events)
+			.field("coalesce", &self.coalesce)
 			.field("filterer", &self.filterer)
 			.finish_non_exhaustive()
 	}
@@ -103,12 +227,32 @@ impl Default for WorkingData {
 			pre_spawn_handler: Default::default(),
 			post_spawn_handler: Default::default(),
 			commands: Vec::new(),
+			jobs: HashMap::new(),
 			grouped: true,
+			capture: None,
+			pty: false,
+			restart: None,
+			readiness: None,
+			ready_timeout: Some(READY_TIMEOUT),
+			fan_out: None,
+			paused_events: PausedEvents::default(),
+			coalesce: false,
 			filterer: Arc::new(()),
 		}
 	}
 }
 
+/// What to do with changes while paused.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PausedEvents {
+	/// Drop them: nothing happens when resuming.
+	#[default]
+	Drop,
+
+	/// Keep them, and action them together when resuming, as if they had all just happened.
+	Accumulate,
+}
+
 /// The environment given to the action handler.
 ///
 /// This deliberately does not implement Clone to make it hard to move it out of the handler, which
@@ -121,16 +265,27 @@ pub struct Action {
 	/// The collected events which triggered the action.
 	pub events: Arc<[Event]>,
 	pub(super) outcome: Arc<OnceCell<Outcome>>,
+	paused: watch::Receiver<bool>,
 }
 
 impl Action {
-	pub(super) fn new(events: Arc<[Event]>) -> Self {
+	pub(super) fn new(events: Arc<[Event]>, paused: watch::Receiver<bool>) -> Self {
 		Self {
 			events,
 			outcome: Default::default(),
+			paused,
 		}
 	}
 
+	/// Whether reacting to changes is [paused](crate::Watchexec::pause).
+	///
+	/// Use this to decide between [`Outcome::Pause`] and [`Outcome::Resume`], for example to
+	/// toggle pausing with a key press.
+	#[must_use]
+	pub fn is_paused(&self) -> bool {
+		*self.paused.borrow()
+	}
+
 	/// Set the action's outcome.
 	///
 	/// This takes `self` and `Action` is not `Clone`, so it's only possible to call it once.
@@ -156,6 +311,9 @@ pub struct PreSpawn {
 	/// The command which is about to be spawned.
 	pub command: Command,
 
+	/// The job the command is part of.
+	pub job: JobId,
+
 	/// The collected events which triggered the action this command issues from.
 	pub events: Arc<[Event]>,
 
@@ -165,6 +323,7 @@ pub struct PreSpawn {
 impl PreSpawn {
 	pub(crate) fn new(
 		command: Command,
+		job: JobId,
 		to_spawn: TokioCommand,
 		events: Arc<[Event]>,
 	) -> (Self, Arc<Mutex<TokioCommand>>) {
@@ -172,6 +331,7 @@ impl PreSpawn {
 		(
 			Self {
 				command,
+				job,
 				events,
 				to_spawn_w: Arc::downgrade(&arc),
 			},
@@ -206,6 +366,9 @@ pub struct PostSpawn {
 	/// The command the process was spawned with.
 	pub command: Command,
 
+	/// The job the command is part of.
+	pub job: JobId,
+
 	/// The collected events which triggered the action the command issues from.
 	pub events: Arc<[Event]>,
 
@@ -214,4 +377,9 @@ pub struct PostSpawn {
 
 	/// Whether the command was run in a process group.
 	pub grouped: bool,
+
+	/// The buffer of the last lines of output, if output capture is enabled.
+	///
+	/// See [`WorkingData::capture`].
+	pub output: Option<OutputBuffer>,
 }
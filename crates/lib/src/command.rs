@@ -7,14 +7,35 @@ use tracing::trace;
 
 use crate::error::RuntimeError;
 
+#[doc(inline)]
+pub use output::OutputBuffer;
+
 #[doc(inline)]
 pub use process::Process;
 
 #[doc(inline)]
-pub use supervisor::Supervisor;
+pub use readiness::Readiness;
+pub(crate) use readiness::READY_TIMEOUT;
+
+#[doc(inline)]
+pub use restart::{RestartOn, RestartPolicy};
+
+#[doc(inline)]
+pub use step::{Continuation, Step};
+
+#[doc(inline)]
+pub use supervisor::{Supervisor, SupervisorOptions};
 
+mod output;
+mod placeholders;
 mod process;
+#[cfg(unix)]
+mod pty;
+mod readiness;
+mod restart;
+mod step;
 mod supervisor;
+mod usage;
 
 #[cfg(test)]
 mod tests;
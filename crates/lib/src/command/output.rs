@@ -0,0 +1,183 @@
+use std::{
+	collections::VecDeque,
+	num::NonZeroUsize,
+	sync::{Arc, Mutex},
+};
+
+use async_priority_channel as priority;
+use tokio::{
+	io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+	spawn,
+	task::JoinHandle,
+};
+use tracing::{debug, trace};
+
+use crate::{
+	action::JobId,
+	event::{Event, OutputStream, Priority, Source, Tag},
+};
+
+use super::readiness::OutputProbe;
+
+/// The last lines of output of a process, when output capture is enabled.
+///
+/// This is a handle to a ring buffer which is filled as the process prints: it only ever holds
+/// the last few lines, as configured with [`WorkingData::capture`][crate::action::WorkingData].
+/// It's cheap to clone, and can be kept around after the process has ended, for example to print
+/// the tail of the output of a failed command.
+#[derive(Clone, Debug)]
+pub struct OutputBuffer {
+	capacity: NonZeroUsize,
+	lines: Arc<Mutex<VecDeque<(OutputStream, String)>>>,
+}
+
+impl OutputBuffer {
+	pub(crate) fn new(capacity: NonZeroUsize) -> Self {
+		Self {
+			capacity,
+			lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity.get()))),
+		}
+	}
+
+	/// The maximum number of lines kept in the buffer.
+	#[must_use]
+	pub const fn capacity(&self) -> NonZeroUsize {
+		self.capacity
+	}
+
+	/// Returns the lines currently in the buffer, oldest first.
+	///
+	/// Lines from both streams are interleaved in the order they were read, which may not be
+	/// exactly the order they were printed in.
+	#[must_use]
+	pub fn lines(&self) -> Vec<(OutputStream, String)> {
+		self.lines
+			.lock()
+			.map(|lines| lines.iter().cloned().collect())
+			.unwrap_or_default()
+	}
+
+	fn push(&self, stream: OutputStream, line: String) {
+		if let Ok(mut lines) = self.lines.lock() {
+			if lines.len() == self.capacity.get() {
+				lines.pop_front();
+			}
+			lines.push_back((stream, line));
+		}
+	}
+}
+
+/// Forwards an output stream of a process, until it closes.
+///
+/// The output is written through as-is to the corresponding stream of the current process. If
+/// there's a buffer, each line is also kept in it and emitted as an event. If there's a readiness
+/// probe, lines are checked against it until one matches.
+pub(crate) fn capture(
+	stream: OutputStream,
+	mut reader: impl AsyncRead + Unpin + Send + 'static,
+	pid: u32,
+	job: JobId,
+	buffer: Option<OutputBuffer>,
+	mut probe: Option<OutputProbe>,
+	events: priority::Sender<Event, Priority>,
+) -> JoinHandle<()> {
+	spawn(async move {
+		let mut tee: Box<dyn AsyncWrite + Unpin + Send> = match stream {
+			OutputStream::Stdout => Box::new(tokio::io::stdout()),
+			OutputStream::Stderr => Box::new(tokio::io::stderr()),
+		};
+
+		let mut chunk = vec![0; 8192];
+		let mut pending = Vec::new();
+		let mut emit = true;
+		loop {
+			let len = match reader.read(&mut chunk).await {
+				Ok(0) => break,
+				Ok(len) => len,
+				Err(err) => {
+					// this is how the end of a pseudo-terminal shows up, so it's not unusual
+					debug!(%err, ?stream, %pid, "while reading process output, stopping");
+					break;
+				}
+			};
+
+			// write through as soon as possible, so partial lines (like prompts and progress bars)
+			// show up without waiting for the end of the line
+			if let Err(err) = tee.write_all(&chunk[..len]).await {
+				trace!(%err, ?stream, "while writing process output through");
+			}
+			tee.flush().await.ok();
+
+			if buffer.is_none() && probe.is_none() {
+				continue;
+			}
+
+			pending.extend_from_slice(&chunk[..len]);
+			while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+				let raw: Vec<u8> = pending.drain(..=end).collect();
+				let line = to_line(&raw);
+				if probe.as_ref().map_or(false, |probe| probe.check(&line)) {
+					probe = None;
+				}
+				if let Some(buffer) = &buffer {
+					emit_line(stream, line, pid, &job, buffer, &events, &mut emit).await;
+				}
+			}
+		}
+
+		if !pending.is_empty() {
+			let line = to_line(&pending);
+			if let Some(probe) = &probe {
+				probe.check(&line);
+			}
+			if let Some(buffer) = &buffer {
+				emit_line(stream, line, pid, &job, buffer, &events, &mut emit).await;
+			}
+		}
+
+		trace!(?stream, %pid, "process output closed");
+	})
+}
+
+fn to_line(raw: &[u8]) -> String {
+	String::from_utf8_lossy(raw)
+		.trim_end_matches(['\n', '\r'])
+		.to_string()
+}
+
+async fn emit_line(
+	stream: OutputStream,
+	line: String,
+	pid: u32,
+	job: &JobId,
+	buffer: &OutputBuffer,
+	events: &priority::Sender<Event, Priority>,
+	emit: &mut bool,
+) {
+	buffer.push(stream, line.clone());
+
+	if !*emit {
+		return;
+	}
+
+	let mut tags = vec![
+		Tag::Source(Source::Internal),
+		Tag::Process(pid),
+		Tag::ProcessOutput { stream, line },
+	];
+	if let Some(name) = job.name() {
+		tags.push(Tag::Job(name.into()));
+	}
+
+	let event = Event {
+		tags,
+		metadata: Default::default(),
+	};
+
+	// keep reading even if events can't be sent anymore, so the process doesn't block
+	trace!(?event, "creating synthetic process output event");
+	if events.send(event, Priority::Low).await.is_err() {
+		debug!("event channel closed, stopping output events");
+		*emit = false;
+	}
+}
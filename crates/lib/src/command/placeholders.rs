@@ -0,0 +1,447 @@
+use std::{
+	collections::BTreeSet,
+	env::current_dir,
+	path::{Path, PathBuf},
+};
+
+use tracing::trace;
+
+use crate::{
+	event::{Event, FileType},
+	paths::common_prefix,
+};
+
+use super::{Command, Shell};
+
+/// All placeholders, which don't overlap.
+const PLACEHOLDERS: [&str; 4] = ["{paths}", "{path}", "{relpaths}", "{common}"];
+
+impl Command {
+	/// Substitutes placeholders in the command with the paths of the given events.
+	///
+	/// The placeholders are:
+	/// - `{paths}`: all the paths, absolute, deduplicated, and sorted;
+	/// - `{path}`: the first of those paths;
+	/// - `{relpaths}`: all the paths, relative to the current directory of the Watchexec process
+	///   (or absolute if they're outside of it);
+	/// - `{common}`: the longest common prefix of the paths (for files, of their parents).
+	///
+	/// For [`Command::Exec`], an argument which is entirely a placeholder expands to one argument
+	/// per value, or to no argument at all if there are no paths. A placeholder within a larger
+	/// argument is replaced by its values separated by spaces, without any quoting.
+	///
+	/// For [`Command::Shell`], each value is quoted as appropriate for the shell, and multiple
+	/// values are separated by spaces. For [`Shell::Cmd`](super::Shell), `%` is escaped so that
+	/// values aren't expanded as variables, and so are `^` and `!` if delayed expansion is turned
+	/// on with `/V:ON` in the shell's arguments.
+	///
+	/// Placeholders are always replaced, wherever they appear. To keep one as-is, double its
+	/// braces: `{{paths}}` becomes a literal `{paths}`.
+	///
+	/// Commands without placeholders are returned unchanged.
+	#[must_use]
+	pub fn substitute(&self, events: &[Event]) -> Self {
+		if !self.has_placeholders() {
+			return self.clone();
+		}
+
+		let values = Values::new(events);
+		trace!(?values, "substituting placeholders");
+
+		match self {
+			Self::Exec { prog, args } => Self::Exec {
+				prog: values.replace(prog, |s| s.to_owned()),
+				args: args
+					.iter()
+					.flat_map(|arg| {
+						if let Some(whole) = values.get(arg) {
+							whole.to_vec()
+						} else {
+							vec![values.replace(arg, |s| s.to_owned())]
+						}
+					})
+					.collect(),
+			},
+			Self::Shell {
+				shell,
+				args,
+				command,
+			} => {
+				let mut substituted = values.replace(command, |s| quote(shell, s, false));
+				// cmd only consumes carets in lines which have a `!` to expand
+				if substituted.contains('!') && delayed_expansion(shell, args) {
+					substituted = values.replace(command, |s| quote(shell, s, true));
+				}
+
+				Self::Shell {
+					shell: shell.clone(),
+					args: args.clone(),
+					command: substituted,
+				}
+			}
+		}
+	}
+
+	fn has_placeholders(&self) -> bool {
+		let has = |s: &str| PLACEHOLDERS.iter().any(|p| s.contains(p));
+		match self {
+			Self::Exec { prog, args } => has(prog) || args.iter().any(|arg| has(arg)),
+			Self::Shell { command, .. } => has(command),
+		}
+	}
+}
+
+/// The values of each placeholder, as strings.
+#[derive(Debug)]
+struct Values {
+	paths: Vec<String>,
+	path: Vec<String>,
+	relpaths: Vec<String>,
+	common: Vec<String>,
+}
+
+impl Values {
+	fn new(events: &[Event]) -> Self {
+		let mut paths = BTreeSet::new();
+		let mut trunks = Vec::new();
+		for (path, file_type) in events.iter().flat_map(Event::paths) {
+			paths.insert(path.to_owned());
+			trunks.push(
+				match file_type {
+					Some(FileType::Dir) => None,
+					_ => path.parent(),
+				}
+				.unwrap_or(path)
+				.to_owned(),
+			);
+		}
+
+		let cwd = current_dir().ok();
+		let relpaths = paths
+			.iter()
+			.map(|path| {
+				cwd.as_deref()
+					.and_then(|cwd| path.strip_prefix(cwd).ok())
+					.filter(|rel| !rel.as_os_str().is_empty())
+					.map_or_else(|| path.clone(), Path::to_owned)
+			})
+			.collect::<Vec<_>>();
+
+		Self {
+			paths: strings(&paths),
+			path: strings(paths.iter().take(1)),
+			relpaths: strings(&relpaths),
+			common: strings(&common_prefix(trunks)),
+		}
+	}
+
+	/// The values of a placeholder, if the string is one.
+	fn get(&self, placeholder: &str) -> Option<&[String]> {
+		match placeholder {
+			"{paths}" => Some(&self.paths),
+			"{path}" => Some(&self.path),
+			"{relpaths}" => Some(&self.relpaths),
+			"{common}" => Some(&self.common),
+			_ => None,
+		}
+	}
+
+	/// Replaces all placeholders within a string, quoting each value with the given function.
+	///
+	/// This is done in a single pass over the string, so placeholders within the values (which can
+	/// come from file names) are never expanded. Placeholders with doubled braces are unescaped.
+	fn replace(&self, s: &str, quote: impl Fn(&str) -> String) -> String {
+		let mut out = String::with_capacity(s.len());
+		let mut rest = s;
+		while let Some(start) = rest.find('{') {
+			out.push_str(&rest[..start]);
+			rest = &rest[start..];
+
+			let escaped = PLACEHOLDERS.iter().find(|placeholder| {
+				rest.strip_prefix('{')
+					.and_then(|inner| inner.strip_prefix(**placeholder))
+					.map_or(false, |after| after.starts_with('}'))
+			});
+			if let Some(placeholder) = escaped {
+				out.push_str(placeholder);
+				rest = &rest[placeholder.len() + 2..];
+				continue;
+			}
+
+			let found = PLACEHOLDERS
+				.iter()
+				.find(|placeholder| rest.starts_with(**placeholder));
+			if let Some(placeholder) = found {
+				let values = self.get(placeholder).unwrap_or_default();
+				let joined = values
+					.iter()
+					.map(|v| quote(v))
+					.collect::<Vec<_>>()
+					.join(" ");
+				out.push_str(&joined);
+				rest = &rest[placeholder.len()..];
+			} else {
+				out.push('{');
+				rest = &rest[1..];
+			}
+		}
+		out.push_str(rest);
+		out
+	}
+}
+
+fn strings<'p>(paths: impl IntoIterator<Item = &'p PathBuf>) -> Vec<String> {
+	paths
+		.into_iter()
+		.map(|p| p.to_string_lossy().into_owned())
+		.collect()
+}
+
+/// Quotes a value for a shell, if it needs it.
+///
+/// `delayed` is whether the line is subject to delayed expansion by cmd.
+#[cfg_attr(not(windows), allow(unused_variables))]
+fn quote(shell: &Shell, value: &str, delayed: bool) -> String {
+	let safe = |extra: &str| {
+		!value.is_empty()
+			&& value
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || "-_./:".contains(c) || extra.contains(c))
+	};
+
+	match shell {
+		Shell::Unix(_) if safe("=,+@%^") => value.to_owned(),
+		Shell::Unix(_) => format!("'{}'", value.replace('\'', r"'\''")),
+
+		Shell::Powershell if safe("\\") => value.to_owned(),
+		Shell::Powershell => format!("'{}'", value.replace('\'', "''")),
+
+		#[cfg(windows)]
+		Shell::Cmd if safe("\\") => value.to_owned(),
+		#[cfg(windows)]
+		Shell::Cmd => quote_cmd(value, delayed),
+	}
+}
+
+/// Quotes a value for cmd.
+///
+/// Paths can't contain double quotes on Windows, but cmd expands variables even within quotes.
+/// `%` is escaped as `%%cd:~,%`, where the second part expands to nothing but keeps the `%` from
+/// pairing up with another. With delayed expansion, carets within quotes escape `!` and `^`.
+#[cfg(any(windows, test))]
+fn quote_cmd(value: &str, delayed: bool) -> String {
+	let mut quoted = String::with_capacity(value.len() + 2);
+	quoted.push('"');
+	for c in value.chars() {
+		match c {
+			'%' => quoted.push_str("%%cd:~,%"),
+			'^' | '!' if delayed => {
+				quoted.push('^');
+				quoted.push(c);
+			}
+			_ => quoted.push(c),
+		}
+	}
+	quoted.push('"');
+	quoted
+}
+
+/// Whether the shell is cmd with delayed expansion turned on in its arguments.
+#[cfg(windows)]
+fn delayed_expansion(shell: &Shell, args: &[String]) -> bool {
+	matches!(shell, Shell::Cmd)
+		&& args
+			.iter()
+			.rev()
+			.find_map(|arg| match arg.to_ascii_uppercase().as_str() {
+				"/V:ON" => Some(true),
+				"/V:OFF" => Some(false),
+				_ => None,
+			})
+			.unwrap_or(false)
+}
+
+// because Shell::Cmd is only on windows
+#[cfg(not(windows))]
+fn delayed_expansion(_: &Shell, _: &[String]) -> bool {
+	false
+}
+
+#[cfg(test)]
+mod test {
+	use crate::event::{Source, Tag};
+
+	use super::*;
+
+	fn event(path: &str) -> Event {
+		Event {
+			tags: vec![
+				Tag::Source(Source::Filesystem),
+				Tag::Path {
+					path: PathBuf::from(path),
+					file_type: Some(FileType::File),
+				},
+			],
+			metadata: Default::default(),
+		}
+	}
+
+	#[test]
+	fn exec_expands_whole_arguments() {
+		let cmd = Command::Exec {
+			prog: "rustfmt".into(),
+			args: vec!["--check".into(), "{paths}".into(), "--root={common}".into()],
+		};
+		let events = [event("/src/b.rs"), event("/src/a.rs"), event("/src/b.rs")];
+		assert_eq!(
+			cmd.substitute(&events),
+			Command::Exec {
+				prog: "rustfmt".into(),
+				args: vec![
+					"--check".into(),
+					"/src/a.rs".into(),
+					"/src/b.rs".into(),
+					"--root=/src".into()
+				],
+			}
+		);
+	}
+
+	#[test]
+	fn exec_drops_empty_placeholders() {
+		let cmd = Command::Exec {
+			prog: "pytest".into(),
+			args: vec!["{path}".into()],
+		};
+		assert_eq!(
+			cmd.substitute(&[]),
+			Command::Exec {
+				prog: "pytest".into(),
+				args: Vec::new(),
+			}
+		);
+	}
+
+	#[test]
+	fn shell_quotes_values() {
+		let cmd = Command::Shell {
+			shell: Shell::Unix("sh".into()),
+			args: Vec::new(),
+			command: "eslint {paths} && echo {path}".into(),
+		};
+		let events = [event("/src/it's here.js"), event("/src/plain.js")];
+		assert_eq!(
+			cmd.substitute(&events),
+			Command::Shell {
+				shell: Shell::Unix("sh".into()),
+				args: Vec::new(),
+				command:
+					r"eslint '/src/it'\''s here.js' /src/plain.js && echo '/src/it'\''s here.js'"
+						.into(),
+			}
+		);
+	}
+
+	#[test]
+	fn values_are_not_expanded_again() {
+		let cmd = Command::Shell {
+			shell: Shell::Unix("sh".into()),
+			args: Vec::new(),
+			command: "echo {paths} {common}".into(),
+		};
+		let events = [event("/tmp/x;id/{common}"), event("/tmp/x;id/it's")];
+		assert_eq!(
+			cmd.substitute(&events),
+			Command::Shell {
+				shell: Shell::Unix("sh".into()),
+				args: Vec::new(),
+				command: r"echo '/tmp/x;id/it'\''s' '/tmp/x;id/{common}' '/tmp/x;id'".into(),
+			}
+		);
+
+		let cmd = Command::Exec {
+			prog: "echo".into(),
+			args: vec!["--files={paths}".into()],
+		};
+		assert_eq!(
+			cmd.substitute(&events[..1]),
+			Command::Exec {
+				prog: "echo".into(),
+				args: vec!["--files=/tmp/x;id/{common}".into()],
+			}
+		);
+	}
+
+	#[test]
+	fn doubled_braces_are_literal() {
+		let cmd = Command::Shell {
+			shell: Shell::Unix("sh".into()),
+			args: Vec::new(),
+			command: "fd -x echo {{path}} {path} {{}} {{common}".into(),
+		};
+		assert_eq!(
+			cmd.substitute(&[event("/a b")]),
+			Command::Shell {
+				shell: Shell::Unix("sh".into()),
+				args: Vec::new(),
+				command: "fd -x echo {path} '/a b' {{}} {/".into(),
+			}
+		);
+
+		let cmd = Command::Exec {
+			prog: "fd".into(),
+			args: vec!["{{paths}}".into(), "{paths}".into()],
+		};
+		assert_eq!(
+			cmd.substitute(&[event("/a")]),
+			Command::Exec {
+				prog: "fd".into(),
+				args: vec!["{paths}".into(), "/a".into()],
+			}
+		);
+	}
+
+	#[test]
+	fn cmd_escapes_expansions() {
+		assert_eq!(
+			quote_cmd(r"C:\50% off\%PATH%", false),
+			r#""C:\50%%cd:~,% off\%%cd:~,%PATH%%cd:~,%""#
+		);
+		assert_eq!(quote_cmd(r"C:\a^b!", false), r#""C:\a^b!""#);
+		assert_eq!(quote_cmd(r"C:\a^b!", true), r#""C:\a^^b^!""#);
+	}
+
+	#[cfg(windows)]
+	#[test]
+	fn cmd_escapes_delayed_expansion_only_when_on() {
+		let cmd = |args: &[&str], command: &str| Command::Shell {
+			shell: Shell::Cmd,
+			args: args.iter().map(|arg| (*arg).into()).collect(),
+			command: command.into(),
+		};
+		let events = [event(r"C:\a^b!")];
+
+		assert_eq!(
+			cmd(&[], "echo {path}").substitute(&events),
+			cmd(&[], r#"echo "C:\a^b!""#)
+		);
+		assert_eq!(
+			cmd(&["/V:ON"], "echo {path}").substitute(&events),
+			cmd(&["/V:ON"], r#"echo "C:\a^^b^!""#)
+		);
+		assert_eq!(
+			cmd(&["/V:ON", "/V:OFF"], "echo {path}").substitute(&events),
+			cmd(&["/V:ON", "/V:OFF"], r#"echo "C:\a^b!""#)
+		);
+	}
+
+	#[test]
+	fn without_placeholders_is_unchanged() {
+		let cmd = Command::Shell {
+			shell: Shell::Unix("sh".into()),
+			args: Vec::new(),
+			command: "awk '{print}' {}".into(),
+		};
+		assert_eq!(cmd.substitute(&[event("/a")]), cmd);
+	}
+}
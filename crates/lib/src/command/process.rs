@@ -18,6 +18,13 @@ pub enum Process {
 	/// An ungrouped process that's been spawned.
 	Ungrouped(Child),
 
+	/// A process that's been spawned in its own session, attached to a pseudo-terminal.
+	///
+	/// The process leads its own process group, so like for grouped processes, signals are
+	/// delivered to the whole group.
+	#[cfg(unix)]
+	Terminal(Child),
+
 	/// The cached exit status of the process.
 	Done(ExitStatus),
 }
@@ -47,6 +54,10 @@ impl Process {
 				debug!(signal=%sig, pid=?c.id(), "sending signal to process");
 				c.signal(sig)
 			}
+			Self::Terminal(c) => {
+				debug!(signal=%sig, pgid=?c.id(), "sending signal to terminal process group");
+				signal_group(c, sig)
+			}
 		}
 		.map_err(RuntimeError::Process)
 	}
@@ -68,6 +79,11 @@ impl Process {
 				debug!(pid=?c.id(), "killing process");
 				c.kill().await
 			}
+			#[cfg(unix)]
+			Self::Terminal(c) => {
+				debug!(pgid=?c.id(), "killing terminal process group");
+				signal_group(c, command_group::Signal::SIGKILL)
+			}
 		}
 		.map_err(RuntimeError::Process)
 	}
@@ -94,6 +110,17 @@ impl Process {
 					false
 				}
 			}),
+			#[cfg(unix)]
+			Self::Terminal(c) => c.try_wait().map(|status| {
+				trace!("try-waiting on terminal process");
+				if let Some(status) = status {
+					trace!(?status, "converting to ::Done");
+					*self = Self::Done(status);
+					true
+				} else {
+					false
+				}
+			}),
 			Self::Ungrouped(c) => c.try_wait().map(|status| {
 				trace!("try-waiting on process");
 				if let Some(status) = status {
@@ -142,7 +169,35 @@ impl Process {
 				*self = Self::Done(status);
 				Ok(Some(status))
 			}
+			#[cfg(unix)]
+			Self::Terminal(c) => {
+				trace!("waiting on terminal process");
+				let status = c.wait().await.map_err(|err| RuntimeError::IoError {
+					about: "waiting on process (terminal)",
+					err,
+				})?;
+				trace!(?status, "converting to ::Done");
+				*self = Self::Done(status);
+				Ok(Some(status))
+			}
 		}
 		.map_err(RuntimeError::Process)
 	}
 }
+
+/// Sends a signal to the process group led by the child.
+#[cfg(unix)]
+fn signal_group(child: &Child, sig: command_group::Signal) -> std::io::Result<()> {
+	use nix::{errno::Errno, sys::signal::killpg, unistd::Pid};
+
+	let Some(id) = child.id() else {
+		// already reaped
+		return Ok(());
+	};
+
+	match killpg(Pid::from_raw(id as _), sig) {
+		// the whole group is already gone
+		Ok(()) | Err(Errno::ESRCH) => Ok(()),
+		Err(errno) => Err(errno.into()),
+	}
+}
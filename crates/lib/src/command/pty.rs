@@ -0,0 +1,175 @@
+use std::{
+	fs::File,
+	io::Error,
+	os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+	process::Stdio,
+};
+
+use async_priority_channel as priority;
+use nix::{
+	libc,
+	pty::{openpty, Winsize},
+	unistd::setsid,
+};
+use tokio::{
+	process::Command as TokioCommand,
+	select,
+	signal::unix::{signal, SignalKind},
+	spawn,
+	task::JoinHandle,
+};
+use tracing::{debug, trace};
+
+use crate::{
+	action::JobId,
+	error::RuntimeError,
+	event::{Event, OutputStream, Priority},
+};
+
+use super::{output::capture, readiness::OutputProbe, OutputBuffer};
+
+/// A pseudo-terminal pair, to run a command in.
+#[derive(Debug)]
+pub(crate) struct Pty {
+	master: File,
+	slave: OwnedFd,
+}
+
+impl Pty {
+	/// Opens a new pseudo-terminal, with the size of our own terminal (if we have one).
+	pub(crate) fn open() -> Result<Self, RuntimeError> {
+		let size = window_size(libc::STDOUT_FILENO);
+		trace!(?size, "opening pseudo-terminal");
+		let pair = openpty(size.as_ref(), None).map_err(|errno| RuntimeError::IoError {
+			about: "opening pseudo-terminal",
+			err: errno.into(),
+		})?;
+
+		// SAFETY: the fds were just opened and nothing else owns them
+		let (master, slave) = unsafe {
+			(
+				File::from_raw_fd(pair.master),
+				OwnedFd::from_raw_fd(pair.slave),
+			)
+		};
+
+		// neither side must leak into other processes spawned meanwhile: if one kept the slave open,
+		// the output wouldn't end when the command does. the stdio of the command itself is dup'd
+		// from the slave, which clears the flag on its copies.
+		for (fd, about) in [
+			(
+				master.as_raw_fd(),
+				"setting pseudo-terminal master to close-on-exec",
+			),
+			(
+				slave.as_raw_fd(),
+				"setting pseudo-terminal slave to close-on-exec",
+			),
+		] {
+			// SAFETY: the fd is valid; this only sets a flag on it
+			if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+				return Err(RuntimeError::IoError {
+					about,
+					err: Error::last_os_error(),
+				});
+			}
+		}
+
+		Ok(Self { master, slave })
+	}
+
+	/// Sets up the command to run in a new session, with the terminal as its standard streams and
+	/// controlling terminal.
+	pub(crate) fn attach(&self, spawnable: &mut TokioCommand) -> Result<(), RuntimeError> {
+		let stdio = || {
+			self.slave
+				.try_clone()
+				.map(Stdio::from)
+				.map_err(|err| RuntimeError::IoError {
+					about: "duplicating pseudo-terminal",
+					err,
+				})
+		};
+
+		spawnable.stdin(stdio()?).stdout(stdio()?).stderr(stdio()?);
+
+		// SAFETY: setsid and ioctl are async-signal-safe
+		unsafe {
+			spawnable.pre_exec(|| {
+				setsid()?;
+				if libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY as _, 0) == -1 {
+					return Err(Error::last_os_error());
+				}
+				Ok(())
+			});
+		}
+
+		Ok(())
+	}
+
+	/// Forwards the output of the terminal, and our own window size changes to it.
+	///
+	/// This consumes the pair: our copy of the slave side is closed, so that the output ends when
+	/// the process (and its children) have closed theirs.
+	pub(crate) fn forward(
+		self,
+		pid: u32,
+		job: JobId,
+		buffer: Option<OutputBuffer>,
+		probe: Option<OutputProbe>,
+		events: priority::Sender<Event, Priority>,
+	) -> Result<JoinHandle<()>, RuntimeError> {
+		let Self { master, slave } = self;
+		drop(slave);
+
+		let fd = master.as_raw_fd();
+		let mut winch =
+			signal(SignalKind::window_change()).map_err(|err| RuntimeError::IoError {
+				about: "listening for window size changes",
+				err,
+			})?;
+
+		let mut output = capture(
+			OutputStream::Stdout,
+			tokio::fs::File::from_std(master),
+			pid,
+			job,
+			buffer,
+			probe,
+			events,
+		);
+
+		Ok(spawn(async move {
+			loop {
+				select! {
+					_ = &mut output => break,
+					Some(()) = winch.recv() => {
+						// the master is still open as long as the output task runs
+						if let Some(size) = window_size(libc::STDOUT_FILENO) {
+							debug!(rows=%size.ws_row, cols=%size.ws_col, "resizing pseudo-terminal");
+							// SAFETY: the fd is valid, and the size is a valid winsize
+							unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &size) };
+						}
+					}
+				}
+			}
+		}))
+	}
+}
+
+/// Gets the window size of the terminal at this fd, if it is one.
+fn window_size(fd: RawFd) -> Option<Winsize> {
+	let mut size = Winsize {
+		ws_row: 0,
+		ws_col: 0,
+		ws_xpixel: 0,
+		ws_ypixel: 0,
+	};
+
+	// SAFETY: size is a valid winsize to write into
+	if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } == -1 {
+		None
+	} else {
+		Some(size)
+	}
+}
@@ -0,0 +1,96 @@
+use std::{path::PathBuf, time::Duration};
+
+use regex::Regex;
+use tokio::{
+	fs::metadata, net::TcpStream, spawn, sync::mpsc::Sender, task::JoinHandle, time::sleep,
+};
+use tracing::trace;
+
+/// How often to check for readiness, for the probes which poll.
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait for readiness by default, before giving up on it.
+pub(crate) const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A probe which determines when a command set is ready, after it's started.
+///
+/// Until a set is ready, the action worker holds on to events, such that an action isn't taken
+/// (like restarting the set) while the set is still starting up. Once it's ready, an event with a
+/// [`Tag::ProcessReady`][crate::event::Tag::ProcessReady] is emitted. Sets which end are never
+/// waited on, whether they became ready or not, and sets which don't become ready within the
+/// [timeout](crate::action::WorkingData::ready_timeout) stop being waited on, with a
+/// [`RuntimeError::ReadinessTimeout`](crate::error::RuntimeError::ReadinessTimeout).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Readiness {
+	/// Ready when this TCP port accepts connections on localhost.
+	Port(u16),
+
+	/// Ready when this file exists.
+	///
+	/// Note that a file which exists when the set starts (like one left over from a previous run)
+	/// makes the set ready immediately.
+	File(PathBuf),
+
+	/// Ready when a line of output of a process of the set matches this pattern.
+	///
+	/// The output is read even if [output capture](crate::action::WorkingData::capture) is
+	/// disabled, but is only emitted as events if it's enabled.
+	Output(Regex),
+}
+
+impl Readiness {
+	/// Starts probing for readiness, sending on `ready` when it is.
+	///
+	/// Returns `None` for output probes, which are checked by the output readers instead.
+	pub(crate) fn probe(&self, ready: Sender<()>) -> Option<JoinHandle<()>> {
+		match self.clone() {
+			Self::Port(port) => Some(spawn(async move {
+				while let Err(err) = TcpStream::connect(("localhost", port)).await {
+					trace!(%port, %err, "port not ready yet");
+					sleep(PROBE_INTERVAL).await;
+				}
+				ready.send(()).await.ok();
+			})),
+			Self::File(path) => Some(spawn(async move {
+				while metadata(&path).await.is_err() {
+					trace!(?path, "file not ready yet");
+					sleep(PROBE_INTERVAL).await;
+				}
+				ready.send(()).await.ok();
+			})),
+			Self::Output(_) => None,
+		}
+	}
+
+	/// The output probe for this readiness, if it's an output pattern.
+	pub(crate) fn output_probe(&self, ready: Sender<()>) -> Option<OutputProbe> {
+		match self {
+			Self::Output(pattern) => Some(OutputProbe {
+				pattern: pattern.clone(),
+				ready,
+			}),
+			_ => None,
+		}
+	}
+}
+
+/// Checks lines of output for a readiness pattern.
+#[derive(Clone, Debug)]
+pub(crate) struct OutputProbe {
+	pattern: Regex,
+	ready: Sender<()>,
+}
+
+impl OutputProbe {
+	/// Checks a line, and returns true if it matched.
+	pub(crate) fn check(&self, line: &str) -> bool {
+		if self.pattern.is_match(line) {
+			trace!(%line, "output matched readiness pattern");
+			self.ready.try_send(()).ok();
+			true
+		} else {
+			false
+		}
+	}
+}
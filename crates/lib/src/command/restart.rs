@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use tracing::{debug, info};
+
+/// Which ends of a command set cause it to be restarted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RestartOn {
+	/// Restart when the set ends unsuccessfully: with a non-zero exit, a signal, etc.
+	#[default]
+	Failure,
+
+	/// Restart whenever the set ends, even successfully.
+	Exit,
+}
+
+/// A policy for automatically restarting command sets which end on their own.
+///
+/// When a set ends (without having been stopped by Watchexec) in a way which matches the policy,
+/// it is run again after a delay. The delay starts at the [initial
+/// delay](RestartPolicy::initial_delay) and doubles with each consecutive restart, up to the
+/// [maximum delay](RestartPolicy::max_delay). Once a run has stayed up for the [reset
+/// period](RestartPolicy::reset_after), it counts as healthy, and the delay goes back to the
+/// initial delay for the next restart.
+///
+/// The restart is driven off the end of the step which completes the set, as reported by the
+/// [`Tag::ProcessCompletion`][crate::event::Tag::ProcessCompletion] of the event which also
+/// carries the [`Tag::SetCompletion`][crate::event::Tag::SetCompletion].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct RestartPolicy {
+	/// Which ends cause a restart.
+	pub on: RestartOn,
+
+	/// How long to wait before the first restart.
+	pub initial_delay: Duration,
+
+	/// The longest to wait before a restart.
+	pub max_delay: Duration,
+
+	/// How many consecutive restarts to do before giving up, if any limit.
+	pub max_retries: Option<u32>,
+
+	/// How long a run needs to stay up to reset the delay and retries.
+	pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+	fn default() -> Self {
+		Self::new(RestartOn::default())
+	}
+}
+
+impl RestartPolicy {
+	/// A policy restarting on the given ends, with defaults for everything else.
+	///
+	/// By default, the delay starts at 1 second and goes up to 1 minute, there's no limit to the
+	/// number of restarts, and a run is healthy after 1 minute.
+	#[must_use]
+	pub const fn new(on: RestartOn) -> Self {
+		Self {
+			on,
+			initial_delay: Duration::from_secs(1),
+			max_delay: Duration::from_secs(60),
+			max_retries: None,
+			reset_after: Duration::from_secs(60),
+		}
+	}
+
+	/// Sets the initial and maximum delays.
+	#[must_use]
+	pub const fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+		self.initial_delay = initial;
+		self.max_delay = max;
+		self
+	}
+
+	/// Sets how many consecutive restarts to do before giving up.
+	#[must_use]
+	pub const fn max_retries(mut self, retries: u32) -> Self {
+		self.max_retries = Some(retries);
+		self
+	}
+
+	/// Sets how long a run needs to stay up to reset the delay and retries.
+	#[must_use]
+	pub const fn reset_after(mut self, period: Duration) -> Self {
+		self.reset_after = period;
+		self
+	}
+
+	/// The delay before the restart which comes after `attempts` consecutive restarts.
+	fn delay(&self, attempts: u32) -> Duration {
+		self.initial_delay
+			.checked_mul(2_u32.saturating_pow(attempts))
+			.unwrap_or(Duration::MAX)
+			.min(self.max_delay)
+	}
+}
+
+/// Tracks the restarts of a command set according to a policy.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Restarts {
+	policy: RestartPolicy,
+	attempts: u32,
+}
+
+impl Restarts {
+	pub(crate) const fn new(policy: RestartPolicy) -> Self {
+		Self {
+			policy,
+			attempts: 0,
+		}
+	}
+
+	/// Given how the set ended and how long it was up for, returns how long to wait before
+	/// restarting it, or `None` if it shouldn't be.
+	pub(crate) fn next(&mut self, success: bool, uptime: Duration) -> Option<Duration> {
+		if success && self.policy.on == RestartOn::Failure {
+			debug!("set ended successfully, not restarting");
+			return None;
+		}
+
+		if uptime >= self.policy.reset_after {
+			debug!(?uptime, "set was up long enough, resetting restarts");
+			self.attempts = 0;
+		}
+
+		if let Some(max) = self.policy.max_retries {
+			if self.attempts >= max {
+				info!(attempts=%self.attempts, "set keeps ending, giving up on restarting it");
+				return None;
+			}
+		}
+
+		let delay = self.policy.delay(self.attempts);
+		self.attempts += 1;
+		info!(?delay, attempt=%self.attempts, "restarting set after delay");
+		Some(delay)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn backoff_doubles_up_to_max() {
+		let mut restarts = Restarts::new(
+			RestartPolicy::new(RestartOn::Failure)
+				.backoff(Duration::from_secs(1), Duration::from_secs(5)),
+		);
+		let delays: Vec<_> = (0..5)
+			.map(|_| restarts.next(false, Duration::ZERO))
+			.collect();
+		assert_eq!(
+			delays,
+			[1, 2, 4, 5, 5]
+				.map(|s| Some(Duration::from_secs(s)))
+				.to_vec()
+		);
+	}
+
+	#[test]
+	fn success_only_restarts_on_exit() {
+		let mut on_failure = Restarts::new(RestartPolicy::new(RestartOn::Failure));
+		assert_eq!(on_failure.next(true, Duration::ZERO), None);
+
+		let mut on_exit = Restarts::new(RestartPolicy::new(RestartOn::Exit));
+		assert_eq!(
+			on_exit.next(true, Duration::ZERO),
+			Some(Duration::from_secs(1))
+		);
+	}
+
+	#[test]
+	fn gives_up_after_max_retries() {
+		let mut restarts = Restarts::new(RestartPolicy::new(RestartOn::Failure).max_retries(2));
+		assert!(restarts.next(false, Duration::ZERO).is_some());
+		assert!(restarts.next(false, Duration::ZERO).is_some());
+		assert_eq!(restarts.next(false, Duration::ZERO), None);
+	}
+
+	#[test]
+	fn resets_after_healthy_run() {
+		let mut restarts = Restarts::new(
+			RestartPolicy::new(RestartOn::Failure)
+				.max_retries(1)
+				.reset_after(Duration::from_secs(10)),
+		);
+		assert_eq!(
+			restarts.next(false, Duration::ZERO),
+			Some(Duration::from_secs(1))
+		);
+		assert_eq!(restarts.next(false, Duration::from_secs(1)), None);
+		assert_eq!(
+			restarts.next(false, Duration::from_secs(10)),
+			Some(Duration::from_secs(1))
+		);
+	}
+}
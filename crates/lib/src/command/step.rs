@@ -0,0 +1,134 @@
+use super::Command;
+
+/// When a step in a command set should run, based on how the previous steps ended.
+///
+/// This is evaluated against the status of the step(s) it runs [after](Step::after), in the same
+/// way as a shell evaluates `&&` and `||` lists: a step which is skipped passes on the status of
+/// its own dependencies. Steps without dependencies (such as the first step in a set) always run.
+///
+/// When a step has several dependencies, it is considered to follow a success only if all of them
+/// succeeded, and a failure if any of them failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Continuation {
+	/// Run regardless of how the previous step ended.
+	#[default]
+	Always,
+
+	/// Run only if the previous step succeeded, like `a && b`.
+	OnSuccess,
+
+	/// Run only if the previous step failed, like `a || b`.
+	OnFailure,
+}
+
+impl Continuation {
+	pub(crate) const fn permits(self, previous_success: bool) -> bool {
+		match self {
+			Self::Always => true,
+			Self::OnSuccess => previous_success,
+			Self::OnFailure => !previous_success,
+		}
+	}
+}
+
+/// A command in a command set, along with when it should run.
+///
+/// A [`Command`] converts into a step which always runs, so a plain `Vec<Command>` can be used
+/// anywhere a set of steps is expected.
+///
+/// By default, a step runs after the step just before it in the set, so sets run sequentially.
+/// Steps can instead depend on any number of earlier steps with [`after()`](Step::after), which
+/// makes the set a graph: steps without dependencies start immediately, and steps whose
+/// dependencies have all ended run concurrently.
+///
+/// For example, to build and then run a program only if the build succeeded, and to print a
+/// message otherwise (like `build && run || echo failed`):
+///
+/// ```
+/// # use watchexec::command::{Command, Step};
+/// # let cmd = |prog: &str| Command::Exec { prog: prog.into(), args: Vec::new() };
+/// let steps = vec![
+///     Step::from(cmd("build")),
+///     Step::on_success(cmd("run")),
+///     Step::on_failure(cmd("echo-failed")),
+/// ];
+/// ```
+///
+/// Or to lint, typecheck, and test in parallel, and then deploy if all of these succeeded:
+///
+/// ```
+/// # use watchexec::command::{Command, Step};
+/// # let cmd = |prog: &str| Command::Exec { prog: prog.into(), args: Vec::new() };
+/// let steps = vec![
+///     Step::from(cmd("lint")),
+///     Step::from(cmd("typecheck")).after([]),
+///     Step::from(cmd("test")).after([]),
+///     Step::on_success(cmd("deploy")).after([0, 1, 2]),
+/// ];
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Step {
+	/// The command to run.
+	pub command: Command,
+
+	/// When to run the command.
+	pub when: Continuation,
+
+	/// The indices of the steps this one runs after.
+	///
+	/// If `None`, the step runs after the one just before it in the set (if any). Dependencies
+	/// must come before the step in the set.
+	pub after: Option<Vec<usize>>,
+}
+
+impl Step {
+	/// A step which runs the command when the step(s) it runs after ended in a particular way.
+	#[must_use]
+	pub const fn new(command: Command, when: Continuation) -> Self {
+		Self {
+			command,
+			when,
+			after: None,
+		}
+	}
+
+	/// A step which always runs the command.
+	#[must_use]
+	pub const fn always(command: Command) -> Self {
+		Self::new(command, Continuation::Always)
+	}
+
+	/// A step which runs the command only if the step(s) it runs after succeeded.
+	#[must_use]
+	pub const fn on_success(command: Command) -> Self {
+		Self::new(command, Continuation::OnSuccess)
+	}
+
+	/// A step which runs the command only if the step(s) it runs after failed.
+	#[must_use]
+	pub const fn on_failure(command: Command) -> Self {
+		Self::new(command, Continuation::OnFailure)
+	}
+
+	/// Sets the steps this one runs after, by their index in the set.
+	///
+	/// With no dependencies, the step starts as soon as the set does.
+	#[must_use]
+	pub fn after(mut self, steps: impl IntoIterator<Item = usize>) -> Self {
+		self.after = Some(steps.into_iter().collect());
+		self
+	}
+
+	pub(crate) fn dependencies(&self, index: usize) -> Vec<usize> {
+		self.after
+			.clone()
+			.unwrap_or_else(|| index.checked_sub(1).into_iter().collect())
+	}
+}
+
+impl From<Command> for Step {
+	fn from(command: Command) -> Self {
+		Self::always(command)
+	}
+}
@@ -1,177 +1,483 @@
-use std::sync::Arc;
+use std::{
+	collections::{BTreeSet, HashMap, VecDeque},
+	num::NonZeroUsize,
+	path::Path,
+	process::ExitStatus,
+	sync::Arc,
+	time::Duration,
+};
 
 use async_priority_channel as priority;
 use command_group::AsyncCommandGroup;
 use tokio::{
 	select, spawn,
 	sync::{
-		mpsc::{self, Sender},
+		mpsc::{self, Receiver, Sender},
 		watch,
 	},
+	task::JoinHandle,
+	time::{sleep_until, timeout, Instant},
 };
-use tracing::{debug, debug_span, error, info, trace, Span};
+use tracing::{debug, debug_span, error, info, trace, warn, Span};
 use watchexec_signals::Signal;
 
 use crate::{
-	action::{PostSpawn, PreSpawn},
-	command::Command,
+	action::{JobId, PostSpawn, PreSpawn},
+	command::{Command, Step},
 	error::RuntimeError,
-	event::{Event, Priority, Source, Tag},
+	event::{Event, OutputStream, Priority, ProcessEnd, ProcessUsage, Source, Tag},
 	handler::{rte, HandlerLock},
 };
 
-use super::Process;
+#[cfg(unix)]
+use super::pty::Pty;
+use super::{
+	output::capture as capture_output,
+	readiness::{OutputProbe, READY_TIMEOUT},
+	restart::Restarts,
+	usage::ExitWatch,
+	OutputBuffer, Process, Readiness, RestartPolicy,
+};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 enum Intervention {
 	Kill,
 	Signal(Signal),
+	Timeout {
+		after: Duration,
+		escalation: Vec<(Signal, Duration)>,
+	},
 }
 
-/// A task which supervises a sequence of processes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StepState {
+	Pending,
+	Running,
+	/// The step ended or was skipped, and counts as a success (or not) for the steps after it.
+	Settled(bool),
+}
+
+/// How long to wait for the output of a process to close after it ends.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+type StepEnd = (
+	usize,
+	Result<(Option<ExitStatus>, ProcessUsage), RuntimeError>,
+);
+
+/// How a [`Supervisor`] runs its command set.
 ///
-/// This spawns processes from a vec of [`Command`]s in order and waits for each to complete while
-/// handling interventions to itself: orders to terminate, or to send a signal to the current
-/// process. It also immediately issues a [`Tag::ProcessCompletion`] event when the set completes.
+/// The defaults are those of [`WorkingData`](crate::action::WorkingData): the default job, run in
+/// a process group, without output capture, pseudo-terminal, restarts, readiness probe, or
+/// fan-out. Set fields with struct update syntax:
+///
+/// ```
+/// # use std::num::NonZeroUsize;
+/// # use watchexec::{action::JobId, command::SupervisorOptions};
+/// let options = SupervisorOptions {
+///     job: JobId::from("server"),
+///     capture: NonZeroUsize::new(20),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct SupervisorOptions {
+	/// The job whose commands are run.
+	///
+	/// For named jobs, the completion events are tagged with [`Tag::Job`].
+	pub job: JobId,
+
+	/// Whether to run the commands in process groups.
+	///
+	/// See [`WorkingData::grouped`](crate::action::WorkingData::grouped).
+	pub grouped: bool,
+
+	/// Whether to capture the output of commands, and how many lines of it to keep per run.
+	///
+	/// See [`WorkingData::capture`](crate::action::WorkingData::capture).
+	pub capture: Option<NonZeroUsize>,
+
+	/// Whether to run the commands in a pseudo-terminal (Unix only).
+	///
+	/// See [`WorkingData::pty`](crate::action::WorkingData::pty).
+	pub pty: bool,
+
+	/// Whether and how to restart the set when it ends on its own.
+	///
+	/// See [`WorkingData::restart`](crate::action::WorkingData::restart).
+	pub restart: Option<RestartPolicy>,
+
+	/// How to tell that the set is ready.
+	///
+	/// See [`WorkingData::readiness`](crate::action::WorkingData::readiness).
+	pub readiness: Option<Readiness>,
+
+	/// How long to wait for the set to be ready, if at all.
+	///
+	/// See [`WorkingData::ready_timeout`](crate::action::WorkingData::ready_timeout).
+	pub ready_timeout: Option<Duration>,
+
+	/// Whether to run the set once per changed path, and how many runs to have going at once.
+	///
+	/// See [`WorkingData::fan_out`](crate::action::WorkingData::fan_out).
+	pub fan_out: Option<NonZeroUsize>,
+}
+
+impl Default for SupervisorOptions {
+	fn default() -> Self {
+		Self {
+			job: JobId::default(),
+			grouped: true,
+			capture: None,
+			pty: false,
+			restart: None,
+			readiness: None,
+			ready_timeout: Some(READY_TIMEOUT),
+			fan_out: None,
+		}
+	}
+}
+
+/// A task which supervises a set of processes.
+///
+/// This spawns processes from a vec of [`Step`]s and waits for each to complete while handling
+/// interventions to itself: orders to terminate, or to send a signal to the running processes.
+/// It also immediately issues a [`Tag::ProcessCompletion`] event when each process completes,
+/// along with a [`Tag::ProcessUsage`] with its timing and resource usage.
+///
+/// Steps run once the steps they run [after](Step::after) have ended, which by default is the step
+/// just before them, such that the set runs in order. Steps whose dependencies have all ended run
+/// concurrently. Steps are skipped if their [`Continuation`](super::Continuation) doesn't match
+/// how their dependencies ended.
+///
+/// The completion event of the step which ends the set additionally carries a
+/// [`Tag::SetCompletion`] with the index of that step. The set counts as successful only if all
+/// of its steps were. If the set is stopped with
+/// [`kill()`](Supervisor::kill), no further steps are run.
+///
+/// The set can be given a time limit with [`timeout()`](Supervisor::timeout). When it runs out,
+/// the set is stopped, and the completion events of the processes which were still running are
+/// tagged with [`Tag::Timeout`].
+///
+/// Processes can be run in a pseudo-terminal (on Unix only), in which case their output is
+/// forwarded to Watchexec's standard output, and changes to the size of Watchexec's terminal
+/// are propagated to the pseudo-terminal.
+///
+/// If output capture is enabled, the output of each process is also issued as
+/// [`Tag::ProcessOutput`] events, which are sent before its completion event.
+///
+/// With a [`RestartPolicy`], sets which end on their own are run again after a delay. While
+/// waiting to restart, the supervisor is not [running](Supervisor::is_running), and the restart
+/// is cancelled by [`kill()`](Supervisor::kill) or by dropping the supervisor. If the set has a
+/// [time limit](Supervisor::timeout), it applies anew to each run.
+///
+/// With a [`Readiness`] probe, the set is only [ready](Supervisor::is_ready) once the probe
+/// succeeds, at which point a [`Tag::ProcessReady`] event is issued. Each run is probed anew.
+///
+/// In fan-out mode, the whole set is run once per path of the actioned events, with only the
+/// events of that path, and with up to the given number of runs going at once. The completion
+/// event of the step which ends the last run carries the [`Tag::SetCompletion`], and the set
+/// counts as successful only if every run was.
+///
+/// Each supervisor runs the commands of one job. For named jobs, the completion events are also
+/// tagged with [`Tag::Job`].
 #[derive(Debug)]
 pub struct Supervisor {
 	intervene: Sender<Intervention>,
 	ongoing: watch::Receiver<bool>,
+	ready: watch::Receiver<bool>,
 }
 
 impl Supervisor {
 	/// Spawns the command set, the supervision task, and returns a new control object.
+	///
+	/// Returns an error without spawning anything if the set is empty, or if a step depends on a
+	/// step which doesn't come before it.
 	pub fn spawn(
 		errors: Sender<RuntimeError>,
 		events: priority::Sender<Event, Priority>,
-		mut commands: Vec<Command>,
-		grouped: bool,
+		steps: Vec<Step>,
+		options: SupervisorOptions,
 		actioned_events: Arc<[Event]>,
 		pre_spawn_handler: HandlerLock<PreSpawn>,
 		post_spawn_handler: HandlerLock<PostSpawn>,
 	) -> Result<Self, RuntimeError> {
-		// get commands in reverse order so pop() returns the next to run
-		commands.reverse();
-		let next = commands.pop().ok_or(RuntimeError::NoCommands)?;
+		if steps.is_empty() {
+			return Err(RuntimeError::NoCommands);
+		}
+
+		let SupervisorOptions {
+			job,
+			grouped,
+			capture,
+			pty,
+			restart,
+			readiness,
+			ready_timeout,
+			fan_out,
+		} = options;
+
+		let dependencies: Vec<Vec<usize>> = steps
+			.iter()
+			.enumerate()
+			.map(|(index, step)| step.dependencies(index))
+			.collect();
+		for (step, deps) in dependencies.iter().enumerate() {
+			if let Some(&dependency) = deps.iter().find(|&&dep| dep >= step) {
+				return Err(RuntimeError::InvalidStepDependency { step, dependency });
+			}
+		}
 
 		let (notify, waiter) = watch::channel(true);
+		let (ready_notify, ready_waiter) = watch::channel(readiness.is_none());
 		let (int_s, int_r) = mpsc::channel(8);
 
 		spawn(async move {
-			let span = debug_span!("supervisor");
+			let span = debug_span!("supervisor", %job);
 
-			let mut next = next;
-			let mut commands = commands;
 			let mut int = int_r;
+			let mut runs = Runs::new(actioned_events, fan_out);
+			let width = steps.len();
+			let (ended_s, mut ended_r) = mpsc::channel::<StepEnd>(width * runs.events.len());
+			let mut states = vec![StepState::Pending; width * runs.events.len()];
+			let mut live: HashMap<usize, Sender<Intervention>> = HashMap::new();
+			let mut stopped = false;
+			let mut deadline: Option<Instant> = None;
+			let mut escalation: VecDeque<(Signal, Duration)> = VecDeque::new();
+			let mut timed_out = false;
+			let mut limit: Option<(Duration, Vec<(Signal, Duration)>)> = None;
+			let mut restarts = restart.map(Restarts::new);
+			let mut started = Instant::now();
+			let mut set_success: Option<bool> = None;
+			let (probed_s, mut probed_r) = mpsc::channel::<()>(1);
+			let output_probe = readiness
+				.as_ref()
+				.and_then(|r| r.output_probe(probed_s.clone()));
+			let mut probe = readiness.as_ref().and_then(|r| r.probe(probed_s.clone()));
+			let mut is_ready = readiness.is_none();
+			let mut ready_deadline = ready_timeout.map(|timeout| started + timeout);
 
+			let mut ready = span.in_scope(|| runs.start(&steps, &dependencies, &mut states));
 			loop {
-				let (mut process, pid) = match spawn_process(
-					span.clone(),
-					next,
-					grouped,
-					actioned_events.clone(),
-					pre_spawn_handler.clone(),
-					post_spawn_handler.clone(),
-				)
-				.await
-				{
-					Ok(pp) => pp,
-					Err(err) => {
-						let _enter = span.enter();
-						error!(%err, "while spawning process");
-						errors.send(err).await.ok();
-						trace!("marking process as done");
-						notify
-							.send(false)
-							.unwrap_or_else(|e| trace!(%e, "error sending process complete"));
-						trace!("closing supervisor task early");
-						return;
+				for index in ready.drain(..) {
+					if stopped {
+						break;
+					}
+
+					let (run, step) = (index / width, index % width);
+					match spawn_process(
+						span.clone(),
+						steps[step].command.clone(),
+						job.clone(),
+						grouped,
+						capture,
+						pty,
+						output_probe.clone(),
+						events.clone(),
+						runs.events[run].clone(),
+						pre_spawn_handler.clone(),
+						post_spawn_handler.clone(),
+					)
+					.await
+					{
+						Ok((process, pid, exit, readers)) => {
+							span.in_scope(
+								|| debug!(?process, ?pid, %run, %step, "spawned process"),
+							);
+							let (tend_s, tend_r) = mpsc::channel(8);
+							live.insert(index, tend_s);
+							spawn(tend(
+								span.clone(),
+								index,
+								process,
+								exit,
+								readers,
+								tend_r,
+								errors.clone(),
+								ended_s.clone(),
+							));
+						}
+						Err(err) => {
+							let _enter = span.enter();
+							error!(%err, step=%index, "while spawning process");
+							errors.send(err).await.ok();
+							trace!("stopping the set");
+							stopped = true;
+							for tend in live.values() {
+								tend.send(Intervention::Kill).await.ok();
+							}
+						}
 					}
-				};
+				}
 
-				span.in_scope(|| debug!(?process, ?pid, "spawned process"));
+				if live.is_empty() {
+					if let Some(probe) = probe.take() {
+						probe.abort();
+					}
+					ready_notify.send(true).ok();
 
-				loop {
-					select! {
-						p = process.wait() => {
-							match p {
-								Ok(_) => break, // deal with it below
-								Err(err) => {
-									let _enter = span.enter();
-									error!(%err, "while waiting on process");
-									errors.try_send(err).ok();
-									trace!("marking process as done");
-									notify.send(false).unwrap_or_else(|e| trace!(%e, "error sending process complete"));
-									trace!("closing supervisor task early");
-									return;
+					let delay = match (&mut restarts, set_success.take()) {
+						(Some(restarts), Some(success)) if !stopped => {
+							span.in_scope(|| restarts.next(success, started.elapsed()))
+						}
+						_ => None,
+					};
+					let Some(delay) = delay else {
+						span.in_scope(|| debug!("no more steps to supervise"));
+						break;
+					};
+
+					notify
+						.send(false)
+						.unwrap_or_else(|e| trace!(%e, "error sending process complete"));
+					let until = Instant::now() + delay;
+					let restart = loop {
+						select! {
+							_ = sleep_until(until) => break true,
+							int = int.recv() => match int {
+								None | Some(Intervention::Kill) => break false,
+								Some(Intervention::Timeout { after, escalation }) => {
+									limit = Some((after, escalation));
+								}
+								Some(Intervention::Signal(signal)) => {
+									span.in_scope(|| debug!(?signal, "waiting to restart, no process to signal"));
+								}
+							},
+						}
+					};
+					if !restart {
+						span.in_scope(|| debug!("restart cancelled"));
+						break;
+					}
+
+					span.in_scope(|| debug!("restarting the set"));
+					notify
+						.send(true)
+						.unwrap_or_else(|e| trace!(%e, "error sending process restart"));
+					states.fill(StepState::Pending);
+					runs.reset();
+					is_ready = readiness.is_none();
+					ready_notify.send(is_ready).ok();
+					while probed_r.try_recv().is_ok() {}
+					probe = readiness.as_ref().and_then(|r| r.probe(probed_s.clone()));
+					started = Instant::now();
+					ready_deadline = ready_timeout.map(|timeout| started + timeout);
+					timed_out = false;
+					(deadline, escalation) = match &limit {
+						Some((after, ladder)) => (Some(started + *after), ladder.clone().into()),
+						None => (None, VecDeque::new()),
+					};
+					ready = span.in_scope(|| runs.start(&steps, &dependencies, &mut states));
+					continue;
+				}
+
+				select! {
+					Some((index, status)) = ended_r.recv() => {
+						live.remove(&index);
+						match status {
+							Err(err) => {
+								let _enter = span.enter();
+								error!(%err, step=%index, "while waiting on process");
+								errors.try_send(err).ok();
+								trace!("stopping the set");
+								states[index] = StepState::Settled(false);
+								stopped = true;
+								for tend in live.values() {
+									tend.send(Intervention::Kill).await.ok();
 								}
 							}
-						},
-						Some(int) = int.recv() => {
-							match int {
-								Intervention::Kill => {
-									if let Err(err) = process.kill().await {
-									let _enter = span.enter();
-										error!(%err, "while killing process");
-										errors.try_send(err).ok();
-										trace!("continuing to watch command");
-									}
+							Ok((status, usage)) => {
+								let end: Option<ProcessEnd> = status.map(Into::into);
+								let success = matches!(end, Some(ProcessEnd::Success));
+								states[index] = StepState::Settled(success);
+								if stopped {
+									span.in_scope(|| debug!("set was stopped, not running further steps"));
+								} else {
+									ready = span.in_scope(|| runs.next(index, &steps, &dependencies, &mut states));
 								}
-								#[cfg(unix)]
-								Intervention::Signal(sig) => {
-									let _enter = span.enter();
-									if let Some(sig) = sig.to_nix() {
-										if let Err(err) = process.signal(sig) {
-											error!(%err, "while sending signal to process");
-											errors.try_send(err).ok();
-											trace!("continuing to watch command");
-										}
-									} else {
-										let err = RuntimeError::UnsupportedSignal(sig);
-										error!(%err, "while sending signal to process");
-										errors.try_send(err).ok();
-										trace!("continuing to watch command");
+
+								let event = span.in_scope(|| {
+									let mut tags = vec![
+										Tag::Source(Source::Internal),
+										Tag::ProcessCompletion(end),
+										Tag::ProcessUsage(usage),
+									];
+									if timed_out {
+										tags.push(Tag::Timeout);
 									}
-								}
-								#[cfg(windows)]
-								Intervention::Signal(sig) => {
+									if live.is_empty() && ready.is_empty() {
+										tags.push(Tag::SetCompletion { step: index % width });
+										set_success = Some(runs.success);
+									}
+									if let Some(name) = job.name() {
+										tags.push(Tag::Job(name.into()));
+									}
+
+									let event = Event {
+										tags,
+										metadata: Default::default(),
+									};
+
+									debug!(?event, "creating synthetic process completion event");
+									event
+								});
+
+								if let Err(err) = events.send(event, Priority::Low).await {
 									let _enter = span.enter();
-									// https://github.com/watchexec/watchexec/issues/219
-									let err = RuntimeError::UnsupportedSignal(sig);
-									error!(%err, "while sending signal to process");
-									errors.try_send(err).ok();
-									trace!("continuing to watch command");
+									error!(%err, "while sending process completion event");
+									errors
+										.try_send(RuntimeError::EventChannelSend {
+											ctx: "command supervisor",
+											err,
+										})
+										.ok();
 								}
 							}
 						}
-						else => break,
 					}
-				}
+					Some(int) = int.recv() => {
+						match int {
+							Intervention::Timeout { after, escalation: ladder } => {
+								span.in_scope(|| debug!(?after, ?ladder, "setting time limit"));
+								if !timed_out {
+									deadline = Some(Instant::now() + after);
+									escalation = ladder.clone().into();
+								}
+								limit = Some((after, ladder));
+							}
+							int => {
+								if let Intervention::Kill = int {
+									span.in_scope(|| debug!("stopping the set"));
+									stopped = true;
+								}
 
-				span.in_scope(|| trace!("got out of loop, waiting once more"));
-				match process.wait().await {
-					Err(err) => {
-						let _enter = span.enter();
-						error!(%err, "while waiting on process");
-						errors.try_send(err).ok();
+								for tend in live.values() {
+									tend.send(int.clone()).await.ok();
+								}
+							}
+						}
 					}
-					Ok(status) => {
-						let event = span.in_scope(|| {
-							let event = Event {
-								tags: vec![
-									Tag::Source(Source::Internal),
-									Tag::ProcessCompletion(status.map(Into::into)),
-								],
-								metadata: Default::default(),
-							};
-
-							debug!(?event, "creating synthetic process completion event");
-							event
-						});
+					Some(()) = probed_r.recv(), if !is_ready => {
+						span.in_scope(|| info!("set is ready"));
+						is_ready = true;
+						if let Some(probe) = probe.take() {
+							probe.abort();
+						}
+						ready_notify.send(true).ok();
+
+						let mut tags = vec![Tag::Source(Source::Internal), Tag::ProcessReady];
+						if let Some(name) = job.name() {
+							tags.push(Tag::Job(name.into()));
+						}
+						let event = Event {
+							tags,
+							metadata: Default::default(),
+						};
 
+						span.in_scope(|| debug!(?event, "creating synthetic process ready event"));
 						if let Err(err) = events.send(event, Priority::Low).await {
 							let _enter = span.enter();
-							error!(%err, "while sending process completion event");
+							error!(%err, "while sending process ready event");
 							errors
 								.try_send(RuntimeError::EventChannelSend {
 									ctx: "command supervisor",
@@ -180,15 +486,36 @@ impl Supervisor {
 								.ok();
 						}
 					}
-				}
+					_ = sleep_until(ready_deadline.unwrap_or_else(Instant::now)), if !is_ready && ready_deadline.is_some() => {
+						let timeout = ready_timeout.unwrap_or_default();
+						span.in_scope(|| warn!(?timeout, "set is not ready in time, giving up on it"));
+						is_ready = true;
+						if let Some(probe) = probe.take() {
+							probe.abort();
+						}
+						ready_notify.send(true).ok();
+						errors.try_send(RuntimeError::ReadinessTimeout(timeout)).ok();
+					}
+					_ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+						timed_out = true;
+						stopped = true;
+						let int = match escalation.pop_front() {
+							Some((signal, grace)) if cfg!(not(windows)) => {
+								span.in_scope(|| debug!(?signal, ?grace, "time limit reached, stopping the set"));
+								deadline = Some(Instant::now() + grace);
+								Intervention::Signal(signal)
+							}
+							_ => {
+								span.in_scope(|| debug!("time limit reached, killing the set"));
+								deadline = None;
+								Intervention::Kill
+							}
+						};
 
-				let _enter = span.enter();
-				if let Some(cmd) = commands.pop() {
-					debug!(?cmd, "queuing up next command");
-					next = cmd;
-				} else {
-					debug!("no more commands to supervise");
-					break;
+						for tend in live.values() {
+							tend.send(int.clone()).await.ok();
+						}
+					}
 				}
 			}
 
@@ -202,11 +529,12 @@ impl Supervisor {
 
 		Ok(Self {
 			ongoing: waiter,
+			ready: ready_waiter,
 			intervene: int_s,
 		})
 	}
 
-	/// Issues a signal to the process.
+	/// Issues a signal to the running processes.
 	///
 	/// On Windows, this currently only supports [`Signal::ForceStop`].
 	///
@@ -225,7 +553,24 @@ impl Supervisor {
 		// only errors on channel closed, and that only happens if the process is dead
 	}
 
-	/// Stops the process.
+	/// Sets a time limit on the set, starting now.
+	///
+	/// When the limit is reached, the running processes are sent each signal of the `escalation`
+	/// in turn, waiting for its duration before moving on to the next, and killed if they're still
+	/// running at the end. On Windows, they are killed immediately. No further steps are run, and
+	/// the completion events of the processes which were stopped are tagged with [`Tag::Timeout`].
+	///
+	/// Setting a limit again replaces the previous one, unless it was already reached.
+	pub async fn timeout(&self, after: Duration, escalation: Vec<(Signal, Duration)>) {
+		trace!(?after, ?escalation, "sending timeout intervention");
+		self.intervene
+			.send(Intervention::Timeout { after, escalation })
+			.await
+			.ok();
+		// only errors on channel closed, and that only happens if the process is dead
+	}
+
+	/// Stops the running processes, and doesn't run any further steps.
 	///
 	/// While this is async, it returns once the signal intervention has been sent internally, not
 	/// when the signal has been delivered.
@@ -237,18 +582,47 @@ impl Supervisor {
 
 	/// Returns true if the supervisor is still running.
 	///
-	/// This is almost always equivalent to whether the _process_ is still running, but may not be
-	/// 100% in sync.
+	/// This is almost always equivalent to whether any _process_ of the set is still running or yet
+	/// to run, but may not be 100% in sync. It's false while waiting to restart the set.
 	pub fn is_running(&self) -> bool {
 		let ongoing = *self.ongoing.borrow();
 		trace!(?ongoing, "supervisor state");
 		ongoing
 	}
 
+	/// Returns true if the set is ready, as determined by its [`Readiness`] probe.
+	///
+	/// This is always true if there's no readiness probe, and when the set isn't running.
+	pub fn is_ready(&self) -> bool {
+		let ready = !self.is_running() || *self.ready.borrow();
+		trace!(?ready, "supervisor readiness");
+		ready
+	}
+
+	/// A watch on the readiness of the set, to wait for it to change without holding on to the
+	/// supervisor.
+	pub(crate) fn ready_watch(&self) -> watch::Receiver<bool> {
+		self.ready.clone()
+	}
+
+	/// Returns only when the set is ready, or isn't running anymore.
+	pub async fn wait_ready(&self) -> Result<(), RuntimeError> {
+		let mut ready = self.ready.clone();
+		while !*ready.borrow_and_update() {
+			debug!("waiting on supervisor readiness");
+			ready
+				.changed()
+				.await
+				.map_err(|err| RuntimeError::InternalSupervisor(err.to_string()))?;
+		}
+
+		Ok(())
+	}
+
 	/// Returns only when the supervisor completes.
 	///
-	/// This is almost always equivalent to waiting for the _process_ to complete, but may not be
-	/// 100% in sync.
+	/// This is almost always equivalent to waiting for the whole set of _processes_ to complete,
+	/// but may not be 100% in sync. It also returns when the set ends and is waiting to restart.
 	pub async fn wait(&self) -> Result<(), RuntimeError> {
 		if !*self.ongoing.borrow() {
 			trace!("supervisor already completed (pre)");
@@ -269,18 +643,297 @@ impl Supervisor {
 	}
 }
 
+/// The runs of a set: one per path in fan-out mode, or a single one otherwise.
+///
+/// The states of the steps of all runs are kept in one flat slice, run after run, such that the
+/// step `s` of the run `r` is at index `r * steps.len() + s`.
+#[derive(Debug)]
+struct Runs {
+	/// The events given to each run.
+	events: Vec<Arc<[Event]>>,
+
+	/// How many runs can be going at once.
+	concurrency: usize,
+
+	/// The next run to start.
+	next: usize,
+
+	/// How many runs have started and not yet settled.
+	active: usize,
+
+	/// Whether all the runs which settled were successful.
+	success: bool,
+}
+
+impl Runs {
+	fn new(events: Arc<[Event]>, fan_out: Option<NonZeroUsize>) -> Self {
+		let events = if fan_out.is_some() {
+			split_by_path(&events)
+		} else {
+			vec![events]
+		};
+
+		Self {
+			concurrency: fan_out.map_or(events.len(), NonZeroUsize::get),
+			events,
+			next: 0,
+			active: 0,
+			success: true,
+		}
+	}
+
+	fn reset(&mut self) {
+		self.next = 0;
+		self.active = 0;
+		self.success = true;
+	}
+
+	/// Starts as many runs as allowed, and returns the steps which are ready to run.
+	fn start(
+		&mut self,
+		steps: &[Step],
+		dependencies: &[Vec<usize>],
+		states: &mut [StepState],
+	) -> Vec<usize> {
+		let width = steps.len();
+		let mut ready = Vec::new();
+		while self.next < self.events.len() && self.active < self.concurrency {
+			let run = self.next;
+			trace!(%run, "starting run");
+			self.next += 1;
+			self.active += 1;
+			ready.extend(
+				next_steps(steps, dependencies, &mut states[run * width..][..width])
+					.into_iter()
+					.map(|step| run * width + step),
+			);
+		}
+
+		ready
+	}
+
+	/// Given a step which just settled, returns the steps which are ready to run, from its run
+	/// or from new runs if its run has settled.
+	fn next(
+		&mut self,
+		index: usize,
+		steps: &[Step],
+		dependencies: &[Vec<usize>],
+		states: &mut [StepState],
+	) -> Vec<usize> {
+		let width = steps.len();
+		let run = index / width;
+		let run_states = &mut states[run * width..][..width];
+		let mut ready: Vec<usize> = next_steps(steps, dependencies, run_states)
+			.into_iter()
+			.map(|step| run * width + step)
+			.collect();
+
+		if run_states
+			.iter()
+			.all(|state| matches!(state, StepState::Settled(_)))
+		{
+			trace!(%run, "run has settled");
+			self.active -= 1;
+			self.success &= run_states
+				.iter()
+				.all(|state| *state == StepState::Settled(true));
+			ready.extend(self.start(steps, dependencies, states));
+		}
+
+		ready
+	}
+}
+
+/// Splits events into one set of events per path, for fan-out.
+///
+/// Each set contains the events which have that path, with only that path. Events without paths
+/// are given to every set. If there are no paths at all, the events are kept as a single set.
+fn split_by_path(events: &Arc<[Event]>) -> Vec<Arc<[Event]>> {
+	let paths: BTreeSet<&Path> = events
+		.iter()
+		.flat_map(Event::paths)
+		.map(|(path, _)| path)
+		.collect();
+	if paths.is_empty() {
+		return vec![events.clone()];
+	}
+
+	paths
+		.into_iter()
+		.map(|path| {
+			events
+				.iter()
+				.filter(|event| {
+					event.paths().next().is_none() || event.paths().any(|(p, _)| p == path)
+				})
+				.map(|event| Event {
+					tags: event
+						.tags
+						.iter()
+						.filter(|tag| !matches!(tag, Tag::Path { path: p, .. } if p != path))
+						.cloned()
+						.collect(),
+					metadata: event.metadata.clone(),
+				})
+				.collect()
+		})
+		.collect()
+}
+
+/// Marks the steps which can't run anymore as settled, and returns those which are ready to run.
+///
+/// Steps are ready when all their dependencies have settled and their continuation permits it.
+fn next_steps(steps: &[Step], dependencies: &[Vec<usize>], states: &mut [StepState]) -> Vec<usize> {
+	let mut ready = Vec::new();
+	'steps: for (index, step) in steps.iter().enumerate() {
+		if states[index] != StepState::Pending {
+			continue;
+		}
+
+		let mut success = true;
+		for &dep in &dependencies[index] {
+			match states[dep] {
+				StepState::Settled(dep_success) => success &= dep_success,
+				StepState::Pending | StepState::Running => continue 'steps,
+			}
+		}
+
+		if dependencies[index].is_empty() || step.when.permits(success) {
+			trace!(step=%index, "step is ready to run");
+			states[index] = StepState::Running;
+			ready.push(index);
+		} else {
+			trace!(step=%index, ?success, "skipping step");
+			states[index] = StepState::Settled(success);
+		}
+	}
+
+	ready
+}
+
+/// Waits on a process of the set, forwarding interventions to it.
+#[allow(clippy::too_many_arguments)]
+async fn tend(
+	span: Span,
+	index: usize,
+	mut process: Process,
+	mut exit: ExitWatch,
+	readers: Vec<JoinHandle<()>>,
+	mut int: Receiver<Intervention>,
+	errors: Sender<RuntimeError>,
+	ended: Sender<StepEnd>,
+) {
+	let usage = loop {
+		select! {
+			u = exit.exited(&mut process) => {
+				match u {
+					Ok(usage) => break usage, // deal with it below
+					Err(err) => {
+						ended.send((index, Err(err))).await.ok();
+						return;
+					}
+				}
+			},
+			Some(int) = int.recv() => {
+				match int {
+					Intervention::Kill => {
+						if let Err(err) = process.kill().await {
+							let _enter = span.enter();
+							error!(%err, step=%index, "while killing process");
+							errors.try_send(err).ok();
+							trace!("continuing to watch command");
+						}
+					}
+					#[cfg(unix)]
+					Intervention::Signal(sig) => {
+						let _enter = span.enter();
+						if let Some(sig) = sig.to_nix() {
+							if let Err(err) = process.signal(sig) {
+								error!(%err, step=%index, "while sending signal to process");
+								errors.try_send(err).ok();
+								trace!("continuing to watch command");
+							}
+						} else {
+							let err = RuntimeError::UnsupportedSignal(sig);
+							error!(%err, step=%index, "while sending signal to process");
+							errors.try_send(err).ok();
+							trace!("continuing to watch command");
+						}
+					}
+					#[cfg(windows)]
+					Intervention::Signal(sig) => {
+						let _enter = span.enter();
+						// https://github.com/watchexec/watchexec/issues/219
+						let err = RuntimeError::UnsupportedSignal(sig);
+						error!(%err, step=%index, "while sending signal to process");
+						errors.try_send(err).ok();
+						trace!("continuing to watch command");
+					}
+					Intervention::Timeout { .. } => {
+						// handled by the supervisor, never sent to processes
+					}
+				}
+			}
+		}
+	};
+
+	span.in_scope(|| trace!(step=%index, "got out of loop, waiting once more"));
+	let status = process.wait().await.map(|status| (status, usage));
+
+	// let the output be read to the end so its events come before the completion event, but
+	// don't wait forever if the output is held open by something else (like a daemon child)
+	for reader in readers {
+		if timeout(OUTPUT_DRAIN_TIMEOUT, reader).await.is_err() {
+			span.in_scope(|| debug!(step=%index, "output still open after process ended"));
+		}
+	}
+
+	ended.send((index, status)).await.ok();
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn spawn_process(
 	span: Span,
 	command: Command,
+	job: JobId,
 	grouped: bool,
+	capture: Option<NonZeroUsize>,
+	pty: bool,
+	probe: Option<OutputProbe>,
+	events: priority::Sender<Event, Priority>,
 	actioned_events: Arc<[Event]>,
 	pre_spawn_handler: HandlerLock<PreSpawn>,
 	post_spawn_handler: HandlerLock<PostSpawn>,
-) -> Result<(Process, u32), RuntimeError> {
+) -> Result<(Process, u32, ExitWatch, Vec<JoinHandle<()>>), RuntimeError> {
+	#[cfg(unix)]
+	let pty = if pty { Some(Pty::open()?) } else { None };
+	#[cfg(unix)]
+	let terminal = pty.is_some();
+	#[cfg(not(unix))]
+	let terminal = {
+		if pty {
+			span.in_scope(|| {
+				tracing::warn!("pseudo-terminals are only supported on Unix, ignoring")
+			});
+		}
+		false
+	};
+
 	let (pre_spawn, spawnable) = span.in_scope::<_, Result<_, RuntimeError>>(|| {
-		debug!(%grouped, ?command, "preparing command");
-		#[cfg_attr(windows, allow(unused_mut))]
+		let command = command.substitute(&actioned_events);
+		debug!(%grouped, %terminal, ?command, "preparing command");
 		let mut spawnable = command.to_spawnable()?;
+		if capture.is_some() || probe.is_some() {
+			spawnable
+				.stdout(std::process::Stdio::piped())
+				.stderr(std::process::Stdio::piped());
+		}
+
+		#[cfg(unix)]
+		if let Some(pty) = &pty {
+			pty.attach(&mut spawnable)?;
+		}
 
 		// Required from Rust 1.66:
 		// https://github.com/rust-lang/rust/pull/101077
@@ -305,6 +958,7 @@ async fn spawn_process(
 		debug!("running pre-spawn handler");
 		Ok(PreSpawn::new(
 			command.clone(),
+			job.clone(),
 			spawnable,
 			actioned_events.clone(),
 		))
@@ -315,55 +969,117 @@ async fn spawn_process(
 		.await
 		.map_err(|e| rte("action pre-spawn", e.as_ref()))?;
 
-	let (proc, id, post_spawn) = span.in_scope::<_, Result<_, RuntimeError>>(|| {
-		let mut spawnable = Arc::try_unwrap(spawnable)
-			.map_err(|_| RuntimeError::HandlerLockHeld("pre-spawn"))?
-			.into_inner();
-
-		info!(command=?spawnable, "spawning command");
-		let (proc, id) = if grouped {
-			let proc = spawnable
-				.group()
-				.kill_on_drop(true)
-				.spawn()
-				.map_err(|err| RuntimeError::IoError {
-					about: "spawning process group",
-					err,
-				})?;
-			let id = proc.id().ok_or(RuntimeError::ProcessDeadOnArrival)?;
-			info!(pgid=%id, "process group spawned");
-			(Process::Grouped(proc), id)
-		} else {
-			let proc =
-				spawnable
-					.kill_on_drop(true)
-					.spawn()
-					.map_err(|err| RuntimeError::IoError {
-						about: "spawning process (ungrouped)",
-						err,
+	let (proc, id, exit, readers, post_spawn) =
+		span.in_scope::<_, Result<_, RuntimeError>>(|| {
+			let mut spawnable = Arc::try_unwrap(spawnable)
+				.map_err(|_| RuntimeError::HandlerLockHeld("pre-spawn"))?
+				.into_inner();
+
+			info!(command=?spawnable, "spawning command");
+			let (proc, id, stdout, stderr) = match () {
+				#[cfg(unix)]
+				() if terminal => {
+					// the process is made a session leader, so it can't also be put in a new group
+					let proc = spawnable.kill_on_drop(true).spawn().map_err(|err| {
+						RuntimeError::IoError {
+							about: "spawning process (terminal)",
+							err,
+						}
 					})?;
-			let id = proc.id().ok_or(RuntimeError::ProcessDeadOnArrival)?;
-			info!(pid=%id, "process spawned");
-			(Process::Ungrouped(proc), id)
-		};
+					let id = proc.id().ok_or(RuntimeError::ProcessDeadOnArrival)?;
+					info!(pgid=%id, "terminal process spawned");
+					(Process::Terminal(proc), id, None, None)
+				}
+				() if grouped => {
+					let mut proc = spawnable
+						.group()
+						.kill_on_drop(true)
+						.spawn()
+						.map_err(|err| RuntimeError::IoError {
+							about: "spawning process group",
+							err,
+						})?;
+					let id = proc.id().ok_or(RuntimeError::ProcessDeadOnArrival)?;
+					info!(pgid=%id, "process group spawned");
+					let (stdout, stderr) = (proc.inner().stdout.take(), proc.inner().stderr.take());
+					(Process::Grouped(proc), id, stdout, stderr)
+				}
+				() => {
+					let mut proc = spawnable.kill_on_drop(true).spawn().map_err(|err| {
+						RuntimeError::IoError {
+							about: "spawning process (ungrouped)",
+							err,
+						}
+					})?;
+					let id = proc.id().ok_or(RuntimeError::ProcessDeadOnArrival)?;
+					info!(pid=%id, "process spawned");
+					let (stdout, stderr) = (proc.stdout.take(), proc.stderr.take());
+					(Process::Ungrouped(proc), id, stdout, stderr)
+				}
+			};
+
+			let exit = ExitWatch::new(id);
+			let mut readers = Vec::with_capacity(2);
+			let output = capture.map(OutputBuffer::new);
 
-		debug!("running post-spawn handler");
-		Ok((
-			proc,
-			id,
-			PostSpawn {
-				command: command.clone(),
-				events: actioned_events.clone(),
+			#[cfg(unix)]
+			if let Some(pty) = pty {
+				readers.push(pty.forward(
+					id,
+					job.clone(),
+					output.clone(),
+					probe.clone(),
+					events.clone(),
+				)?);
+			}
+
+			if let Some(buffer) = &output {
+				debug!(lines=%buffer.capacity(), "capturing process output");
+			}
+			if let Some(stdout) = stdout {
+				readers.push(capture_output(
+					OutputStream::Stdout,
+					stdout,
+					id,
+					job.clone(),
+					output.clone(),
+					probe.clone(),
+					events.clone(),
+				));
+			}
+			if let Some(stderr) = stderr {
+				readers.push(capture_output(
+					OutputStream::Stderr,
+					stderr,
+					id,
+					job.clone(),
+					output.clone(),
+					probe.clone(),
+					events.clone(),
+				));
+			}
+
+			debug!("running post-spawn handler");
+			Ok((
+				proc,
 				id,
-				grouped,
-			},
-		))
-	})?;
+				exit,
+				readers,
+				PostSpawn {
+					command: command.clone(),
+					job: job.clone(),
+					events: actioned_events.clone(),
+					id,
+					grouped: grouped || terminal,
+					output,
+				},
+			))
+		})?;
 
 	post_spawn_handler
 		.call(post_spawn)
 		.await
 		.map_err(|e| rte("action post-spawn", e.as_ref()))?;
 
-	Ok((proc, id))
+	Ok((proc, id, exit, readers))
 }
@@ -1,5 +1,19 @@
-use super::{Command, Shell};
+use std::{num::NonZeroUsize, sync::Arc};
+
+use async_priority_channel as priority;
 use command_group::AsyncCommandGroup;
+use regex::Regex;
+use tokio::sync::mpsc;
+use watchexec_signals::Signal;
+
+use super::{
+	Command, Readiness, RestartOn, RestartPolicy, Shell, Step, Supervisor, SupervisorOptions,
+};
+use crate::{
+	action::JobId,
+	error::RuntimeError,
+	event::{Event, OutputStream, Priority, ProcessEnd, Tag},
+};
 
 #[tokio::test]
 #[cfg(unix)]
@@ -126,3 +140,702 @@ async fn windows_shell_unix_style_powershell() -> Result<(), std::io::Error> {
 	.success());
 	Ok(())
 }
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_tags_named_job_completions() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Command::Exec {
+			prog: "true".into(),
+			args: Vec::new(),
+		}
+		.into()],
+		SupervisorOptions {
+			job: JobId::from("frontend"),
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+	sup.wait().await.expect("wait on supervisor");
+
+	let (event, priority) = ev_r.recv().await.expect("completion event");
+	assert_eq!(priority, Priority::Low);
+	assert!(event.tags.contains(&Tag::Job("frontend".into())));
+	assert_eq!(event.jobs().collect::<Vec<_>>(), vec!["frontend"]);
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_follows_step_continuations() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let exec = |prog: &str| Command::Exec {
+		prog: prog.into(),
+		args: Vec::new(),
+	};
+
+	// false && true || true
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![
+			Step::always(exec("false")),
+			Step::on_success(exec("true")),
+			Step::on_failure(exec("true")),
+		],
+		Default::default(),
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+	sup.wait().await.expect("wait on supervisor");
+
+	// events of the same priority aren't necessarily received in order
+	let mut events = Vec::new();
+	while let Ok((event, _)) = ev_r.try_recv() {
+		events.push(event);
+	}
+	assert_eq!(events.len(), 2);
+
+	let (last, first): (Vec<_>, Vec<_>) = events
+		.into_iter()
+		.partition(|event| event.tags.contains(&Tag::SetCompletion { step: 2 }));
+	assert!(first[0]
+		.tags
+		.iter()
+		.any(|tag| matches!(tag, Tag::ProcessCompletion(Some(ProcessEnd::ExitError(_))))));
+	assert!(last[0]
+		.tags
+		.contains(&Tag::ProcessCompletion(Some(ProcessEnd::Success))));
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_runs_step_graphs() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let exec = |prog: &str| Command::Exec {
+		prog: prog.into(),
+		args: Vec::new(),
+	};
+
+	// (true & false) then either of the last two depending on both
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![
+			Step::always(exec("true")),
+			Step::always(exec("false")).after([]),
+			Step::on_success(exec("true")).after([0, 1]),
+			Step::on_failure(exec("true")).after([0, 1]),
+		],
+		Default::default(),
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+	sup.wait().await.expect("wait on supervisor");
+
+	let mut events = Vec::new();
+	while let Ok((event, _)) = ev_r.try_recv() {
+		events.push(event);
+	}
+	assert_eq!(events.len(), 3);
+	let set_completions: Vec<_> = events
+		.iter()
+		.flat_map(|event| &event.tags)
+		.filter(|tag| matches!(tag, Tag::SetCompletion { .. }))
+		.collect();
+	assert_eq!(set_completions, vec![&Tag::SetCompletion { step: 3 }]);
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_kills_all_branches() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, _ev_r) = priority::bounded(64);
+
+	let sleep = || Command::Exec {
+		prog: "sleep".into(),
+		args: vec!["10".into()],
+	};
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![
+			Step::always(sleep()),
+			Step::always(sleep()).after([]),
+			Step::always(sleep()).after([0, 1]),
+		],
+		Default::default(),
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	sup.kill().await;
+	tokio::time::timeout(std::time::Duration::from_secs(5), sup.wait())
+		.await
+		.expect("supervisor should end promptly")
+		.expect("wait on supervisor");
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_times_out() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![
+			Step::always(Command::Exec {
+				prog: "sleep".into(),
+				args: vec!["10".into()],
+			}),
+			Step::always(Command::Exec {
+				prog: "true".into(),
+				args: Vec::new(),
+			}),
+		],
+		Default::default(),
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	sup.timeout(
+		std::time::Duration::from_millis(100),
+		vec![(Signal::Terminate, std::time::Duration::from_secs(5))],
+	)
+	.await;
+	tokio::time::timeout(std::time::Duration::from_secs(5), sup.wait())
+		.await
+		.expect("supervisor should end promptly")
+		.expect("wait on supervisor");
+
+	let (event, _) = ev_r.recv().await.expect("completion event");
+	assert!(
+		event.tags.contains(&Tag::Timeout),
+		"completion should be marked as timed out: {event:?}"
+	);
+	assert_eq!(
+		event.completions().collect::<Vec<_>>(),
+		vec![Some(ProcessEnd::ExitSignal(Signal::Terminate))],
+		"only the first step should have run"
+	);
+	assert!(ev_r.is_empty(), "no further events expected");
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_escalates_timeout() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Step::always(Command::Exec {
+			prog: "sh".into(),
+			args: vec!["-c".into(), "trap '' INT TERM; sleep 10".into()],
+		})],
+		Default::default(),
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	sup.timeout(
+		std::time::Duration::from_millis(100),
+		vec![
+			(Signal::Interrupt, std::time::Duration::from_millis(100)),
+			(Signal::Terminate, std::time::Duration::from_millis(100)),
+		],
+	)
+	.await;
+	tokio::time::timeout(std::time::Duration::from_secs(5), sup.wait())
+		.await
+		.expect("supervisor should end promptly")
+		.expect("wait on supervisor");
+
+	let (event, _) = ev_r.recv().await.expect("completion event");
+	assert!(event.tags.contains(&Tag::Timeout));
+	assert_eq!(
+		event.completions().collect::<Vec<_>>(),
+		vec![Some(ProcessEnd::ExitSignal(Signal::ForceStop))],
+		"process ignoring the signals should have been killed"
+	);
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_restarts_with_backoff() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Step::always(Command::Exec {
+			prog: "false".into(),
+			args: Vec::new(),
+		})],
+		SupervisorOptions {
+			restart: Some(
+				RestartPolicy::new(RestartOn::Failure)
+					.backoff(
+						std::time::Duration::from_millis(10),
+						std::time::Duration::from_millis(50),
+					)
+					.max_retries(2),
+			),
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	let mut completions = 0;
+	while completions < 3 {
+		let (event, _) = tokio::time::timeout(std::time::Duration::from_secs(5), ev_r.recv())
+			.await
+			.expect("set should be restarted")
+			.expect("completion event");
+		completions += event.completions().count();
+	}
+
+	tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+	assert!(!sup.is_running(), "supervisor should have given up");
+	assert!(
+		ev_r.is_empty(),
+		"set should not be restarted more than twice"
+	);
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_fails_set_on_any_failed_step() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let exec = |prog: &str, args: &[&str]| Command::Exec {
+		prog: prog.into(),
+		args: args.iter().map(|arg| (*arg).into()).collect(),
+	};
+
+	// the failing branch ends before its succeeding sibling
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![
+			Step::always(exec("true", &[])),
+			Step::always(exec("false", &[])).after([0]),
+			Step::always(exec("sleep", &["0.2"])).after([0]),
+		],
+		SupervisorOptions {
+			restart: Some(
+				RestartPolicy::new(RestartOn::Failure)
+					.backoff(
+						std::time::Duration::from_millis(10),
+						std::time::Duration::from_millis(50),
+					)
+					.max_retries(1),
+			),
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	let mut completions = 0;
+	while completions < 6 {
+		let (event, _) = tokio::time::timeout(std::time::Duration::from_secs(5), ev_r.recv())
+			.await
+			.expect("set should be restarted as it failed")
+			.expect("completion event");
+		completions += event.completions().count();
+	}
+
+	sup.wait().await.expect("wait on supervisor");
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_restart_is_cancelled_by_kill() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Step::always(Command::Exec {
+			prog: "true".into(),
+			args: Vec::new(),
+		})],
+		SupervisorOptions {
+			restart: Some(RestartPolicy::new(RestartOn::Exit).backoff(
+				std::time::Duration::from_secs(1),
+				std::time::Duration::from_secs(1),
+			)),
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	ev_r.recv().await.expect("completion event");
+	sup.kill().await;
+	tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+	assert!(ev_r.is_empty(), "set should not be restarted after a kill");
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_waits_for_output_readiness() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Step::always(Command::Exec {
+			prog: "sh".into(),
+			args: vec![
+				"-c".into(),
+				"echo starting; sleep 0.2; echo listening on 1234; sleep 10".into(),
+			],
+		})],
+		SupervisorOptions {
+			readiness: Some(Readiness::Output(
+				Regex::new("^listening on").expect("valid pattern"),
+			)),
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	assert!(!sup.is_ready(), "should not be ready before the pattern");
+	tokio::time::timeout(std::time::Duration::from_secs(5), sup.wait_ready())
+		.await
+		.expect("should become ready")
+		.expect("wait for readiness");
+	assert!(sup.is_ready());
+
+	let (event, _) = ev_r.recv().await.expect("ready event");
+	assert!(event.tags.contains(&Tag::ProcessReady), "{event:?}");
+	sup.kill().await;
+	sup.wait().await.expect("wait on supervisor");
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_waits_for_port_readiness() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, _ev_r) = priority::bounded(64);
+
+	let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+	let port = listener.local_addr().expect("local addr").port();
+	drop(listener);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Step::always(Command::Exec {
+			prog: "sleep".into(),
+			args: vec!["10".into()],
+		})],
+		SupervisorOptions {
+			readiness: Some(Readiness::Port(port)),
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+	assert!(
+		!sup.is_ready(),
+		"should not be ready before the port is open"
+	);
+
+	let _listener = std::net::TcpListener::bind(("127.0.0.1", port)).expect("bind again");
+	tokio::time::timeout(std::time::Duration::from_secs(5), sup.wait_ready())
+		.await
+		.expect("should become ready")
+		.expect("wait for readiness");
+	sup.kill().await;
+	sup.wait().await.expect("wait on supervisor");
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_gives_up_on_readiness() {
+	let (er_s, mut er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Step::always(Command::Exec {
+			prog: "sleep".into(),
+			args: vec!["10".into()],
+		})],
+		SupervisorOptions {
+			readiness: Some(Readiness::File("/nonexistent/watchexec-never-ready".into())),
+			ready_timeout: Some(std::time::Duration::from_millis(300)),
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+
+	assert!(!sup.is_ready());
+	tokio::time::timeout(std::time::Duration::from_secs(5), sup.wait_ready())
+		.await
+		.expect("should stop waiting")
+		.expect("wait for readiness");
+	assert!(sup.is_running());
+	assert!(matches!(
+		er_r.recv().await,
+		Some(RuntimeError::ReadinessTimeout(_))
+	));
+	assert!(ev_r.is_empty(), "should not be reported as ready");
+
+	sup.kill().await;
+	sup.wait().await.expect("wait on supervisor");
+}
+
+#[test]
+fn supervisor_rejects_forward_dependencies() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, _ev_r) = priority::bounded(64);
+
+	let err = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Step::always(Command::Exec {
+			prog: "true".into(),
+			args: Vec::new(),
+		})
+		.after([0])],
+		Default::default(),
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect_err("self-dependency");
+	assert!(matches!(
+		err,
+		RuntimeError::InvalidStepDependency {
+			step: 0,
+			dependency: 0
+		}
+	));
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_captures_output() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Command::Exec {
+			prog: "sh".into(),
+			args: vec!["-c".into(), "echo out; echo err >&2".into()],
+		}
+		.into()],
+		SupervisorOptions {
+			capture: NonZeroUsize::new(10),
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+	sup.wait().await.expect("wait on supervisor");
+
+	let mut output = Vec::new();
+	while let Ok((event, _)) = ev_r.try_recv() {
+		output.extend(
+			event
+				.output()
+				.map(|(stream, line)| (stream, line.to_owned())),
+		);
+	}
+
+	output.sort();
+	assert_eq!(
+		output,
+		vec![
+			(OutputStream::Stdout, "out".into()),
+			(OutputStream::Stderr, "err".into())
+		]
+	);
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_runs_in_terminal() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Command::Exec {
+			prog: "sh".into(),
+			args: vec![
+				"-c".into(),
+				"test -t 0 && test -t 1 && test -t 2 && echo tty".into(),
+			],
+		}
+		.into()],
+		SupervisorOptions {
+			capture: NonZeroUsize::new(10),
+			pty: true,
+			..Default::default()
+		},
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+	sup.wait().await.expect("wait on supervisor");
+
+	let mut output = Vec::new();
+	let mut completions = Vec::new();
+	while let Ok((event, _)) = ev_r.try_recv() {
+		output.extend(
+			event
+				.output()
+				.map(|(stream, line)| (stream, line.to_owned())),
+		);
+		completions.extend(event.completions());
+	}
+
+	assert_eq!(completions, vec![Some(ProcessEnd::Success)]);
+	assert_eq!(output, vec![(OutputStream::Stdout, "tty".into())]);
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_measures_usage() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Command::Exec {
+			prog: "sleep".into(),
+			args: vec!["0.2".into()],
+		}
+		.into()],
+		Default::default(),
+		Arc::new([]),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+	sup.wait().await.expect("wait on supervisor");
+
+	let (event, _) = ev_r.recv().await.expect("completion event");
+	let usage = *event.usages().next().expect("usage tag");
+	assert!(usage.wall_time >= std::time::Duration::from_millis(200));
+	if cfg!(target_os = "linux") {
+		assert!(usage.user_time.is_some());
+		assert!(usage.system_time.is_some());
+		assert!(usage.max_rss.unwrap_or_default() > 0);
+	}
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn supervisor_fans_out_per_path() {
+	let (er_s, _er_r) = mpsc::channel(64);
+	let (ev_s, ev_r) = priority::bounded(64);
+
+	let events: Vec<Event> = ["/c", "/a", "/b"]
+		.into_iter()
+		.map(|path| Event {
+			tags: vec![Tag::Path {
+				path: path.into(),
+				file_type: None,
+			}],
+			metadata: Default::default(),
+		})
+		.collect();
+
+	let sup = Supervisor::spawn(
+		er_s,
+		ev_s,
+		vec![Command::Exec {
+			prog: "sh".into(),
+			args: vec!["-c".into(), "echo {path}".into()],
+		}
+		.into()],
+		SupervisorOptions {
+			capture: NonZeroUsize::new(10),
+			fan_out: NonZeroUsize::new(2),
+			..Default::default()
+		},
+		events.into(),
+		Default::default(),
+		Default::default(),
+	)
+	.expect("spawn supervisor");
+	sup.wait().await.expect("wait on supervisor");
+
+	let mut output = Vec::new();
+	let mut completions = 0;
+	let mut set_completions = 0;
+	while let Ok((event, _)) = ev_r.try_recv() {
+		output.extend(event.output().map(|(_, line)| line.to_owned()));
+		completions += event.completions().count();
+		set_completions += event
+			.tags
+			.iter()
+			.filter(|tag| matches!(tag, Tag::SetCompletion { .. }))
+			.count();
+	}
+
+	output.sort();
+	assert_eq!(output, vec!["/a", "/b", "/c"]);
+	assert_eq!(completions, 3);
+	assert_eq!(set_completions, 1);
+}
@@ -0,0 +1,114 @@
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+use std::time::Instant;
+
+#[cfg(target_os = "linux")]
+use nix::libc;
+#[cfg(target_os = "linux")]
+use tokio::task::{spawn_blocking, JoinHandle};
+use tracing::trace;
+
+use crate::{error::RuntimeError, event::ProcessUsage};
+
+use super::Process;
+
+/// Watches for a process to exit, and measures its resource usage.
+#[derive(Debug)]
+pub(crate) struct ExitWatch {
+	started: Instant,
+
+	#[cfg(target_os = "linux")]
+	exited: Option<JoinHandle<Option<libc::rusage>>>,
+}
+
+impl ExitWatch {
+	/// Starts watching a process which was just spawned.
+	pub(crate) fn new(pid: u32) -> Self {
+		#[cfg(not(target_os = "linux"))]
+		let _ = pid;
+
+		Self {
+			started: Instant::now(),
+			#[cfg(target_os = "linux")]
+			exited: Some(spawn_blocking(move || wait_exited(pid))),
+		}
+	}
+
+	/// Waits for the process to exit.
+	///
+	/// On Linux, the process is not reaped, so that its resource usage can be read: call
+	/// [`Process::wait()`] afterwards to get its exit status. Elsewhere, only the wall-clock time
+	/// is measured.
+	///
+	/// This can be cancelled and called again.
+	pub(crate) async fn exited(
+		&mut self,
+		process: &mut Process,
+	) -> Result<ProcessUsage, RuntimeError> {
+		#[cfg(target_os = "linux")]
+		if let Some(exited) = &mut self.exited {
+			let usage = exited.await;
+			self.exited = None;
+			if let Ok(Some(usage)) = usage {
+				let usage = ProcessUsage {
+					wall_time: self.started.elapsed(),
+					user_time: Some(duration(usage.ru_utime)),
+					system_time: Some(duration(usage.ru_stime)),
+					// this is in kilobytes on Linux
+					max_rss: u64::try_from(usage.ru_maxrss).ok().map(|kb| kb * 1024),
+				};
+				trace!(?usage, "process exited");
+				return Ok(usage);
+			}
+		}
+
+		process.wait().await?;
+		let usage = ProcessUsage {
+			wall_time: self.started.elapsed(),
+			..Default::default()
+		};
+		trace!(?usage, "process exited (without resource usage)");
+		Ok(usage)
+	}
+}
+
+/// Waits for a process to exit without reaping it, and returns its resource usage.
+///
+/// The usage includes that of the children of the process which it waited for.
+#[cfg(target_os = "linux")]
+fn wait_exited(pid: u32) -> Option<libc::rusage> {
+	// SAFETY: these are plain C structs, for which zeroes are valid
+	let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+	let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+	loop {
+		// the libc wrapper for waitid doesn't expose the rusage argument of the syscall
+		// SAFETY: the pointers are valid for the duration of the call
+		let ret = unsafe {
+			libc::syscall(
+				libc::SYS_waitid,
+				libc::P_PID,
+				pid as libc::id_t,
+				&mut info as *mut libc::siginfo_t,
+				libc::WEXITED | libc::WNOWAIT,
+				&mut usage as *mut libc::rusage,
+			)
+		};
+
+		if ret == 0 {
+			return Some(usage);
+		}
+
+		let err = std::io::Error::last_os_error();
+		if err.kind() != std::io::ErrorKind::Interrupted {
+			trace!(%err, %pid, "could not wait for process exit, resource usage is unavailable");
+			return None;
+		}
+	}
+}
+
+#[cfg(target_os = "linux")]
+fn duration(time: libc::timeval) -> Duration {
+	Duration::from_secs(time.tv_sec.unsigned_abs())
+		+ Duration::from_micros(time.tv_usec.unsigned_abs())
+}
@@ -1,15 +1,17 @@
 //! Configuration and builders for [`crate::Watchexec`].
 
-use std::{fmt, path::Path, sync::Arc, time::Duration};
+use std::{any::Any, collections::HashMap, fmt, num::NonZeroUsize, sync::Arc, time::Duration};
 
 use tracing::debug;
 
 use crate::{
-	action::{Action, PostSpawn, PreSpawn},
-	command::Command,
+	action::{Action, JobId, PausedEvents, PostSpawn, PreSpawn},
+	command::{Command, Readiness, RestartPolicy, Step},
 	filter::Filterer,
-	fs::Watcher,
+	fs::{Fallback, WatchedPath, Watcher},
 	handler::{Handler, HandlerLock},
+	source::{EventSource, SourceConfig},
+	timer::Schedule,
 	ErrorHook,
 };
 
@@ -39,6 +41,14 @@ pub struct RuntimeConfig {
 	/// Working data for keyboard event sources.
 	pub keyboard: crate::keyboard::WorkingData,
 
+	/// Working data for the timer event source.
+	pub timer: crate::timer::WorkingData,
+
+	/// Configuration for custom event sources, by name.
+	///
+	/// Sources without an entry here get an empty [`SourceConfig`].
+	pub sources: HashMap<String, SourceConfig>,
+
 	/// Working data for the action processing.
 	///
 	/// This is the task responsible for scheduling the actions in response to events, applying the
@@ -48,12 +58,14 @@ pub struct RuntimeConfig {
 
 impl RuntimeConfig {
 	/// Set the pathset to be watched.
+	///
+	/// Paths are watched recursively, unless given as [`WatchedPath`]s which say otherwise.
 	pub fn pathset<I, P>(&mut self, pathset: I) -> &mut Self
 	where
 		I: IntoIterator<Item = P>,
-		P: AsRef<Path>,
+		P: Into<WatchedPath>,
 	{
-		self.fs.pathset = pathset.into_iter().map(|p| p.as_ref().into()).collect();
+		self.fs.pathset = pathset.into_iter().map(Into::into).collect();
 		debug!(pathset=?self.fs.pathset, "RuntimeConfig: pathset");
 		self
 	}
@@ -65,12 +77,64 @@ impl RuntimeConfig {
 		self
 	}
 
+	/// Set what to do if the native file watcher runs out of resources.
+	///
+	/// See [`fs::Fallback`][crate::fs::Fallback] for details.
+	pub fn file_watcher_fallback(&mut self, fallback: Fallback) -> &mut Self {
+		debug!(?fallback, "RuntimeConfig: file_watcher_fallback");
+		self.fs.fallback = fallback;
+		self
+	}
+
+	/// Drop modifications which don't change the contents of files, remembering up to `capacity`
+	/// files, or stop doing so with `None`.
+	///
+	/// See [`fs::WorkingData::fingerprint`][crate::fs::WorkingData::fingerprint] for details.
+	pub fn file_fingerprint(&mut self, capacity: Option<usize>) -> &mut Self {
+		debug!(?capacity, "RuntimeConfig: file_fingerprint");
+		self.fs.fingerprint = capacity;
+		self
+	}
+
 	/// Enable monitoring of 'end of file' from stdin
 	pub fn keyboard_emit_eof(&mut self, enable: bool) -> &mut Self {
 		self.keyboard.eof = enable;
 		self
 	}
 
+	/// Enable reading key presses from stdin, in raw mode if it's a terminal.
+	///
+	/// See [`keyboard::WorkingData::keys`][crate::keyboard::WorkingData::keys] for more.
+	pub fn keyboard_emit_keys(&mut self, enable: bool) -> &mut Self {
+		debug!(?enable, "RuntimeConfig: keyboard_emit_keys");
+		self.keyboard.keys = enable;
+		self
+	}
+
+	/// Set the schedules on which timer events are emitted.
+	///
+	/// See [`timer::WorkingData::schedules`][crate::timer::WorkingData::schedules] for more.
+	pub fn timer_schedules(&mut self, schedules: impl IntoIterator<Item = Schedule>) -> &mut Self {
+		self.timer.schedules = schedules.into_iter().collect();
+		debug!(schedules=?self.timer.schedules, "RuntimeConfig: timer_schedules");
+		self
+	}
+
+	/// Set the configuration of a custom event source.
+	///
+	/// The source registered with [`InitConfig::event_source()`] under this name gets the value as
+	/// a [`SourceConfig`]. See the [`source`][crate::source] module for more.
+	pub fn source_config(
+		&mut self,
+		name: impl Into<String>,
+		config: impl Any + Send + Sync,
+	) -> &mut Self {
+		let name = name.into();
+		debug!(%name, "RuntimeConfig: source_config");
+		self.sources.insert(name, SourceConfig::new(config));
+		self
+	}
+
 	/// Set the action throttle.
 	pub fn action_throttle(&mut self, throttle: impl Into<Duration>) -> &mut Self {
 		self.action.throttle = throttle.into();
@@ -85,22 +149,116 @@ impl RuntimeConfig {
 		self
 	}
 
+	/// Enable capturing the output of commands, keeping up to that many lines per run.
+	///
+	/// See [`action::WorkingData::capture`][crate::action::WorkingData::capture] for more.
+	pub fn command_capture(&mut self, lines: Option<NonZeroUsize>) -> &mut Self {
+		debug!(?lines, "RuntimeConfig: command_capture");
+		self.action.capture = lines;
+		self
+	}
+
+	/// Toggle whether to run commands in a pseudo-terminal (on Unix only).
+	///
+	/// See [`action::WorkingData::pty`][crate::action::WorkingData::pty] for more.
+	pub fn command_pty(&mut self, pty: bool) -> &mut Self {
+		debug!(?pty, "RuntimeConfig: command_pty");
+		self.action.pty = pty;
+		self
+	}
+
+	/// Set the policy for restarting commands which end on their own.
+	///
+	/// See [`action::WorkingData::restart`][crate::action::WorkingData::restart] for more.
+	pub fn command_restart(&mut self, restart: Option<RestartPolicy>) -> &mut Self {
+		debug!(?restart, "RuntimeConfig: command_restart");
+		self.action.restart = restart;
+		self
+	}
+
+	/// Set the probe which determines when commands are ready.
+	///
+	/// See [`action::WorkingData::readiness`][crate::action::WorkingData::readiness] for more.
+	pub fn command_readiness(&mut self, readiness: Option<Readiness>) -> &mut Self {
+		debug!(?readiness, "RuntimeConfig: command_readiness");
+		self.action.readiness = readiness;
+		self
+	}
+
+	/// Set how long to wait for commands to be ready.
+	///
+	/// See [`action::WorkingData::ready_timeout`][crate::action::WorkingData::ready_timeout] for more.
+	pub fn command_ready_timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+		debug!(?timeout, "RuntimeConfig: command_ready_timeout");
+		self.action.ready_timeout = timeout;
+		self
+	}
+
+	/// Set what to do with changes while paused.
+	///
+	/// See [`action::WorkingData::paused_events`][crate::action::WorkingData::paused_events] for more.
+	pub fn paused_events(&mut self, paused_events: PausedEvents) -> &mut Self {
+		debug!(?paused_events, "RuntimeConfig: paused_events");
+		self.action.paused_events = paused_events;
+		self
+	}
+
+	/// Enable reducing the events of each action to their net effect.
+	///
+	/// See [`action::WorkingData::coalesce`][crate::action::WorkingData::coalesce] for more.
+	pub fn action_coalesce(&mut self, enable: bool) -> &mut Self {
+		debug!(?enable, "RuntimeConfig: action_coalesce");
+		self.action.coalesce = enable;
+		self
+	}
+
+	/// Enable running commands once per changed path, with up to that many runs at once.
+	///
+	/// See [`action::WorkingData::fan_out`][crate::action::WorkingData::fan_out] for more.
+	pub fn command_fan_out(&mut self, concurrency: Option<NonZeroUsize>) -> &mut Self {
+		debug!(?concurrency, "RuntimeConfig: command_fan_out");
+		self.action.fan_out = concurrency;
+		self
+	}
+
 	/// Set a single command to run on action.
 	///
 	/// This is a convenience for `.commands(vec![Command...])`.
 	pub fn command(&mut self, command: Command) -> &mut Self {
 		debug!(?command, "RuntimeConfig: command");
-		self.action.commands = vec![command];
+		self.action.commands = vec![command.into()];
 		self
 	}
 
 	/// Set the commands to run on action.
-	pub fn commands(&mut self, commands: impl Into<Vec<Command>>) -> &mut Self {
-		self.action.commands = commands.into();
+	///
+	/// This takes [`Step`]s, or plain [`Command`]s which always run.
+	pub fn commands(&mut self, commands: impl IntoIterator<Item = impl Into<Step>>) -> &mut Self {
+		self.action.commands = commands.into_iter().map(Into::into).collect();
 		debug!(commands=?self.action.commands, "RuntimeConfig: commands");
 		self
 	}
 
+	/// Set the commands to run for a job.
+	///
+	/// Setting the commands of the default job is equivalent to [`commands()`](Self::commands).
+	/// See [`action::WorkingData::jobs`][crate::action::WorkingData::jobs] for more.
+	pub fn job(
+		&mut self,
+		id: impl Into<JobId>,
+		commands: impl IntoIterator<Item = impl Into<Step>>,
+	) -> &mut Self {
+		let id = id.into();
+		let commands: Vec<Step> = commands.into_iter().map(Into::into).collect();
+		debug!(%id, ?commands, "RuntimeConfig: job");
+		if id.is_default() {
+			self.action.commands = commands;
+		} else {
+			self.action.jobs.insert(id, commands);
+		}
+		self
+	}
+
 	/// Set the filterer implementation to use.
 	pub fn filterer(&mut self, filterer: Arc<dyn Filterer>) -> &mut Self {
 		debug!(?filterer, "RuntimeConfig: filterer");
@@ -175,6 +333,11 @@ pub struct InitConfig {
 	/// The default (1024) is usually fine. If you expect a much larger throughput of events,
 	/// adjusting this value may help.
 	pub event_channel_size: usize,
+
+	/// Custom event sources.
+	///
+	/// These are run alongside the built-in sources. See the [`source`][crate::source] module.
+	pub sources: Vec<Box<dyn EventSource>>,
 }
 
 impl Default for InitConfig {
@@ -183,6 +346,7 @@ impl Default for InitConfig {
 			error_handler: Box::new(()) as _,
 			error_channel_size: 64,
 			event_channel_size: 1024,
+			sources: Vec::new(),
 		}
 	}
 }
@@ -214,6 +378,15 @@ impl InitConfig {
 		self.event_channel_size = size;
 		self
 	}
+
+	/// Add a custom event source.
+	///
+	/// See the [documentation on the field](InitConfig#structfield.sources) for more details.
+	pub fn event_source(&mut self, source: impl EventSource) -> &mut Self {
+		debug!(name=%source.name(), "InitConfig: event_source");
+		self.sources.push(Box::new(source));
+		self
+	}
 }
 
 impl fmt::Debug for InitConfig {
@@ -221,6 +394,10 @@ impl fmt::Debug for InitConfig {
 		f.debug_struct("InitConfig")
 			.field("error_channel_size", &self.error_channel_size)
 			.field("event_channel_size", &self.event_channel_size)
+			.field(
+				"sources",
+				&self.sources.iter().map(|s| s.name()).collect::<Vec<_>>(),
+			)
 			.finish_non_exhaustive()
 	}
 }
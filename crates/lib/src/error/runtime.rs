@@ -178,6 +178,13 @@ pub enum RuntimeError {
 	#[diagnostic(code(watchexec::runtime::process_doa))]
 	ProcessDeadOnArrival,
 
+	/// Error received when a command set wasn't ready within its readiness timeout.
+	///
+	/// The set keeps running, but events aren't held for it anymore.
+	#[error("command was not ready after {0:?}, handling events anyway")]
+	#[diagnostic(code(watchexec::runtime::readiness_timeout))]
+	ReadinessTimeout(std::time::Duration),
+
 	/// Error received when a [`Signal`] is unsupported
 	///
 	/// This may happen if the signal is not supported on the current platform, or if Watchexec
@@ -193,6 +200,19 @@ pub enum RuntimeError {
 	#[diagnostic(code(watchexec::runtime::no_commands))]
 	NoCommands,
 
+	/// Error received when a step of a command set depends on a step which doesn't come before it.
+	///
+	/// This is generally a programmer error and should be caught earlier.
+	#[error("step {step} depends on step {dependency}, which doesn't come before it")]
+	#[diagnostic(code(watchexec::runtime::invalid_step_dependency))]
+	InvalidStepDependency {
+		/// The index of the step with the invalid dependency.
+		step: usize,
+
+		/// The index of the dependency.
+		dependency: usize,
+	},
+
 	/// Error received when trying to render a [`Command::Shell`](crate::command::Command) that has no `command`
 	///
 	/// This is generally a programmer error and should be caught earlier.
@@ -4,7 +4,7 @@ use miette::Diagnostic;
 use thiserror::Error;
 use tokio::sync::watch;
 
-use crate::{action, fs, keyboard};
+use crate::{action, fs, keyboard, timer};
 
 // compatibility re-export
 #[deprecated(
@@ -32,6 +32,23 @@ pub enum ReconfigError {
 	#[error("reconfig: keyboard watch: {0}")]
 	#[diagnostic(code(watchexec::reconfig::keyboard_watch))]
 	KeyboardWatch(#[from] watch::error::SendError<keyboard::WorkingData>),
+
+	/// Error received when the timer event source cannot be updated.
+	#[error("reconfig: timer watch: {0}")]
+	#[diagnostic(code(watchexec::reconfig::timer_watch))]
+	TimerWatch(#[from] watch::error::SendError<timer::WorkingData>),
+}
+
+/// Error when parsing a cron schedule for a [timer][crate::timer].
+#[derive(Debug, Diagnostic, Error)]
+#[error("invalid cron schedule {expression:?}: {reason}")]
+#[diagnostic(code(watchexec::timer::cron_parse))]
+pub struct CronParseError {
+	/// The expression which failed to parse.
+	pub expression: String,
+
+	/// Why it failed.
+	pub reason: String,
 }
 
 /// Errors emitted by the filesystem watcher.
@@ -101,6 +118,48 @@ pub enum FsWatcherError {
 		#[source]
 		err: notify::Error,
 	},
+
+	/// Warning emitted when the native watcher ran out of resources, and polling is used instead.
+	///
+	/// This is only emitted with a [`Fallback`](crate::fs::Fallback) other than `Off`.
+	#[error(
+		"ran out of resources, polling {} instead (every {interval:?})",
+		.path.as_ref().map_or_else(|| "everything".to_string(), |path| format!("{path:?}"))
+	)]
+	#[diagnostic(
+		code(watchexec::fs_watcher::poll_fallback),
+		severity(Warning),
+		help("to watch natively again, raise the inotify limits, see inotify(7) and https://watchexec.github.io/docs/inotify-limits.html")
+	)]
+	PollFallback {
+		/// The path which is polled, or `None` if everything is.
+		path: Option<PathBuf>,
+
+		/// How often it's polled.
+		interval: std::time::Duration,
+
+		/// The underlying error.
+		#[source]
+		err: notify::Error,
+	},
+
+	/// Error received when a watched path was removed, replaced, or moved, and can't be watched
+	/// again nor waited for.
+	///
+	/// The path is no longer watched. Setting the pathset again retries it.
+	#[error("lost watch on {path:?} and could not recover it")]
+	#[diagnostic(
+		code(watchexec::fs_watcher::root_lost),
+		help("the path may have become unreadable, or all of its ancestors may have been removed")
+	)]
+	RootLost {
+		/// The path that was lost.
+		path: PathBuf,
+
+		/// The underlying error.
+		#[source]
+		err: notify::Error,
+	},
 }
 
 /// Errors emitted by the keyboard watcher.
@@ -112,4 +171,13 @@ pub enum KeyboardWatcherError {
 	#[error("failed to shut down stdin watcher")]
 	#[diagnostic(code(watchexec::keyboard_watcher))]
 	StdinShutdown,
+
+	/// Error received when putting the terminal in raw mode fails.
+	#[error("failed to put terminal in raw mode: {err}")]
+	#[diagnostic(code(watchexec::keyboard_watcher::raw_mode))]
+	RawMode {
+		/// The underlying error.
+		#[source]
+		err: std::io::Error,
+	},
 }
@@ -2,23 +2,48 @@
 
 use std::{
 	collections::{HashMap, HashSet},
+	env::current_dir,
 	fs::metadata,
 	mem::take,
 	path::{Path, PathBuf},
-	time::Duration,
+	time::{Duration, Instant},
 };
 
 use async_priority_channel as priority;
 use normalize_path::NormalizePath;
-use notify::{Config, Watcher as _};
-use tokio::sync::{mpsc, watch};
-use tracing::{debug, error, trace};
+use notify::{
+	event::{CreateKind, EventKind, ModifyKind, RemoveKind, RenameMode},
+	Config, Watcher as _,
+};
+use tokio::{
+	select, spawn,
+	sync::{mpsc, watch},
+	task::spawn_blocking,
+	time::timeout,
+};
+use tracing::{debug, error, trace, warn};
 
 use crate::{
 	error::{CriticalError, FsWatcherError, RuntimeError},
-	event::{Event, Priority, Source, Tag},
+	event::{Event, FileType, Priority, Source, Tag},
 };
 
+use fingerprint::Fingerprints;
+use missing::{Lookout, Missing};
+
+mod fingerprint;
+mod missing;
+
+/// How long to wait for the second half of a rename, after the first.
+///
+/// Watchers which report renames in two halves send them one right after the other, so this only
+/// needs to cover scheduling delays. A first half which isn't followed by its second within that
+/// time is sent on its own: that's a file moved in or out of the watched paths.
+const RENAME_WINDOW: Duration = Duration::from_millis(50);
+
+/// The longest events are held for while fingerprinting, if the watcher doesn't go quiet.
+const MAX_HOLD: Duration = Duration::from_millis(500);
+
 /// What kind of filesystem watcher to use.
 ///
 /// For now only native and poll watchers are supported. In the future there may be additional
@@ -58,20 +83,73 @@ impl Watcher {
 		}
 		.map_err(|err| RuntimeError::FsWatcher {
 			kind: self,
-			err: if cfg!(target_os = "linux")
-				&& (matches!(err.kind, notify::ErrorKind::MaxFilesWatch)
-					|| matches!(err.kind, notify::ErrorKind::Io(ref ioerr) if ioerr.raw_os_error() == Some(28)))
-			{
+			err: if too_many_watches(&err) {
 				FsWatcherError::TooManyWatches(err)
-			} else if cfg!(target_os = "linux")
-				&& matches!(err.kind, notify::ErrorKind::Io(ref ioerr) if ioerr.raw_os_error() == Some(24))
-			{
+			} else if too_many_handles(&err) {
 				FsWatcherError::TooManyHandles(err)
 			} else {
 				FsWatcherError::Create(err)
 			},
 		})
 	}
+
+	fn is_poll(self) -> bool {
+		matches!(self, Self::Poll(_))
+	}
+}
+
+fn too_many_watches(err: &notify::Error) -> bool {
+	cfg!(target_os = "linux")
+		&& (matches!(err.kind, notify::ErrorKind::MaxFilesWatch)
+			|| matches!(err.kind, notify::ErrorKind::Io(ref ioerr) if ioerr.raw_os_error() == Some(28)))
+}
+
+fn too_many_handles(err: &notify::Error) -> bool {
+	cfg!(target_os = "linux")
+		&& matches!(err.kind, notify::ErrorKind::Io(ref ioerr) if ioerr.raw_os_error() == Some(24))
+}
+
+/// What to do when the native watcher runs out of resources.
+///
+/// On Linux, watching large trees can exceed the limits on inotify watches or instances (see
+/// [`FsWatcherError::TooManyWatches`] and [`FsWatcherError::TooManyHandles`]). By default, this
+/// is reported as an error, and what couldn't be watched isn't. Polling can be used instead,
+/// which is slower to notice changes and costlier, but isn't limited in the same way. Falling back
+/// is reported with [`FsWatcherError::PollFallback`], as a warning.
+///
+/// This also applies when watched paths are watched again, after being replaced or created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fallback {
+	/// Don't fall back, only report the error.
+	Off,
+
+	/// Poll the watched paths which couldn't be watched natively, at this interval, and keep
+	/// watching the others natively.
+	///
+	/// Each of these is polled as a whole, including the parts of it which were watched natively
+	/// before running out.
+	PollFailed(Duration),
+
+	/// Switch to polling everything, at this interval.
+	///
+	/// This lasts until the [kind of watcher](WorkingData::watcher) is changed.
+	PollAll(Duration),
+}
+
+impl Default for Fallback {
+	fn default() -> Self {
+		Self::Off
+	}
+}
+
+impl Fallback {
+	fn interval(self) -> Option<Duration> {
+		match self {
+			Self::Off => None,
+			Self::PollFailed(interval) | Self::PollAll(interval) => Some(interval),
+		}
+	}
 }
 
 /// The configuration of the [fs][self] worker.
@@ -81,48 +159,199 @@ impl Watcher {
 #[non_exhaustive]
 pub struct WorkingData {
 	/// The set of paths to be watched.
+	///
+	/// Paths which don't exist are waited for: their nearest existing ancestor is watched instead,
+	/// and once they're created, they're watched and a create event is emitted for them. Watched
+	/// paths which are removed or moved away are waited for again in the same way, and those
+	/// which are replaced by another file or directory are watched again.
+	///
+	/// These events for watched paths are emitted by the worker itself, rather than reported by the
+	/// watcher, and carry `watched-root` metadata saying what happened: `created`, `removed`, or
+	/// `replaced`. Paths which can't be recovered are reported with
+	/// [`FsWatcherError::RootLost`].
+	///
+	/// Relative paths are made absolute from the current directory when the worker takes them.
 	pub pathset: Vec<WatchedPath>,
 
 	/// The kind of watcher to be used.
 	pub watcher: Watcher,
+
+	/// What to do if the native watcher runs out of resources.
+	pub fallback: Fallback,
+
+	/// Whether to drop modifications which don't change the contents of files, and how many files
+	/// to remember the contents of.
+	///
+	/// When this is set, each file modified is fingerprinted with its size, modified time,
+	/// permissions, and a hash of its contents (unless its size and modified time are the same as
+	/// the last time and older than a couple seconds, in which case the metadata is trusted and
+	/// it's assumed to be unchanged). Modify events for files whose
+	/// fingerprints don't change are dropped, so rewriting a file with the same contents (like
+	/// `touch` or some formatters do) doesn't trigger anything. Files larger than 16 MiB aren't
+	/// fingerprinted. To hash files once they're written rather than halfway through, events are
+	/// held until the watcher is quiet for a moment, for up to half a second.
+	///
+	/// There's nothing to compare the first modification of a file to, so that one always goes
+	/// through. Fingerprints are forgotten when files are created, removed, or renamed, and the
+	/// least recently modified ones are forgotten beyond the given number of files.
+	pub fingerprint: Option<usize>,
 }
 
 /// A path to watch.
 ///
-/// This is currently only a wrapper around a [`PathBuf`], but may be augmented in the future.
+/// Directories are watched recursively by default, that is, changes to anything within them are
+/// reported, however deep. They can also be watched [non-recursively](Self::non_recursive), for
+/// changes to the directory and its immediate children only, or [to a given
+/// depth](Self::max_depth).
+///
+/// Converting from a path makes a recursive `WatchedPath`.
 #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct WatchedPath(PathBuf);
+pub struct WatchedPath {
+	path: PathBuf,
+	depth: Option<usize>,
+}
+
+impl WatchedPath {
+	/// Watches a path recursively.
+	pub fn recursive(path: impl Into<PathBuf>) -> Self {
+		Self {
+			path: path.into(),
+			depth: None,
+		}
+	}
+
+	/// Watches a path non-recursively: only the path itself, and if it's a directory, its
+	/// immediate children.
+	///
+	/// This is the same as a [max depth](Self::max_depth) of 1.
+	pub fn non_recursive(path: impl Into<PathBuf>) -> Self {
+		Self::max_depth(path, 1)
+	}
+
+	/// Watches a path and what's within it, up to a depth.
+	///
+	/// A depth of 1 is the immediate children of the path, 2 is their children too, and so on. A
+	/// depth of 0 is treated as 1.
+	///
+	/// Only a depth of 1 can be watched natively: for deeper depths, the path is watched
+	/// recursively, and events for paths deeper than that are dropped.
+	pub fn max_depth(path: impl Into<PathBuf>, depth: usize) -> Self {
+		Self {
+			path: path.into(),
+			depth: Some(depth.max(1)),
+		}
+	}
+
+	/// The path being watched.
+	#[must_use]
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Whether the path is watched recursively, without a depth limit.
+	#[must_use]
+	pub fn is_recursive(&self) -> bool {
+		self.depth.is_none()
+	}
+
+	/// The maximum depth the path is watched to, if any.
+	#[must_use]
+	pub fn depth(&self) -> Option<usize> {
+		self.depth
+	}
+
+	/// Makes the path absolute, from the current directory, and normalises it.
+	///
+	/// Watchers report absolute paths, so this is what's compared to them.
+	fn absolute(&self) -> Self {
+		let path = if self.path.is_absolute() {
+			self.path.clone()
+		} else {
+			current_dir().map_or_else(|_| self.path.clone(), |cwd| cwd.join(&self.path))
+		};
+
+		Self {
+			path: path.normalize(),
+			depth: self.depth,
+		}
+	}
+
+	/// Whether a path is within this one, and not deeper than the depth limit.
+	///
+	/// This only works with absolute paths, see [`WatchedPath::absolute()`].
+	fn covers(&self, path: &Path) -> Option<bool> {
+		let rel = path.strip_prefix(&self.path).ok()?;
+		Some(
+			self.depth
+				.map_or(true, |depth| rel.components().count() <= depth),
+		)
+	}
+
+	fn mode(&self) -> notify::RecursiveMode {
+		if self.depth == Some(1) {
+			notify::RecursiveMode::NonRecursive
+		} else {
+			notify::RecursiveMode::Recursive
+		}
+	}
+}
 
 impl From<PathBuf> for WatchedPath {
 	fn from(path: PathBuf) -> Self {
-		Self(path)
+		Self::recursive(path)
+	}
+}
+
+impl From<&PathBuf> for WatchedPath {
+	fn from(path: &PathBuf) -> Self {
+		Self::recursive(path)
 	}
 }
 
 impl From<&str> for WatchedPath {
 	fn from(path: &str) -> Self {
-		Self(path.into())
+		Self::recursive(path)
+	}
+}
+
+impl From<String> for WatchedPath {
+	fn from(path: String) -> Self {
+		Self::recursive(path)
 	}
 }
 
 impl From<&Path> for WatchedPath {
 	fn from(path: &Path) -> Self {
-		Self(path.into())
+		Self::recursive(path)
 	}
 }
 
 impl From<WatchedPath> for PathBuf {
 	fn from(path: WatchedPath) -> Self {
-		path.0
+		path.path
 	}
 }
 
 impl AsRef<Path> for WatchedPath {
 	fn as_ref(&self) -> &Path {
-		self.0.as_ref()
+		self.path.as_ref()
 	}
 }
 
+/// Whether any of the paths of an event are within the depth limits of the watched paths.
+///
+/// Paths which aren't within any watched path are kept, as are events without paths.
+fn within_depth(pathset: &[WatchedPath], nev: &notify::Event) -> bool {
+	nev.paths.is_empty()
+		|| nev.paths.iter().any(|path| {
+			let mut covers = pathset
+				.iter()
+				.filter_map(|watched| watched.covers(path))
+				.peekable();
+			covers.peek().is_none() || covers.any(|covered| covered)
+		})
+}
+
 /// Launch the filesystem event worker.
 ///
 /// While you can run several, you should only have one.
@@ -166,59 +395,168 @@ pub async fn worker(
 	debug!("launching filesystem worker");
 
 	let mut watcher_type = Watcher::default();
-	let mut watcher = None;
-	let mut pathset = HashSet::new();
+	let mut watcher: Option<Box<dyn notify::Watcher + Send>> = None;
+	let mut pathset = Roots::new();
+	let mut missing = Missing::default();
+
+	// the watcher which ran out of resources, if polling everything instead
+	let mut degraded = None;
+	// the watcher for paths which couldn't be watched natively, if polling those
+	let mut poller: Option<Box<dyn notify::Watcher + Send>> = None;
+	let mut polled = HashSet::new();
+
+	let (recheck_s, mut recheck_r) = mpsc::unbounded_channel();
+	let (waiting_s, waiting_r) = watch::channel(Vec::new());
+	let (wanted_s, wanted_r) = watch::channel(Vec::new());
+	let starter = Starter {
+		working: working.clone(),
+		wanted: wanted_r,
+		lookout: Lookout::new(waiting_r, recheck_s),
+		events: events.clone(),
+		errors: errors.clone(),
+	};
+
+	// set when the watcher is changed to fall back to polling, to apply the pathset to it
+	let mut reapply = false;
+
+	loop {
+		if !take(&mut reapply) {
+			select! {
+				changed = working.changed() => {
+					if changed.is_err() {
+						break;
+					}
+				}
+				Some(()) = recheck_r.recv() => {
+					while recheck_r.try_recv().is_ok() {}
+					if let Some(w) = watcher.as_mut() {
+						let unattached =
+							recheck(w.as_mut(), watcher_type, &mut pathset, &mut missing, &events, &errors)
+								.await?;
+
+						// paths watched again can run out of resources too
+						let fallback = working.borrow().fallback;
+						for (path, what, err) in unattached {
+							match fall_back(
+								w.as_mut(),
+								path.clone(),
+								err,
+								watcher_type,
+								fallback,
+								&starter,
+								&mut poller,
+								&mut polled,
+								&errors,
+							)
+							.await?
+							{
+								FellBack::No(path, err) => {
+									errors.send(lost_root(watcher_type, path, err)).await?;
+								}
+								FellBack::Polled if polled.contains(&path) => {
+									let file_type = identity(path.path()).map(|(_, ft)| ft);
+									announce(path.path(), what, file_type, &events, &errors).await?;
+								}
+								FellBack::Polled => {}
+								FellBack::PollAll => {
+									degraded = Some(watcher_type);
+									reapply = true;
+									break;
+								}
+							}
+						}
+
+						waiting_s.send_replace(missing.waiting());
+					}
+
+					if reapply {
+						debug!("falling back to polling everything");
+						watcher.take();
+					}
+					continue;
+				}
+			}
+		}
 
-	while working.changed().await.is_ok() {
 		// In separate scope so we drop the working read lock as early as we can
-		let (new_watcher, to_watch, to_drop) = {
+		let (new_watcher, to_watch, to_drop, fallback) = {
 			let data = working.borrow();
 			trace!(?data, "filesystem worker got a working data change");
 
-			if data.pathset.is_empty() {
+			// made absolute once here, so they compare to the paths of events
+			let wanted = data
+				.pathset
+				.iter()
+				.map(WatchedPath::absolute)
+				.collect::<Vec<_>>();
+			wanted_s.send_replace(wanted.clone());
+
+			if wanted.is_empty() {
 				trace!("no more watched paths, dropping watcher");
 				watcher.take();
+				poller.take();
 				pathset.drain();
+				polled.drain();
+				missing.clear();
+				waiting_s.send_replace(Vec::new());
 				continue;
 			}
 
-			if watcher.is_none() || watcher_type != data.watcher {
+			if degraded.map_or(false, |kind| kind != data.watcher) {
+				degraded = None;
+			}
+			let wanted_type = match data.fallback.interval() {
+				Some(interval) if degraded.is_some() => Watcher::Poll(interval),
+				_ => data.watcher,
+			};
+
+			if watcher.is_none() || watcher_type != wanted_type {
+				poller.take();
 				pathset.drain();
+				polled.drain();
+				missing.clear();
 
-				(Some(data.watcher), data.pathset.clone(), Vec::new())
+				(Some(wanted_type), wanted, Vec::new(), data.fallback)
 			} else {
-				let mut to_watch = Vec::with_capacity(data.pathset.len());
+				let mut to_watch = Vec::with_capacity(wanted.len());
 				let mut to_drop = Vec::with_capacity(pathset.len());
-				for path in &data.pathset {
-					if !pathset.contains(path) {
+				for path in &wanted {
+					if !pathset.contains_key(path)
+						&& !missing.contains(path)
+						&& !polled.contains(path)
+					{
 						to_watch.push(path.clone());
 					}
 				}
 
-				for path in &pathset {
-					if !data.pathset.contains(path) {
+				for path in pathset.keys().chain(missing.paths()).chain(polled.iter()) {
+					if !wanted.contains(path) {
 						to_drop.push(path.clone());
 					}
 				}
 
-				(None, to_watch, to_drop)
+				(None, to_watch, to_drop, data.fallback)
 			}
 		};
 
 		if let Some(kind) = new_watcher {
 			debug!(?kind, "creating new watcher");
-			let n_errors = errors.clone();
-			let n_events = events.clone();
-			match kind.create(move |nev: Result<notify::Event, notify::Error>| {
-				trace!(event = ?nev, "receiving possible event from watcher");
-				if let Err(e) = process_event(nev, kind, &n_events) {
-					n_errors.try_send(e).ok();
-				}
-			}) {
+			match starter.start(kind) {
 				Ok(w) => {
 					watcher = Some(w);
 					watcher_type = kind;
 				}
+				Err(RuntimeError::FsWatcher {
+					err: FsWatcherError::TooManyWatches(err) | FsWatcherError::TooManyHandles(err),
+					..
+				}) if !kind.is_poll() && fallback.interval().is_some() => {
+					errors
+						.send(poll_fallback(kind, fallback, None, err))
+						.await?;
+					degraded = Some(kind);
+					reapply = true;
+					continue;
+				}
 				Err(e) => {
 					errors.send(e).await?;
 				}
@@ -229,6 +567,20 @@ pub async fn worker(
 			debug!(?to_watch, ?to_drop, "applying changes to the watcher");
 
 			for path in to_drop {
+				if missing.contains(&path) {
+					trace!(?path, "no longer waiting for path");
+					missing.remove(w.as_mut(), &path, &pathset);
+					continue;
+				}
+
+				if polled.remove(&path) {
+					trace!(?path, "removing path from the poll watcher");
+					if let Some(p) = poller.as_mut() {
+						p.unwatch(path.as_ref()).ok();
+					}
+					continue;
+				}
+
 				trace!(?path, "removing path from the watcher");
 				if let Err(err) = w.unwatch(path.as_ref()) {
 					error!(?err, "notify unwatch() error");
@@ -242,17 +594,47 @@ pub async fn worker(
 
 			for path in to_watch {
 				trace!(?path, "adding path to the watcher");
-				if let Err(err) = w.watch(path.as_ref(), notify::RecursiveMode::Recursive) {
-					error!(?err, "notify watch() error");
-					for e in notify_multi_path_errors(watcher_type, path, err, false) {
-						errors.send(e).await?;
+				let err = match attach(w.as_mut(), path.clone(), &mut pathset, &mut missing) {
+					Ok(_) => continue,
+					Err(err) => err,
+				};
+
+				let (path, err) = match fall_back(
+					w.as_mut(),
+					path,
+					err,
+					watcher_type,
+					fallback,
+					&starter,
+					&mut poller,
+					&mut polled,
+					&errors,
+				)
+				.await?
+				{
+					FellBack::No(path, err) => (path, err),
+					FellBack::Polled => continue,
+					FellBack::PollAll => {
+						degraded = Some(watcher_type);
+						reapply = true;
+						break;
 					}
+				};
+
+				error!(?err, "notify watch() error");
+				for e in notify_multi_path_errors(watcher_type, path, err, false) {
+					errors.send(e).await?;
+				}
 				// TODO: unwatch and re-watch manually while ignoring all the erroring paths
 				// See https://github.com/watchexec/watchexec/issues/218
-				} else {
-					pathset.insert(path);
-				}
 			}
+
+			waiting_s.send_replace(missing.waiting());
+		}
+
+		if reapply {
+			debug!("falling back to polling everything");
+			watcher.take();
 		}
 	}
 
@@ -260,6 +642,325 @@ pub async fn worker(
 	Ok(())
 }
 
+/// Starts watchers, along with the processing of their events.
+struct Starter {
+	working: watch::Receiver<WorkingData>,
+	wanted: watch::Receiver<Vec<WatchedPath>>,
+	lookout: Lookout,
+	events: priority::Sender<Event, Priority>,
+	errors: mpsc::Sender<RuntimeError>,
+}
+
+impl Starter {
+	fn start(&self, kind: Watcher) -> Result<Box<dyn notify::Watcher + Send>, RuntimeError> {
+		let (raw_s, raw_r) = mpsc::unbounded_channel();
+		let w = kind.create(move |nev: Result<notify::Event, notify::Error>| {
+			trace!(event = ?nev, "receiving possible event from watcher");
+			raw_s.send(nev).ok();
+		})?;
+
+		// this ends when the watcher is dropped, along with its end of the channel
+		spawn(process_events(
+			kind,
+			raw_r,
+			self.working.clone(),
+			self.wanted.clone(),
+			self.lookout.clone(),
+			self.events.clone(),
+			self.errors.clone(),
+		));
+		Ok(w)
+	}
+}
+
+/// What [`fall_back()`] did.
+enum FellBack {
+	/// Nothing, the error is given back.
+	No(WatchedPath, notify::Error),
+
+	/// The path is polled instead, or polling it failed and that was reported.
+	Polled,
+
+	/// The fallback is to poll everything, which is up to the caller.
+	PollAll,
+}
+
+/// Falls back to polling a path which couldn't be watched for lack of resources, if configured to.
+#[allow(clippy::too_many_arguments)]
+async fn fall_back(
+	w: &mut (dyn notify::Watcher + Send),
+	path: WatchedPath,
+	err: notify::Error,
+	kind: Watcher,
+	fallback: Fallback,
+	starter: &Starter,
+	poller: &mut Option<Box<dyn notify::Watcher + Send>>,
+	polled: &mut HashSet<WatchedPath>,
+	errors: &mpsc::Sender<RuntimeError>,
+) -> Result<FellBack, CriticalError> {
+	if !(too_many_watches(&err) || too_many_handles(&err)) || kind.is_poll() {
+		return Ok(FellBack::No(path, err));
+	}
+
+	// some of the path may have been watched before running out
+	w.unwatch(path.as_ref()).ok();
+
+	let interval = match fallback {
+		Fallback::Off => return Ok(FellBack::No(path, err)),
+		Fallback::PollAll(_) => {
+			errors
+				.send(poll_fallback(kind, fallback, None, err))
+				.await?;
+			return Ok(FellBack::PollAll);
+		}
+		Fallback::PollFailed(interval) => interval,
+	};
+
+	if poller.is_none() {
+		match starter.start(Watcher::Poll(interval)) {
+			Ok(p) => *poller = Some(p),
+			Err(e) => errors.send(e).await?,
+		}
+	}
+
+	let p = match poller.as_mut() {
+		Some(p) => p,
+		None => return Ok(FellBack::No(path, err)),
+	};
+
+	trace!(?path, "adding path to the poll watcher");
+	match p.watch(path.as_ref(), path.mode()) {
+		Ok(()) => {
+			errors
+				.send(poll_fallback(
+					kind,
+					fallback,
+					Some(path.clone().into()),
+					err,
+				))
+				.await?;
+			polled.insert(path);
+		}
+		Err(err) => {
+			for e in notify_multi_path_errors(Watcher::Poll(interval), path, err, false) {
+				errors.send(e).await?;
+			}
+		}
+	}
+
+	Ok(FellBack::Polled)
+}
+
+fn poll_fallback(
+	kind: Watcher,
+	fallback: Fallback,
+	path: Option<PathBuf>,
+	err: notify::Error,
+) -> RuntimeError {
+	warn!(?kind, ?fallback, ?path, ?err, "falling back to polling");
+	RuntimeError::FsWatcher {
+		kind,
+		err: FsWatcherError::PollFallback {
+			path,
+			interval: fallback.interval().unwrap_or_default(),
+			err,
+		},
+	}
+}
+
+/// The watched paths which are being watched, and what they were when they started to be.
+type Roots = HashMap<WatchedPath, Option<(Identity, FileType)>>;
+
+/// What identifies a file, to tell when a path is replaced by another file.
+#[cfg(unix)]
+type Identity = (u64, u64);
+#[cfg(not(unix))]
+type Identity = Option<std::time::SystemTime>;
+
+/// What identifies the file at a path, and what type of file it is.
+fn identity(path: &Path) -> Option<(Identity, FileType)> {
+	let meta = metadata(path).ok()?;
+	let file_type = meta.file_type().into();
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::MetadataExt;
+		Some(((meta.dev(), meta.ino()), file_type))
+	}
+
+	#[cfg(not(unix))]
+	{
+		Some((meta.created().ok(), file_type))
+	}
+}
+
+/// Watches a path, or waits for it to exist if it doesn't.
+///
+/// Returns whether the path is now watched.
+fn attach(
+	w: &mut (dyn notify::Watcher + Send),
+	path: WatchedPath,
+	pathset: &mut Roots,
+	missing: &mut Missing,
+) -> Result<bool, notify::Error> {
+	let mut tries = 0;
+	loop {
+		tries += 1;
+		match w.watch(path.as_ref(), path.mode()) {
+			Ok(()) => {
+				let id = identity(path.path());
+				pathset.insert(path, id);
+				return Ok(true);
+			}
+			Err(err) if !path.path().exists() => {
+				return missing
+					.wait(w, path, pathset)
+					.map(|()| false)
+					.map_err(|_| err);
+			}
+			// the path was created between the two, like when it's removed and created again
+			Err(err) if tries < 3 && is_not_found(&err) => {
+				trace!(?path, ?err, "path appeared while watching it, trying again");
+			}
+			Err(err) => return Err(err),
+		}
+	}
+}
+
+fn is_not_found(err: &notify::Error) -> bool {
+	match &err.kind {
+		notify::ErrorKind::PathNotFound => true,
+		notify::ErrorKind::Io(err) => err.kind() == std::io::ErrorKind::NotFound,
+		_ => false,
+	}
+}
+
+/// Looks again at whether watched paths exist, and are still the same files.
+///
+/// Watched paths which have been removed (or moved away) go back to waiting, those which have
+/// been replaced are watched again, and missing paths which now exist are watched. Each of these
+/// emits an event for the path, with `watched-root` metadata saying what happened: `removed`,
+/// `replaced`, or `created`.
+///
+/// Paths which exist but can't be watched again are returned, with what they'd be announced as,
+/// so they can [fall back](fall_back) to polling.
+async fn recheck(
+	w: &mut (dyn notify::Watcher + Send),
+	kind: Watcher,
+	pathset: &mut Roots,
+	missing: &mut Missing,
+	events: &priority::Sender<Event, Priority>,
+	errors: &mpsc::Sender<RuntimeError>,
+) -> Result<Vec<(WatchedPath, &'static str, notify::Error)>, CriticalError> {
+	let mut unattached = Vec::new();
+	for (path, id) in pathset.clone() {
+		let now = identity(path.path());
+		if now.is_some() && (id.is_none() || now == id) {
+			continue;
+		}
+
+		w.unwatch(path.as_ref()).ok();
+		pathset.remove(&path);
+		if now.is_none() {
+			debug!(?path, "watched path is gone");
+			// it may already be back, so what it was is what's reported
+			let file_type = id.map(|(_, file_type)| file_type);
+			announce(path.path(), "removed", file_type, events, errors).await?;
+		} else {
+			debug!(?path, "watched path was replaced");
+		}
+
+		match attach(w, path.clone(), pathset, missing) {
+			Ok(true) => {
+				let file_type = root_type(pathset, &path);
+				announce(path.path(), "replaced", file_type, events, errors).await?;
+			}
+			Ok(false) => {}
+			Err(err) => unattached.push((path, "replaced", err)),
+		}
+	}
+
+	let (appeared, failed) = missing.appeared(w, pathset);
+	for (path, err) in failed {
+		errors.send(lost_root(kind, path, err)).await?;
+	}
+
+	for path in appeared {
+		debug!(?path, "watched path now exists");
+		match attach(w, path.clone(), pathset, missing) {
+			Ok(true) => {
+				let file_type = root_type(pathset, &path);
+				announce(path.path(), "created", file_type, events, errors).await?;
+			}
+			Ok(false) => {}
+			Err(err) => unattached.push((path, "created", err)),
+		}
+	}
+
+	Ok(unattached)
+}
+
+fn lost_root(kind: Watcher, path: WatchedPath, err: notify::Error) -> RuntimeError {
+	RuntimeError::FsWatcher {
+		kind,
+		err: FsWatcherError::RootLost {
+			path: path.into(),
+			err,
+		},
+	}
+}
+
+/// The type of a watched path, as it was when it started to be watched.
+fn root_type(pathset: &Roots, path: &WatchedPath) -> Option<FileType> {
+	pathset
+		.get(path)
+		.copied()
+		.flatten()
+		.map(|(_, file_type)| file_type)
+}
+
+/// Emits an event for what happened to a watched path, which is or was of `file_type`.
+async fn announce(
+	path: &Path,
+	what: &str,
+	file_type: Option<FileType>,
+	events: &priority::Sender<Event, Priority>,
+	errors: &mpsc::Sender<RuntimeError>,
+) -> Result<(), CriticalError> {
+	let kind = match (what, file_type) {
+		("removed", Some(FileType::Dir)) => EventKind::Remove(RemoveKind::Folder),
+		("removed", Some(FileType::File)) => EventKind::Remove(RemoveKind::File),
+		("removed", _) => EventKind::Remove(RemoveKind::Any),
+		(_, Some(FileType::Dir)) => EventKind::Create(CreateKind::Folder),
+		(_, Some(FileType::File)) => EventKind::Create(CreateKind::File),
+		_ => EventKind::Create(CreateKind::Any),
+	};
+
+	let event = Event {
+		tags: vec![
+			Tag::Source(Source::Filesystem),
+			Tag::FileEventKind(kind),
+			Tag::Path {
+				path: path.normalize(),
+				file_type,
+			},
+		],
+		metadata: HashMap::from([("watched-root".to_string(), vec![what.to_string()])]),
+	};
+
+	trace!(?event, "announcing what happened to watched path");
+	if let Err(err) = events.try_send(event, Priority::Normal) {
+		errors
+			.send(RuntimeError::EventChannelTrySend {
+				ctx: "fs watcher",
+				err,
+			})
+			.await?;
+	}
+
+	Ok(())
+}
+
 fn notify_multi_path_errors(
 	kind: Watcher,
 	path: WatchedPath,
@@ -294,6 +995,227 @@ fn notify_multi_path_errors(
 	errs
 }
 
+/// Pairs up the halves of renames, drops writes which don't change anything if configured to,
+/// and processes the events from a watcher.
+async fn process_events(
+	kind: Watcher,
+	mut raw: mpsc::UnboundedReceiver<Result<notify::Event, notify::Error>>,
+	working: watch::Receiver<WorkingData>,
+	wanted: watch::Receiver<Vec<WatchedPath>>,
+	lookout: Lookout,
+	events: priority::Sender<Event, Priority>,
+	errors: mpsc::Sender<RuntimeError>,
+) {
+	let mut renames = Renames::default();
+	let mut fingerprints = Fingerprints::default();
+
+	// when fingerprinting, events are held until the watcher is quiet for as long as the rename
+	// window, so files are hashed once they're written rather than halfway through (like when
+	// they're truncated then written)
+	let mut held = Vec::new();
+	let mut held_since = Instant::now();
+
+	loop {
+		fingerprints.set_capacity(working.borrow().fingerprint);
+
+		let nev = if renames.is_pending() || !held.is_empty() {
+			if let Ok(nev) = timeout(RENAME_WINDOW, raw.recv()).await {
+				nev
+			} else {
+				if renames.is_pending() {
+					trace!("no second half for rename, sending the first on its own");
+				}
+				held.extend(renames.flush());
+				send_events(
+					take(&mut held),
+					kind,
+					&wanted,
+					&lookout,
+					&mut fingerprints,
+					&events,
+					&errors,
+				)
+				.await;
+				continue;
+			}
+		} else {
+			raw.recv().await
+		};
+
+		let nevs = match nev {
+			Some(Ok(nev)) => renames.pair(nev),
+			Some(Err(err)) => vec![Err(err)],
+			None => break,
+		};
+
+		let hold = fingerprints.is_enabled();
+		if hold && held.is_empty() {
+			held_since = Instant::now();
+		}
+		held.extend(nevs);
+		if hold && held_since.elapsed() < MAX_HOLD {
+			continue;
+		}
+
+		send_events(
+			take(&mut held),
+			kind,
+			&wanted,
+			&lookout,
+			&mut fingerprints,
+			&events,
+			&errors,
+		)
+		.await;
+	}
+
+	held.extend(renames.flush());
+	send_events(
+		held,
+		kind,
+		&wanted,
+		&lookout,
+		&mut fingerprints,
+		&events,
+		&errors,
+	)
+	.await;
+	trace!("watcher is gone, done processing its events");
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn send_events(
+	nevs: Vec<Result<notify::Event, notify::Error>>,
+	kind: Watcher,
+	wanted: &watch::Receiver<Vec<WatchedPath>>,
+	lookout: &Lookout,
+	fingerprints: &mut Fingerprints,
+	events: &priority::Sender<Event, Priority>,
+	errors: &mpsc::Sender<RuntimeError>,
+) {
+	let mut nevs = {
+		let pathset = wanted.borrow();
+		nevs.into_iter()
+			.filter(|nev| {
+				let nev = match nev {
+					Ok(nev) => nev,
+					Err(_) => return true,
+				};
+
+				if !lookout.check(&pathset, nev) {
+					return false;
+				}
+
+				if !within_depth(&pathset, nev) {
+					trace!(?nev, "event is deeper than watched, dropping");
+					return false;
+				}
+
+				true
+			})
+			.collect::<Vec<_>>()
+	};
+
+	if fingerprints.is_enabled() && !nevs.is_empty() {
+		// hashing reads files, so it's done off the async runtime
+		let mut fps = take(fingerprints);
+		let filtered = spawn_blocking(move || {
+			nevs.retain(|nev| nev.as_ref().map_or(true, |nev| fps.is_change(nev)));
+			(fps, nevs)
+		})
+		.await;
+		nevs = match filtered {
+			Ok((fps, nevs)) => {
+				*fingerprints = fps;
+				nevs
+			}
+			Err(err) => {
+				error!(%err, "fingerprinting panicked, dropping events");
+				return;
+			}
+		};
+	}
+
+	for nev in nevs {
+		if let Err(e) = process_event(nev, kind, events) {
+			errors.try_send(e).ok();
+		}
+	}
+}
+
+/// Correlates the halves of renames into single events.
+///
+/// Watchers report renames in various ways: as a `From` event for the original path followed by
+/// a `To` event for the new path, with a tracker (like inotify's cookie) to tie them together or
+/// not; as a `Both` event with both paths; or as inotify does, as all three. This turns the halves
+/// into a `Both` event, and drops `Both` events which duplicate halves already paired.
+#[derive(Debug, Default)]
+struct Renames {
+	/// A `From` half, waiting for its `To`.
+	pending: Option<notify::Event>,
+
+	/// The tracker of the last rename paired from halves.
+	paired: Option<usize>,
+}
+
+impl Renames {
+	fn is_pending(&self) -> bool {
+		self.pending.is_some()
+	}
+
+	fn flush(&mut self) -> Vec<Result<notify::Event, notify::Error>> {
+		self.pending.take().map(Ok).into_iter().collect()
+	}
+
+	fn pair(&mut self, mut nev: notify::Event) -> Vec<Result<notify::Event, notify::Error>> {
+		let mode = match nev.kind {
+			EventKind::Modify(ModifyKind::Name(mode)) => Some(mode),
+			_ => None,
+		};
+
+		match mode {
+			Some(RenameMode::From) if nev.paths.len() == 1 => {
+				trace!(?nev, "first half of rename, waiting for the second");
+				let flushed = self.flush();
+				self.pending = Some(nev);
+				flushed
+			}
+			Some(RenameMode::To)
+				if nev.paths.len() == 1
+					&& self
+						.pending
+						.as_ref()
+						.map_or(false, |from| from.tracker() == nev.tracker()) =>
+			{
+				let from = self.pending.take().expect("checked above");
+				trace!(?from, to=?nev, "pairing rename halves");
+				self.paired = nev.tracker();
+				nev.kind = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+				nev.paths.splice(0..0, from.paths);
+				vec![Ok(nev)]
+			}
+			Some(RenameMode::Both) if nev.tracker().is_some() && nev.tracker() == self.paired => {
+				trace!(?nev, "rename was already paired from its halves, dropping");
+				Vec::new()
+			}
+			_ => {
+				let mut nevs = self.flush();
+				if mode == Some(RenameMode::Both)
+					&& nev.tracker().is_some()
+					&& nevs.iter().any(|from| {
+						from.as_ref()
+							.map_or(false, |from| from.tracker() == nev.tracker())
+					}) {
+					trace!(?nev, "rename supersedes its first half");
+					nevs.clear();
+				}
+				nevs.push(Ok(nev));
+				nevs
+			}
+		}
+	}
+}
+
 fn process_event(
 	nev: Result<notify::Event, notify::Error>,
 	kind: Watcher,
@@ -308,14 +1230,23 @@ fn process_event(
 	tags.push(Tag::Source(Source::Filesystem));
 	tags.push(Tag::FileEventKind(nev.kind));
 
-	for path in nev.paths {
+	for path in &nev.paths {
 		// possibly pull file_type from whatever notify (or the native driver) returns?
 		tags.push(Tag::Path {
-			file_type: metadata(&path).ok().map(|m| m.file_type().into()),
+			file_type: metadata(path).ok().map(|m| m.file_type().into()),
 			path: path.normalize(),
 		});
 	}
 
+	if let (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) =
+		(nev.kind, nev.paths.as_slice())
+	{
+		tags.push(Tag::Rename {
+			from: from.normalize(),
+			to: to.normalize(),
+		});
+	}
+
 	if let Some(pid) = nev.attrs.process_id() {
 		tags.push(Tag::Process(pid));
 	}
@@ -342,3 +1273,161 @@ fn process_event(
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn rename(mode: RenameMode, path: &str, tracker: Option<usize>) -> notify::Event {
+		let nev =
+			notify::Event::new(EventKind::Modify(ModifyKind::Name(mode))).add_path(path.into());
+		match tracker {
+			Some(tracker) => nev.set_tracker(tracker),
+			None => nev,
+		}
+	}
+
+	fn paths(nevs: &[Result<notify::Event, notify::Error>]) -> Vec<(EventKind, Vec<&str>)> {
+		nevs.iter()
+			.flatten()
+			.map(|nev| {
+				(
+					nev.kind,
+					nev.paths
+						.iter()
+						.map(|p| p.to_str().unwrap_or("?"))
+						.collect(),
+				)
+			})
+			.collect()
+	}
+
+	const BOTH: EventKind = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+
+	#[test]
+	fn pairs_halves_and_drops_duplicate() {
+		// as inotify reports them
+		let mut renames = Renames::default();
+		assert!(renames
+			.pair(rename(RenameMode::From, "/a", Some(7)))
+			.is_empty());
+		assert!(renames.is_pending());
+		assert_eq!(
+			paths(&renames.pair(rename(RenameMode::To, "/b", Some(7)))),
+			vec![(BOTH, vec!["/a", "/b"])]
+		);
+		let both = rename(RenameMode::Both, "/a", Some(7)).add_path("/b".into());
+		assert!(renames.pair(both).is_empty());
+		assert!(!renames.is_pending());
+	}
+
+	#[test]
+	fn pairs_halves_without_tracker() {
+		// as Windows reports them
+		let mut renames = Renames::default();
+		assert!(renames
+			.pair(rename(RenameMode::From, "/a", None))
+			.is_empty());
+		assert_eq!(
+			paths(&renames.pair(rename(RenameMode::To, "/b", None))),
+			vec![(BOTH, vec!["/a", "/b"])]
+		);
+	}
+
+	#[test]
+	fn lone_half_goes_on_its_own() {
+		let mut renames = Renames::default();
+		assert!(renames
+			.pair(rename(RenameMode::From, "/a", Some(1)))
+			.is_empty());
+		let other = notify::Event::new(EventKind::Any).add_path("/c".into());
+		assert_eq!(
+			paths(&renames.pair(other)),
+			vec![
+				(
+					EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+					vec!["/a"]
+				),
+				(EventKind::Any, vec!["/c"]),
+			]
+		);
+		assert!(!renames.is_pending());
+		assert!(renames.flush().is_empty());
+	}
+
+	#[test]
+	fn depth_limits_events() {
+		let pathset = vec![
+			WatchedPath::non_recursive("/conf"),
+			WatchedPath::max_depth("/src", 2),
+			WatchedPath::recursive("/src/deep"),
+		];
+		let at = |path: &str| notify::Event::new(EventKind::Any).add_path(path.into());
+
+		assert!(within_depth(&pathset, &at("/conf")));
+		assert!(within_depth(&pathset, &at("/conf/a.toml")));
+		assert!(!within_depth(&pathset, &at("/conf/data/b.csv")));
+		assert!(within_depth(&pathset, &at("/src/lib/a.rs")));
+		assert!(!within_depth(&pathset, &at("/src/lib/x/a.rs")));
+		assert!(within_depth(&pathset, &at("/src/deep/x/y/a.rs")));
+		assert!(within_depth(&pathset, &at("/elsewhere/a/b/c")));
+		assert!(within_depth(
+			&pathset,
+			&at("/conf/data/b.csv").add_path("/conf/b.csv".into())
+		));
+	}
+
+	#[test]
+	fn depth_limits_events_of_relative_paths() {
+		let cwd = current_dir().expect("current dir");
+		let pathset = vec![WatchedPath::non_recursive("./conf/../conf").absolute()];
+		assert_eq!(pathset[0].path(), cwd.join("conf"));
+
+		// as watchers report them
+		let at = |path: &str| notify::Event::new(EventKind::Any).add_path(cwd.join(path));
+		assert!(within_depth(&pathset, &at("conf/a.toml")));
+		assert!(!within_depth(&pathset, &at("conf/data/b.csv")));
+	}
+
+	#[tokio::test]
+	async fn removal_is_announced_as_what_was_there() {
+		// the path may have been recreated as something else by the time it's announced
+		let path = std::env::temp_dir();
+		let (events_s, events_r) = priority::unbounded();
+		let (errors_s, _errors_r) = mpsc::channel(1);
+		announce(&path, "removed", Some(FileType::File), &events_s, &errors_s)
+			.await
+			.expect("announce");
+
+		let (event, _) = events_r.recv().await.expect("event");
+		assert!(event
+			.tags
+			.contains(&Tag::FileEventKind(EventKind::Remove(RemoveKind::File))));
+		assert_eq!(
+			event.paths().next().map(|(_, file_type)| file_type),
+			Some(Some(&FileType::File))
+		);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn identity_changes_when_replaced() {
+		let dir = std::env::temp_dir().join(format!("watchexec-identity-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).expect("create temp dir");
+		let path = dir.join("conf");
+		let tmp = dir.join("conf.tmp");
+
+		std::fs::write(&path, "1").expect("write");
+		let before = identity(&path);
+		assert!(before.is_some());
+		std::fs::write(&path, "2").expect("write");
+		assert_eq!(identity(&path), before);
+
+		std::fs::write(&tmp, "3").expect("write");
+		std::fs::rename(&tmp, &path).expect("rename");
+		assert_ne!(identity(&path), before);
+
+		std::fs::remove_dir_all(&dir).ok();
+		assert_eq!(identity(&path), None);
+	}
+}
@@ -0,0 +1,362 @@
+use std::{
+	collections::{hash_map::DefaultHasher, HashMap},
+	fs::{metadata, symlink_metadata, File, Permissions},
+	hash::Hasher,
+	io::{self, Read},
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
+};
+
+use notify::event::{CreateKind, EventKind, ModifyKind, RemoveKind};
+use tracing::trace;
+
+/// Files larger than this aren't hashed, and their changes are always passed on.
+const MAX_HASH_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The coarsest modified time resolution of common filesystems (FAT has two seconds).
+///
+/// Files modified more recently than this may be modified again without their modified time
+/// changing, so they're always hashed.
+const MTIME_GRANULARITY: Duration = Duration::from_secs(2);
+
+/// What's known of the contents of a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Fingerprint {
+	len: u64,
+	modified: Option<SystemTime>,
+	permissions: Permissions,
+	hash: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+	fingerprint: Fingerprint,
+	last_seen: u64,
+}
+
+/// A bounded cache of file fingerprints, to tell writes which change nothing from real changes.
+///
+/// Only files which have been modified since the cache was created are in it: there's nothing to
+/// compare the first modification of a file to, so that one is always a change.
+#[derive(Debug, Default)]
+pub(super) struct Fingerprints {
+	capacity: usize,
+	entries: HashMap<PathBuf, Entry>,
+	clock: u64,
+}
+
+impl Fingerprints {
+	/// Sets the maximum number of files to keep fingerprints of, or disables them with `None`.
+	pub fn set_capacity(&mut self, capacity: Option<usize>) {
+		let capacity = capacity.unwrap_or(0);
+		if capacity != self.capacity {
+			trace!(?capacity, "resizing fingerprint cache");
+			self.capacity = capacity;
+			self.evict();
+		}
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.capacity > 0
+	}
+
+	/// Whether the event should be passed on.
+	///
+	/// This is false for modifications of files which leave their contents and permissions as they
+	/// were. Other events forget the fingerprints of the paths they're about, and of the paths
+	/// within them, as they're now stale or unknown.
+	pub fn is_change(&mut self, nev: &notify::Event) -> bool {
+		if self.capacity == 0 {
+			return true;
+		}
+
+		match nev.kind {
+			EventKind::Modify(ModifyKind::Name(_)) => {}
+			EventKind::Modify(_) if !nev.paths.is_empty() => {
+				let mut changed = false;
+				for path in &nev.paths {
+					changed |= self.update(path);
+				}
+				if !changed {
+					trace!(?nev, "contents unchanged, dropping event");
+				}
+				return changed;
+			}
+			EventKind::Access(_) => return true,
+			_ => {}
+		}
+
+		let within = may_contain_files(nev);
+		for path in &nev.paths {
+			self.forget(path, within);
+		}
+		true
+	}
+
+	/// Fingerprints a file again, and returns whether it changed.
+	fn update(&mut self, path: &Path) -> bool {
+		self.clock += 1;
+		let previous = self
+			.entries
+			.get(path)
+			.map(|entry| entry.fingerprint.clone());
+		let fingerprint = match fingerprint(path, previous.as_ref()) {
+			Ok(Some(fingerprint)) => fingerprint,
+			Ok(None) => {
+				self.entries.remove(path);
+				return true;
+			}
+			Err(err) => {
+				trace!(?path, ?err, "could not fingerprint file");
+				self.entries.remove(path);
+				return true;
+			}
+		};
+
+		let changed = previous.map_or(true, |previous| {
+			previous.len != fingerprint.len
+				|| previous.hash != fingerprint.hash
+				|| previous.permissions != fingerprint.permissions
+		});
+
+		self.entries.insert(
+			path.to_owned(),
+			Entry {
+				fingerprint,
+				last_seen: self.clock,
+			},
+		);
+		self.evict();
+		changed
+	}
+
+	/// Forgets the fingerprint of a path, and if `within`, of all the paths within it.
+	fn forget(&mut self, path: &Path, within: bool) {
+		if within {
+			self.entries.retain(|known, _| !known.starts_with(path));
+		} else {
+			self.entries.remove(path);
+		}
+	}
+
+	/// Drops the least recently seen entries if over capacity.
+	///
+	/// This drops an eighth of the cache at a time, so it doesn't have to sort it on every insert.
+	fn evict(&mut self) {
+		if self.entries.len() <= self.capacity {
+			return;
+		}
+
+		let target = self.capacity - self.capacity / 8;
+		let mut ages = self
+			.entries
+			.values()
+			.map(|entry| entry.last_seen)
+			.collect::<Vec<_>>();
+		ages.sort_unstable();
+		let cutoff = ages[self.entries.len() - target - 1];
+		self.entries.retain(|_, entry| entry.last_seen > cutoff);
+		trace!(entries=%self.entries.len(), "evicted old fingerprints");
+	}
+}
+
+/// Whether the paths of an event may be directories, whose contents are affected too.
+///
+/// This goes by the kind of event if it says, or else by what's at the paths now. If nothing is
+/// there anymore, like for a removal, it can't be told and the answer is yes.
+fn may_contain_files(nev: &notify::Event) -> bool {
+	match nev.kind {
+		EventKind::Create(CreateKind::File) | EventKind::Remove(RemoveKind::File) => false,
+		EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder) => true,
+		_ => {
+			// for renames, the path which is still there tells what the other one was
+			let dirs = nev
+				.paths
+				.iter()
+				.filter_map(|path| symlink_metadata(path).ok().map(|meta| meta.is_dir()))
+				.collect::<Vec<_>>();
+			dirs.is_empty() || dirs.contains(&true)
+		}
+	}
+}
+
+/// Fingerprints a file, or returns `None` if it's not a file or too large.
+///
+/// If the size and modified time are the same as the previous fingerprint's, the contents are
+/// assumed to be the same, and the file isn't read. That's trusted only for modified times which
+/// are older than the filesystem's resolution, as more recent writes can't be told apart by them.
+fn fingerprint(path: &Path, previous: Option<&Fingerprint>) -> io::Result<Option<Fingerprint>> {
+	let meta = metadata(path)?;
+	if !meta.is_file() || meta.len() > MAX_HASH_SIZE {
+		return Ok(None);
+	}
+
+	let modified = meta.modified().ok();
+	let settled = modified.map_or(false, |modified| {
+		modified
+			.elapsed()
+			.map_or(false, |age| age >= MTIME_GRANULARITY)
+	});
+	if let Some(previous) = previous {
+		if settled && previous.len == meta.len() && previous.modified == modified {
+			return Ok(Some(Fingerprint {
+				permissions: meta.permissions(),
+				..previous.clone()
+			}));
+		}
+	}
+
+	Ok(Some(Fingerprint {
+		hash: hash(path)?,
+		len: meta.len(),
+		modified,
+		permissions: meta.permissions(),
+	}))
+}
+
+fn hash(path: &Path) -> io::Result<u64> {
+	let mut file = File::open(path)?;
+	let mut hasher = DefaultHasher::new();
+	let mut buf = vec![0; 64 * 1024];
+	loop {
+		let n = file.read(&mut buf)?;
+		if n == 0 {
+			break;
+		}
+		hasher.write(&buf[..n]);
+	}
+	Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod test {
+	use std::{
+		fs::{create_dir, remove_file, write},
+		time::{SystemTime, UNIX_EPOCH},
+	};
+
+	use notify::event::DataChange;
+
+	use super::*;
+
+	fn tempdir(name: &str) -> PathBuf {
+		let nanos = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |d| d.as_nanos());
+		let dir = std::env::temp_dir().join(format!("watchexec-fingerprint-{name}-{nanos}"));
+		create_dir(&dir).expect("create temp dir");
+		dir
+	}
+
+	fn event(kind: EventKind, path: &Path) -> notify::Event {
+		notify::Event::new(kind).add_path(path.to_owned())
+	}
+
+	const WRITE: EventKind = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+
+	#[test]
+	fn identical_writes_are_not_changes() {
+		let dir = tempdir("identical");
+		let file = dir.join("a.txt");
+		let mut fps = Fingerprints::default();
+		fps.set_capacity(Some(16));
+
+		write(&file, "hello").expect("write");
+		assert!(fps.is_change(&event(WRITE, &file)));
+
+		write(&file, "hello").expect("write");
+		assert!(!fps.is_change(&event(WRITE, &file)));
+
+		write(&file, "world").expect("write");
+		assert!(fps.is_change(&event(WRITE, &file)));
+
+		remove_file(&file).expect("remove");
+		assert!(fps.is_change(&event(EventKind::Remove(RemoveKind::File), &file)));
+		write(&file, "world").expect("write");
+		assert!(fps.is_change(&event(EventKind::Create(CreateKind::File), &file)));
+		assert!(fps.is_change(&event(WRITE, &file)));
+
+		std::fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn recent_writes_are_hashed_despite_same_metadata() {
+		let dir = tempdir("recent");
+		let file = dir.join("a.txt");
+
+		write(&file, "world").expect("write");
+		let meta = metadata(&file).expect("metadata");
+		let previous = Fingerprint {
+			len: meta.len(),
+			modified: meta.modified().ok(),
+			permissions: meta.permissions(),
+			hash: hash(&file).expect("hash") ^ 1,
+		};
+
+		let current = fingerprint(&file, Some(&previous))
+			.expect("fingerprint")
+			.expect("is a file");
+		assert_ne!(current.hash, previous.hash);
+
+		std::fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn removing_a_folder_forgets_its_files() {
+		let dir = tempdir("folder");
+		let sub = dir.join("sub");
+		create_dir(&sub).expect("create dir");
+		let inner = sub.join("a.txt");
+		let outer = dir.join("b.txt");
+		let mut fps = Fingerprints::default();
+		fps.set_capacity(Some(16));
+
+		for file in [&inner, &outer] {
+			write(file, "hello").expect("write");
+			fps.is_change(&event(WRITE, file));
+		}
+
+		fps.is_change(&event(EventKind::Remove(RemoveKind::Folder), &sub));
+		assert!(!fps.entries.contains_key(&inner));
+		assert!(fps.entries.contains_key(&outer));
+
+		fps.is_change(&event(EventKind::Remove(RemoveKind::File), &outer));
+		assert!(fps.entries.is_empty());
+
+		std::fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn disabled_passes_everything() {
+		let dir = tempdir("disabled");
+		let file = dir.join("a.txt");
+		let mut fps = Fingerprints::default();
+
+		write(&file, "hello").expect("write");
+		assert!(fps.is_change(&event(WRITE, &file)));
+		assert!(fps.is_change(&event(WRITE, &file)));
+
+		std::fs::remove_dir_all(dir).ok();
+	}
+
+	#[test]
+	fn cache_is_bounded() {
+		let dir = tempdir("bounded");
+		let mut fps = Fingerprints::default();
+		fps.set_capacity(Some(8));
+
+		for n in 0..20 {
+			let file = dir.join(format!("{n}.txt"));
+			write(&file, "x").expect("write");
+			fps.is_change(&event(WRITE, &file));
+			assert!(fps.entries.len() <= 8);
+		}
+
+		// the most recent is still known
+		assert!(!fps.is_change(&event(WRITE, &dir.join("19.txt"))));
+		// the oldest isn't
+		assert!(fps.is_change(&event(WRITE, &dir.join("0.txt"))));
+
+		std::fs::remove_dir_all(dir).ok();
+	}
+}
@@ -0,0 +1,289 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+};
+
+use normalize_path::NormalizePath;
+use notify::{
+	event::{EventKind, ModifyKind},
+	RecursiveMode,
+};
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, trace};
+
+use super::{Roots, WatchedPath};
+
+/// How many times to look again at missing paths in one go, if they keep changing.
+const MAX_RECHECKS: usize = 16;
+
+/// Watched paths which don't exist, and the ancestors watched in their stead.
+///
+/// Each missing path has its nearest existing ancestor watched non-recursively, so that its
+/// creation (or that of the next directory down to it) can be seen.
+#[derive(Debug, Default)]
+pub(super) struct Missing {
+	paths: HashMap<WatchedPath, PathBuf>,
+}
+
+impl Missing {
+	pub fn contains(&self, path: &WatchedPath) -> bool {
+		self.paths.contains_key(path)
+	}
+
+	pub fn paths(&self) -> impl Iterator<Item = &WatchedPath> {
+		self.paths.keys()
+	}
+
+	pub fn clear(&mut self) {
+		self.paths.clear();
+	}
+
+	/// The missing paths and the ancestors watched for them, for a [`Lookout`].
+	///
+	/// These are absolute and normalised like the watched paths, see [`WatchedPath::absolute()`].
+	pub fn waiting(&self) -> Vec<(PathBuf, PathBuf)> {
+		self.paths
+			.iter()
+			.map(|(path, ancestor)| (path.path().to_owned(), ancestor.clone()))
+			.collect()
+	}
+
+	/// Starts waiting for a path to exist, by watching its nearest existing ancestor.
+	pub fn wait(
+		&mut self,
+		w: &mut dyn notify::Watcher,
+		path: WatchedPath,
+		attached: &Roots,
+	) -> Result<(), notify::Error> {
+		let ancestor = nearest_ancestor(path.path())
+			.ok_or_else(|| notify::Error::path_not_found().add_path(path.clone().into()))?;
+
+		if self.paths.get(&path) == Some(&ancestor) {
+			return Ok(());
+		}
+
+		self.remove(w, &path, attached);
+		let watched = self.paths.values().any(|other| other == &ancestor)
+			|| attached.keys().any(|other| other.path() == ancestor);
+		if !watched {
+			trace!(?ancestor, "watching ancestor of missing path");
+			w.watch(&ancestor, RecursiveMode::NonRecursive)?;
+		}
+
+		debug!(?path, ?ancestor, "waiting for path to exist");
+		self.paths.insert(path, ancestor);
+		Ok(())
+	}
+
+	/// Stops waiting for a path, and unwatches its ancestor if nothing else needs it.
+	pub fn remove(&mut self, w: &mut dyn notify::Watcher, path: &WatchedPath, attached: &Roots) {
+		let ancestor = match self.paths.remove(path) {
+			Some(ancestor) => ancestor,
+			None => return,
+		};
+
+		let needed = self.paths.values().any(|other| other == &ancestor)
+			|| attached.keys().any(|other| other.path() == ancestor);
+		if !needed {
+			trace!(?ancestor, "unwatching ancestor of formerly missing path");
+			w.unwatch(&ancestor).ok();
+		}
+	}
+
+	/// Looks again at the missing paths, and returns those which now exist.
+	///
+	/// Those are no longer waited for. For the others, the nearest existing ancestor is looked up
+	/// again, and watched instead of the previous one if it's changed. As directories can be
+	/// created in quick succession (like with `mkdir -p`), this is repeated while they change.
+	pub fn appeared(
+		&mut self,
+		w: &mut dyn notify::Watcher,
+		attached: &Roots,
+	) -> (Vec<WatchedPath>, Vec<(WatchedPath, notify::Error)>) {
+		let mut appeared = Vec::new();
+		let mut errors = Vec::new();
+		for _ in 0..MAX_RECHECKS {
+			let mut moved = false;
+			for (path, ancestor) in self.paths.clone() {
+				if path.path().exists() {
+					self.remove(w, &path, attached);
+					appeared.push(path);
+				} else if nearest_ancestor(path.path()).as_ref() != Some(&ancestor) {
+					moved = true;
+					if let Err(err) = self.wait(w, path.clone(), attached) {
+						self.remove(w, &path, attached);
+						errors.push((path, err));
+					}
+				}
+			}
+
+			if !moved {
+				break;
+			}
+		}
+
+		(appeared, errors)
+	}
+}
+
+/// The closest ancestor of a path which exists and is a directory.
+fn nearest_ancestor(path: &Path) -> Option<PathBuf> {
+	path.ancestors()
+		.skip(1)
+		.map(|ancestor| {
+			if ancestor.as_os_str().is_empty() {
+				Path::new(".")
+			} else {
+				ancestor
+			}
+		})
+		.find(|ancestor| ancestor.is_dir())
+		.map(Path::to_owned)
+}
+
+/// What the event processor needs to know about missing paths.
+///
+/// Events which are only from watching the ancestors of missing paths are dropped, and events
+/// which may mean that watched paths have come to exist or stopped existing make the worker look
+/// again at them.
+#[derive(Clone, Debug)]
+pub(super) struct Lookout {
+	waiting: watch::Receiver<Vec<(PathBuf, PathBuf)>>,
+	recheck: mpsc::UnboundedSender<()>,
+}
+
+impl Lookout {
+	pub fn new(
+		waiting: watch::Receiver<Vec<(PathBuf, PathBuf)>>,
+		recheck: mpsc::UnboundedSender<()>,
+	) -> Self {
+		Self { waiting, recheck }
+	}
+
+	/// Whether the event should be passed on.
+	///
+	/// The watched paths must be [absolute](WatchedPath::absolute), as the paths of events are.
+	pub fn check(&self, pathset: &[WatchedPath], nev: &notify::Event) -> bool {
+		if nev.paths.is_empty() || matches!(nev.kind, EventKind::Access(_)) {
+			return true;
+		}
+
+		let waiting = self.waiting.borrow();
+		let paths = nev
+			.paths
+			.iter()
+			.map(|path| path.normalize())
+			.collect::<Vec<_>>();
+
+		let on_the_way = paths
+			.iter()
+			.any(|path| waiting.iter().any(|(root, _)| root.starts_with(path)));
+		let removed = matches!(
+			nev.kind,
+			EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+		) && paths.iter().any(|path| {
+			pathset
+				.iter()
+				.any(|watched| watched.path().starts_with(path))
+		});
+		if on_the_way || removed {
+			trace!(?nev, "event may concern the existence of watched paths");
+			self.recheck.send(()).ok();
+		}
+
+		if waiting.is_empty() {
+			return true;
+		}
+
+		let noise = paths.iter().all(|path| {
+			let is_missing = waiting.iter().any(|(root, _)| root == path);
+			let near_ancestor = waiting
+				.iter()
+				.any(|(_, ancestor)| path == ancestor || path.parent() == Some(ancestor.as_path()));
+			let attached = pathset.iter().any(|watched| {
+				!waiting.iter().any(|(root, _)| root == watched.path())
+					&& watched.covers(path) == Some(true)
+			});
+			is_missing || (near_ancestor && !attached)
+		});
+		if noise {
+			trace!(?nev, "event is from watching for missing paths, dropping");
+		}
+		!noise
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn drops_ancestor_events() {
+		let (_waiting_s, waiting_r) = watch::channel(vec![(
+			PathBuf::from("/proj/build/gen"),
+			PathBuf::from("/proj"),
+		)]);
+		let (recheck_s, mut recheck_r) = mpsc::unbounded_channel();
+		let lookout = Lookout::new(waiting_r, recheck_s);
+		let pathset = vec![
+			WatchedPath::recursive("/proj/build/gen"),
+			WatchedPath::recursive("/proj/src"),
+		];
+		let at = |kind, path: &str| notify::Event::new(kind).add_path(path.into());
+		let create = EventKind::Create(notify::event::CreateKind::Folder);
+
+		assert!(!lookout.check(&pathset, &at(create, "/proj/README")));
+		assert!(recheck_r.try_recv().is_err());
+
+		assert!(lookout.check(&pathset, &at(create, "/proj/src/main.rs")));
+		assert!(recheck_r.try_recv().is_err());
+
+		assert!(!lookout.check(&pathset, &at(create, "/proj/build")));
+		assert!(recheck_r.try_recv().is_ok());
+
+		let remove = EventKind::Remove(notify::event::RemoveKind::Folder);
+		assert!(lookout.check(&pathset, &at(remove, "/proj/src")));
+		assert!(recheck_r.try_recv().is_ok());
+	}
+
+	#[test]
+	fn relative_paths_match_events() {
+		let missing_dir = format!("watchexec-missing-{}", std::process::id());
+		let path = WatchedPath::recursive(format!("./{missing_dir}/gen")).absolute();
+		let mut missing = Missing::default();
+		missing
+			.wait(&mut notify::NullWatcher, path.clone(), &Roots::new())
+			.expect("wait");
+
+		let (_waiting_s, waiting_r) = watch::channel(missing.waiting());
+		let (recheck_s, mut recheck_r) = mpsc::unbounded_channel();
+		let lookout = Lookout::new(waiting_r, recheck_s);
+		let pathset = vec![path];
+
+		// as watchers report them
+		let cwd = std::env::current_dir().expect("current dir");
+		let at = |path: &str| {
+			notify::Event::new(EventKind::Create(notify::event::CreateKind::Folder))
+				.add_path(cwd.join(path))
+		};
+
+		assert!(!lookout.check(&pathset, &at("README")));
+		assert!(recheck_r.try_recv().is_err());
+
+		assert!(!lookout.check(&pathset, &at(&missing_dir)));
+		assert!(recheck_r.try_recv().is_ok());
+	}
+
+	#[test]
+	fn nearest_existing_ancestor() {
+		let dir = std::env::temp_dir();
+		assert_eq!(
+			nearest_ancestor(&dir.join("watchexec-missing/a/b")),
+			Some(dir.clone())
+		);
+		assert_eq!(
+			nearest_ancestor(Path::new("watchexec-missing")),
+			Some(PathBuf::from("."))
+		);
+	}
+}
@@ -1,11 +1,14 @@
 //! Event source for keyboard input and related events
-use async_priority_channel as priority;
-use tokio::{
-	io::AsyncReadExt,
-	sync::{mpsc, oneshot, watch},
+use std::{
+	io::Read,
+	sync::{Arc, Mutex, PoisonError},
 };
-use tracing::trace;
-pub use watchexec_events::Keyboard;
+
+use async_priority_channel as priority;
+use once_cell::sync::Lazy;
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::{debug, trace};
+pub use watchexec_events::{Key, Keyboard};
 
 use crate::{
 	error::{CriticalError, KeyboardWatcherError, RuntimeError},
@@ -20,6 +23,21 @@ use crate::{
 pub struct WorkingData {
 	/// Whether or not to watch for 'end of file' on stdin
 	pub eof: bool,
+
+	/// Whether or not to read key presses from stdin
+	///
+	/// Each key press is emitted as a [`Keyboard::Key`] event, which the action handler can bind
+	/// to whatever it wants. If stdin is a terminal, it's put in raw mode (on Unix only) so that
+	/// keys are read as they're pressed rather than line by line, and aren't echoed; it's restored
+	/// when this is disabled again or Watchexec stops. Signal keys like Ctrl-C keep working as
+	/// usual. If stdin isn't a terminal, its input is read as keys without changing anything.
+	///
+	/// In raw mode, Ctrl-D is also emitted as [`Keyboard::Eof`] if [`eof`](WorkingData::eof) is
+	/// enabled, as the terminal doesn't translate it to an end of file anymore.
+	///
+	/// Note that commands share the terminal with Watchexec: they inherit its raw mode, and input
+	/// meant for them may be read as keys instead.
+	pub keys: bool,
 }
 
 /// Launch the filesystem event worker.
@@ -32,34 +50,43 @@ pub async fn worker(
 	errors: mpsc::Sender<RuntimeError>,
 	events: priority::Sender<Event, Priority>,
 ) -> Result<(), CriticalError> {
-	let mut send_close = None;
+	let mut send_close: Option<oneshot::Sender<()>> = None;
+	let mut watching = (false, false);
 	while working.changed().await.is_ok() {
-		let watch_for_eof = { working.borrow().eof };
-		match (watch_for_eof, &send_close) {
-			// If we want to watch stdin and we're not already watching it then spawn a task to watch it
-			(true, None) => {
-				let (close_s, close_r) = tokio::sync::oneshot::channel::<()>();
-
-				send_close = Some(close_s);
-				tokio::spawn(watch_stdin(errors.clone(), events.clone(), close_r));
-			}
-			// If we don't want to watch stdin but we are already watching it then send a close signal to end the
-			// watching
-			(false, Some(_)) => {
-				// Repeat match using 'take'
-				if let Some(close_s) = send_close.take() {
-					if close_s.send(()).is_err() {
-						errors
-							.send(RuntimeError::KeyboardWatcher {
-								err: KeyboardWatcherError::StdinShutdown,
-							})
-							.await?;
-					}
-				}
+		let want = {
+			let working = working.borrow();
+			(working.eof, working.keys)
+		};
+		if want == watching {
+			// No action is required
+			continue;
+		}
+
+		// If we're already watching stdin, send a close signal to end the watching, and start
+		// again with the new settings below if we still want to watch it
+		if let Some(close_s) = send_close.take() {
+			if close_s.send(()).is_err() {
+				errors
+					.send(RuntimeError::KeyboardWatcher {
+						err: KeyboardWatcherError::StdinShutdown,
+					})
+					.await?;
 			}
-			// Otherwise no action is required
-			_ => {}
 		}
+
+		if want.0 || want.1 {
+			let (close_s, close_r) = oneshot::channel::<()>();
+
+			send_close = Some(close_s);
+			tokio::spawn(watch_stdin(
+				errors.clone(),
+				events.clone(),
+				want.0,
+				want.1,
+				close_r,
+			));
+		}
+		watching = want;
 	}
 
 	Ok(())
@@ -68,24 +95,56 @@ pub async fn worker(
 async fn watch_stdin(
 	errors: mpsc::Sender<RuntimeError>,
 	events: priority::Sender<Event, Priority>,
+	eof: bool,
+	keys: bool,
 	mut close_r: oneshot::Receiver<()>,
 ) -> Result<(), CriticalError> {
-	let mut stdin = tokio::io::stdin();
-	let mut buffer = [0; 10];
+	// Restores the terminal when dropped, including when this task is dropped on shutdown
+	let raw = if keys {
+		match RawMode::enter() {
+			Ok(raw) => Some(raw),
+			Err(err) => {
+				errors
+					.send(RuntimeError::KeyboardWatcher {
+						err: KeyboardWatcherError::RawMode { err },
+					})
+					.await?;
+				None
+			}
+		}
+	} else {
+		None
+	};
+	let is_raw = raw.as_ref().map_or(false, RawMode::is_active);
+
+	let mut read_r = subscribe_stdin();
+
 	loop {
 		tokio::select! {
-			result = stdin.read(&mut buffer[..]) => {
+			result = read_r.recv() => {
 				// Read from stdin and if we've read 0 bytes then we assume stdin has received an 'eof' so
 				// we send that event into the system and break out of the loop as 'eof' means that there will
 				// be no more information on stdin.
 				match result {
-					Ok(0) => {
-						send_event(errors, events, Keyboard::Eof).await?;
+					Some(Ok(bytes)) if bytes.is_empty() => {
+						if eof {
+							send_event(&errors, &events, Keyboard::Eof).await?;
+						}
 						break;
 					}
-					Err(_) => break,
-					_ => {
+					Some(Ok(bytes)) => {
+						if !keys {
+							continue;
+						}
+
+						for key in parse_keys(&bytes) {
+							if is_raw && eof && key == Key::Ctrl('d') {
+								send_event(&errors, &events, Keyboard::Eof).await?;
+							}
+							send_event(&errors, &events, Keyboard::Key(key)).await?;
+						}
 					}
+					Some(Err(_)) | None => break,
 				}
 			}
 			_ = &mut close_r => {
@@ -96,12 +155,188 @@ async fn watch_stdin(
 		}
 	}
 
+	// unsubscribes: anything read from now on is discarded, until the next subscriber
+	read_r.close();
+	drop(raw);
 	Ok(())
 }
 
+type StdinRead = std::io::Result<Vec<u8>>;
+
+/// Who gets what's read from stdin, and whether it has ended.
+#[derive(Debug, Default)]
+struct StdinState {
+	subscriber: Option<mpsc::Sender<StdinRead>>,
+	ended: bool,
+}
+
+/// The state of the one thread which reads stdin, which is started on first use.
+///
+/// Stdin is read from a plain thread rather than with tokio, as tokio waits for its blocking reads
+/// to finish before it can shut down, which with a terminal is whenever a key is pressed. A read
+/// can't be cancelled, so the thread is never stopped: it's shared by all keyboard watchers, so
+/// that restarting one doesn't leave another reader behind to compete for input.
+static STDIN: Lazy<Arc<Mutex<StdinState>>> = Lazy::new(|| {
+	let state: Arc<Mutex<StdinState>> = Arc::default();
+	let reader = state.clone();
+	std::thread::spawn(move || {
+		let mut stdin = std::io::stdin();
+		let mut buffer = [0; 64];
+		loop {
+			let read = stdin.read(&mut buffer).map(|n| buffer[..n].to_vec());
+			let end = !matches!(read, Ok(ref bytes) if !bytes.is_empty());
+			let subscriber = {
+				let mut state = reader.lock().unwrap_or_else(PoisonError::into_inner);
+				state.ended = end;
+				state.subscriber.clone().filter(|s| !s.is_closed())
+			};
+
+			if let Some(subscriber) = subscriber {
+				// fails if the subscriber is gone, in which case this is discarded
+				subscriber.blocking_send(read).ok();
+			} else {
+				trace!("nothing is reading stdin, discarding input");
+			}
+
+			if end {
+				break;
+			}
+		}
+	});
+	state
+});
+
+/// Starts receiving what's read from stdin, instead of any previous subscriber.
+///
+/// If stdin has already ended, the end is received right away.
+fn subscribe_stdin() -> mpsc::Receiver<StdinRead> {
+	let (read_s, read_r) = mpsc::channel(8);
+	let mut state = STDIN.lock().unwrap_or_else(PoisonError::into_inner);
+	if state.ended {
+		read_s.try_send(Ok(Vec::new())).ok();
+	}
+	state.subscriber = Some(read_s);
+	read_r
+}
+
+/// Parses keys from what was read from stdin.
+///
+/// Unknown escape sequences and control characters are ignored.
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+	let mut keys = Vec::new();
+	let text = String::from_utf8_lossy(bytes);
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		keys.push(match c {
+			'\x1b' => match chars.peek() {
+				Some('[') => {
+					chars.next();
+					match chars.next() {
+						Some('A') => Key::Up,
+						Some('B') => Key::Down,
+						Some('C') => Key::Right,
+						Some('D') => Key::Left,
+						seq => {
+							trace!(?seq, "ignoring unknown escape sequence");
+							// skip the rest of the sequence, up to its final character
+							let is_end = |c: &char| c.is_ascii_alphabetic() || *c == '~';
+							if seq.map_or(false, |c| !is_end(&c)) {
+								while chars.next_if(|c| !is_end(c)).is_some() {}
+								chars.next();
+							}
+							continue;
+						}
+					}
+				}
+				_ => Key::Escape,
+			},
+			'\r' | '\n' => Key::Enter,
+			'\t' => Key::Tab,
+			'\x7f' | '\x08' => Key::Backspace,
+			'\x01'..='\x1a' => Key::Ctrl(char::from(b'a' + (c as u8) - 1)),
+			c if c.is_control() => {
+				trace!(?c, "ignoring control character");
+				continue;
+			}
+			c => Key::Char(c),
+		});
+	}
+
+	keys
+}
+
+/// Puts the terminal on stdin in raw mode, and restores it when dropped.
+#[derive(Debug)]
+struct RawMode {
+	#[cfg(unix)]
+	original: Option<nix::sys::termios::Termios>,
+}
+
+impl RawMode {
+	/// Enters raw mode, if stdin is a terminal (and on Unix).
+	///
+	/// This is not quite raw mode: the terminal stops echoing and buffering lines, but keeps
+	/// generating signals and processing output, so it behaves normally for everything else.
+	fn enter() -> Result<Self, std::io::Error> {
+		#[cfg(unix)]
+		{
+			use nix::{
+				sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg, SpecialCharacterIndices},
+				unistd::isatty,
+			};
+
+			const STDIN: i32 = 0;
+			if !isatty(STDIN).unwrap_or(false) {
+				debug!("stdin is not a terminal, not entering raw mode");
+				return Ok(Self { original: None });
+			}
+
+			let original = tcgetattr(STDIN)?;
+			let mut raw = original.clone();
+			raw.local_flags
+				.remove(LocalFlags::ICANON | LocalFlags::ECHO);
+			raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+			raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+			tcsetattr(STDIN, SetArg::TCSANOW, &raw)?;
+			debug!("entered raw mode");
+			Ok(Self {
+				original: Some(original),
+			})
+		}
+
+		#[cfg(not(unix))]
+		{
+			debug!("raw mode is only supported on unix");
+			Ok(Self {})
+		}
+	}
+
+	fn is_active(&self) -> bool {
+		#[cfg(unix)]
+		return self.original.is_some();
+
+		#[cfg(not(unix))]
+		return false;
+	}
+}
+
+impl Drop for RawMode {
+	fn drop(&mut self) {
+		#[cfg(unix)]
+		if let Some(original) = self.original.take() {
+			use nix::sys::termios::{tcsetattr, SetArg};
+			if let Err(err) = tcsetattr(0, SetArg::TCSANOW, &original) {
+				debug!(%err, "failed to restore terminal from raw mode");
+			} else {
+				debug!("restored terminal from raw mode");
+			}
+		}
+	}
+}
+
 async fn send_event(
-	errors: mpsc::Sender<RuntimeError>,
-	events: priority::Sender<Event, Priority>,
+	errors: &mpsc::Sender<RuntimeError>,
+	events: &priority::Sender<Event, Priority>,
 	msg: Keyboard,
 ) -> Result<(), CriticalError> {
 	let tags = vec![Tag::Source(Source::Keyboard), Tag::Keyboard(msg)];
@@ -123,3 +358,26 @@ async fn send_event(
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn parses_keys() {
+		assert_eq!(
+			parse_keys(b"r\n\x1b[A\x1b[5~\x1b[Hx\x03\x7f\x1bq\xc3\xa9"),
+			vec![
+				Key::Char('r'),
+				Key::Enter,
+				Key::Up,
+				Key::Char('x'),
+				Key::Ctrl('c'),
+				Key::Backspace,
+				Key::Escape,
+				Key::Char('q'),
+				Key::Char('é'),
+			]
+		);
+	}
+}
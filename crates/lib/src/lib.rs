@@ -103,6 +103,8 @@ pub mod fs;
 pub mod keyboard;
 pub mod paths;
 pub mod signal;
+pub mod source;
+pub mod timer;
 
 // the core experience
 pub mod config;
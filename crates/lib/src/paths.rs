@@ -65,18 +65,29 @@ where
 /// - `META_CHANGED` -> `Modify(Metadata(_))`
 /// - `REMOVED` -> `Remove(_)`
 /// - `RENAMED` -> `Modify(Name(_))`
+/// - `RENAMED_FROM` and `RENAMED_TO` -> the original and new paths of [`Tag::Rename`]s
 /// - `WRITTEN` -> `Modify(Data(_))`, `Access(Close(Write))`
 /// - `OTHERWISE_CHANGED` -> anything else
 /// - plus `COMMON` with the common prefix of all paths (even if there's only one path).
 ///
 /// It ignores non-path events and pathed events without event kind. Multiple events are sorted in
 /// byte order and joined with the platform-specific path separator (`:` for unix, `;` for Windows).
+///
+/// `RENAMED_FROM` and `RENAMED_TO` are the exception: they're in the order of the renames instead,
+/// and not deduplicated, so that the nth path of one corresponds to the nth path of the other.
 pub fn summarise_events_to_env<'events>(
 	events: impl IntoIterator<Item = &'events Event>,
 ) -> HashMap<&'static str, OsString> {
 	let mut all_trunks = Vec::new();
 	let mut kind_buckets = HashMap::new();
+	let mut renames = Vec::new();
 	for event in events {
+		renames.extend(
+			event
+				.renames()
+				.map(|(from, to)| (from.to_owned(), to.to_owned())),
+		);
+
 		let (paths, trunks): (Vec<_>, Vec<_>) = event
 			.paths()
 			.map(|(p, ft)| {
@@ -115,6 +126,15 @@ pub fn summarise_events_to_env<'events>(
 	}
 
 	let common_path = common_prefix(all_trunks);
+	let strip = |p: &PathBuf| {
+		common_path
+			.as_ref()
+			.and_then(|prefix| p.strip_prefix(prefix).ok())
+			.map_or_else(
+				|| p.clone().into_os_string(),
+				|suffix| suffix.as_os_str().to_owned(),
+			)
+	};
 
 	let mut grouped_buckets = HashMap::new();
 	for (kind, paths) in kind_buckets {
@@ -129,39 +149,43 @@ pub fn summarise_events_to_env<'events>(
 				_ => "OTHERWISE_CHANGED",
 			})
 			.or_insert_with(HashSet::new)
-			.extend(paths.into_iter().map(|ref p| {
-				common_path
-					.as_ref()
-					.and_then(|prefix| p.strip_prefix(prefix).ok())
-					.map_or_else(
-						|| p.clone().into_os_string(),
-						|suffix| suffix.as_os_str().to_owned(),
-					)
-			}));
+			.extend(paths.iter().map(strip));
 	}
 
 	let mut res: HashMap<&'static str, OsString> = grouped_buckets
 		.into_iter()
 		.map(|(kind, paths)| {
-			let mut joined =
-				OsString::with_capacity(paths.iter().map(|p| p.len()).sum::<usize>() + paths.len());
-
 			let mut paths = paths.into_iter().collect::<Vec<_>>();
 			paths.sort();
-			paths.into_iter().enumerate().for_each(|(i, path)| {
-				if i > 0 {
-					joined.push(PATH_SEPARATOR);
-				}
-				joined.push(path);
-			});
-
-			(kind, joined)
+			(kind, join_paths(paths))
 		})
 		.collect();
 
+	if !renames.is_empty() {
+		let (from, to): (Vec<_>, Vec<_>) = renames
+			.iter()
+			.map(|(from, to)| (strip(from), strip(to)))
+			.unzip();
+		res.insert("RENAMED_FROM", join_paths(from));
+		res.insert("RENAMED_TO", join_paths(to));
+	}
+
 	if let Some(common_path) = common_path {
 		res.insert("COMMON", common_path.into_os_string());
 	}
 
 	res
 }
+
+/// Joins paths with the platform-specific path separator.
+fn join_paths(paths: Vec<OsString>) -> OsString {
+	let mut joined =
+		OsString::with_capacity(paths.iter().map(|p| p.len()).sum::<usize>() + paths.len());
+	for (i, path) in paths.into_iter().enumerate() {
+		if i > 0 {
+			joined.push(PATH_SEPARATOR);
+		}
+		joined.push(path);
+	}
+	joined
+}
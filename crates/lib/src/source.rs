@@ -0,0 +1,147 @@
+//! Custom event sources.
+//!
+//! Watchexec has built-in sources for the filesystem, signals, the keyboard, and timers. Others
+//! can be added by implementing [`EventSource`] and registering them with
+//! [`InitConfig::event_source()`][crate::config::InitConfig::event_source]. They're then started,
+//! reconfigured, and shut down along with the built-in sources.
+//!
+//! Sources are configured through [`RuntimeConfig::source_config()`][crate::config::RuntimeConfig::source_config],
+//! which can hold a value of any type for each source, and which the source gets as a
+//! [`SourceConfig`] whenever Watchexec is (re)configured.
+
+use std::{any::Any, fmt, sync::Arc};
+
+use async_priority_channel as priority;
+use futures::future::BoxFuture;
+use tokio::sync::{mpsc, watch};
+
+use crate::{
+	error::{CriticalError, RuntimeError},
+	event::{Event, Priority},
+};
+
+/// A source of events, run alongside the built-in ones.
+///
+/// # Examples
+///
+/// A source which emits an event every second:
+///
+/// ```
+/// use std::time::Duration;
+/// use futures::future::BoxFuture;
+/// use watchexec::{
+///     error::CriticalError,
+///     event::{Event, Priority, Source, Tag},
+///     source::{EventSource, SourceContext},
+/// };
+///
+/// struct Heartbeat;
+///
+/// impl EventSource for Heartbeat {
+///     fn name(&self) -> &str {
+///         "heartbeat"
+///     }
+///
+///     fn run(self: Box<Self>, ctx: SourceContext) -> BoxFuture<'static, Result<(), CriticalError>> {
+///         Box::pin(async move {
+///             loop {
+///                 tokio::time::sleep(Duration::from_secs(1)).await;
+///                 let event = Event {
+///                     tags: vec![Tag::Source(Source::Internal)],
+///                     metadata: Default::default(),
+///                 };
+///                 if ctx.events.send(event, Priority::Low).await.is_err() {
+///                     // Watchexec is shutting down
+///                     return Ok(());
+///                 }
+///             }
+///         })
+///     }
+/// }
+/// ```
+pub trait EventSource: Send + 'static {
+	/// The name of the source, used for logging and to look up its configuration.
+	fn name(&self) -> &str;
+
+	/// Runs the source.
+	///
+	/// The returned future runs for as long as Watchexec does, unless the source has nothing more
+	/// to do. It's dropped when Watchexec shuts down, so it doesn't need to watch for that itself,
+	/// but it may also end early when the [config channel](SourceContext::config) closes or
+	/// sending an event fails.
+	///
+	/// Returning a [`CriticalError`] stops Watchexec, as it does for the built-in sources.
+	/// Recoverable errors should be sent to the [error channel](SourceContext::errors) instead.
+	fn run(self: Box<Self>, ctx: SourceContext) -> BoxFuture<'static, Result<(), CriticalError>>;
+}
+
+/// What an [`EventSource`] gets to work with.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SourceContext {
+	/// The configuration of the source.
+	///
+	/// This has the value given to
+	/// [`RuntimeConfig::source_config()`][crate::config::RuntimeConfig::source_config] for the
+	/// source's name, and changes when Watchexec is reconfigured. It's marked as changed on start,
+	/// even if it's empty.
+	pub config: watch::Receiver<SourceConfig>,
+
+	/// Where to send events.
+	pub events: priority::Sender<Event, Priority>,
+
+	/// Where to send runtime errors, which go to the
+	/// [error handler](crate::config::InitConfig::on_error).
+	pub errors: mpsc::Sender<RuntimeError>,
+}
+
+/// The configuration of an [`EventSource`].
+///
+/// This holds a value of any type, which the source can get back with [`get()`](Self::get). It's
+/// empty if no configuration was given for the source.
+#[derive(Clone, Default)]
+pub struct SourceConfig(Option<Arc<dyn Any + Send + Sync>>);
+
+impl SourceConfig {
+	/// Wraps a value.
+	#[must_use]
+	pub fn new(value: impl Any + Send + Sync) -> Self {
+		Self(Some(Arc::new(value)))
+	}
+
+	/// The value, if there's one and it's of this type.
+	#[must_use]
+	pub fn get<T: Any>(&self) -> Option<&T> {
+		self.0.as_deref().and_then(|value| value.downcast_ref())
+	}
+
+	/// Whether there's no value.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.0.is_none()
+	}
+}
+
+impl fmt::Debug for SourceConfig {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("SourceConfig")
+			.field(&if self.is_empty() { "empty" } else { "set" })
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn config_downcasts() {
+		let config = SourceConfig::new(String::from("mqtt://localhost"));
+		assert_eq!(
+			config.get::<String>().map(String::as_str),
+			Some("mqtt://localhost")
+		);
+		assert_eq!(config.get::<u16>(), None);
+		assert!(SourceConfig::default().get::<String>().is_none());
+	}
+}
@@ -0,0 +1,361 @@
+//! Event source for periodic timers.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use async_priority_channel as priority;
+use chrono::{DateTime, Local};
+use tokio::{
+	select,
+	sync::{mpsc, watch},
+	time::{sleep_until, Instant},
+};
+use tracing::{debug, trace};
+
+use crate::{
+	error::{CriticalError, CronParseError, RuntimeError},
+	event::{Event, Priority, Source, Tag},
+};
+
+/// The longest the worker sleeps for before checking the time again.
+///
+/// Cron schedules are in wall-clock time, which can jump (when the system is suspended, or the
+/// clock is adjusted), so long waits are done in steps rather than all at once.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// The configuration of the [timer][self] worker.
+///
+/// This is marked non-exhaustive so new configuration can be added without breaking.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct WorkingData {
+	/// The schedules to emit events on.
+	///
+	/// Each time a schedule fires, an event is emitted with a [`Source::Time`] tag, and a
+	/// [`Tag::Schedule`] with the schedule's description. These go through filtering and the action
+	/// handler like any other event.
+	///
+	/// Schedules start over when this changes: intervals count from the time of the change.
+	pub schedules: Vec<Schedule>,
+}
+
+/// When a timer fires.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+	kind: ScheduleKind,
+	description: String,
+}
+
+#[derive(Clone, Debug)]
+enum ScheduleKind {
+	Interval(Duration),
+	Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+	/// Fires every `interval`, starting one interval from when the timer is started.
+	///
+	/// # Panics
+	///
+	/// Panics if the interval is zero.
+	#[must_use]
+	pub fn interval(interval: Duration) -> Self {
+		assert!(!interval.is_zero(), "timer interval must not be zero");
+		Self {
+			kind: ScheduleKind::Interval(interval),
+			description: format!("every {interval:?}"),
+		}
+	}
+
+	/// Fires on a cron schedule, in local time.
+	///
+	/// This takes the common five fields (minute, hour, day of month, month, day of week), or six
+	/// or seven with seconds first and the year last. Shorthands like `@hourly` or `@daily` are also
+	/// accepted. In five-field expressions, days of the week are numbered as in crontab, from 0 for
+	/// Sunday to 6 for Saturday, with 7 for Sunday too. In six or seven fields, they're numbered
+	/// from 1 for Sunday to 7 for Saturday instead. Names (like `MON-FRI`) work in either.
+	///
+	/// This is the same as parsing the expression with [`FromStr`].
+	pub fn cron(expression: &str) -> Result<Self, CronParseError> {
+		let expression = expression.trim();
+		let error = |reason: String| CronParseError {
+			expression: expression.to_owned(),
+			reason,
+		};
+
+		let fields: Vec<&str> = expression.split_whitespace().collect();
+		let full = if let [minute, hour, day, month, weekdays] = fields[..] {
+			let weekdays = crontab_weekdays(weekdays).map_err(error)?;
+			format!("0 {minute} {hour} {day} {month} {weekdays}")
+		} else {
+			expression.to_owned()
+		};
+
+		let schedule = cron::Schedule::from_str(&full).map_err(|err| error(err.to_string()))?;
+
+		Ok(Self {
+			kind: ScheduleKind::Cron(Box::new(schedule)),
+			description: expression.to_owned(),
+		})
+	}
+
+	/// The description of the schedule, as used in its events' [`Tag::Schedule`].
+	///
+	/// This is the expression for cron schedules, and `every {interval:?}` for intervals.
+	#[must_use]
+	pub fn description(&self) -> &str {
+		&self.description
+	}
+}
+
+/// Translates a crontab day of week field, from 0 or 7 for Sunday, to the `cron` crate's numbering,
+/// from 1 for Sunday to 7 for Saturday.
+///
+/// Items without numbers, like `*` or `MON-FRI`, are kept as they are.
+fn crontab_weekdays(field: &str) -> Result<String, String> {
+	fn number(day: &str) -> Result<u32, String> {
+		const NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+		let lower = day.to_ascii_lowercase();
+		if let Some(n) = NAMES.iter().position(|name| lower == *name) {
+			return Ok(n as u32);
+		}
+
+		match day.parse() {
+			Ok(n) if n <= 7 => Ok(n),
+			_ => Err(format!(
+				"invalid day of week {day:?}: days go from 0 (or 7) for Sunday to 6 for Saturday"
+			)),
+		}
+	}
+
+	let mut items = Vec::new();
+	for item in field.split(',') {
+		let (range, step) = match item.split_once('/') {
+			Some((range, step)) => (range, Some(step)),
+			None => (item, None),
+		};
+
+		if !range.bytes().any(|b| b.is_ascii_digit()) {
+			items.push(item.to_owned());
+			continue;
+		}
+
+		let step_suffix = step.map(|step| format!("/{step}")).unwrap_or_default();
+		let (start, end) = match range.split_once('-') {
+			Some((start, end)) => (number(start)?, Some(number(end)?)),
+			None => (number(range)?, None),
+		};
+
+		// 7 is Sunday as well, but only makes sense as the start of a range as 0
+		let start = start % 7;
+		match end {
+			None => items.push(format!("{}{step_suffix}", start + 1)),
+			Some(7) => {
+				// up to Saturday, then Sunday separately if the range would get to it
+				items.push(format!("{}-7{step_suffix}", start + 1));
+				let step: Option<u32> = step.and_then(|step| step.parse().ok());
+				if step.map_or(true, |step| step != 0 && (7 - start) % step == 0) {
+					items.push("1".into());
+				}
+			}
+			Some(end) => items.push(format!("{}-{}{step_suffix}", start + 1, end + 1)),
+		}
+	}
+
+	Ok(items.join(","))
+}
+
+impl FromStr for Schedule {
+	type Err = CronParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::cron(s)
+	}
+}
+
+impl fmt::Display for Schedule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.description)
+	}
+}
+
+/// The next time a schedule fires.
+#[derive(Clone, Copy, Debug)]
+enum Next {
+	At(Instant),
+	OnClock(DateTime<Local>),
+	Never,
+}
+
+impl Next {
+	fn first(schedule: &Schedule) -> Self {
+		match &schedule.kind {
+			ScheduleKind::Interval(interval) => Self::At(Instant::now() + *interval),
+			ScheduleKind::Cron(cron) => cron
+				.upcoming(Local)
+				.next()
+				.map_or(Self::Never, Self::OnClock),
+		}
+	}
+
+	fn after(self, schedule: &Schedule) -> Self {
+		match (self, &schedule.kind) {
+			(Self::At(at), ScheduleKind::Interval(interval)) => {
+				// if we've fallen behind, skip the missed ticks instead of firing them all at once
+				let now = Instant::now();
+				let next = at + *interval;
+				Self::At(if next <= now { now + *interval } else { next })
+			}
+			(Self::OnClock(at), ScheduleKind::Cron(cron)) => {
+				let now = Local::now().max(at);
+				cron.after(&now).next().map_or(Self::Never, Self::OnClock)
+			}
+			_ => Self::first(schedule),
+		}
+	}
+
+	/// How long until this is due, or `None` if it's due now.
+	fn remaining(self) -> Option<Duration> {
+		match self {
+			Self::At(at) => Some(at.saturating_duration_since(Instant::now())),
+			Self::OnClock(at) => (at - Local::now()).to_std().ok(),
+			Self::Never => Some(Duration::MAX),
+		}
+		.filter(|left| !left.is_zero())
+	}
+}
+
+/// Launch the timer event worker.
+///
+/// While you can run several, you should only have one.
+///
+/// Sends timer events to the provided 'events' channel.
+pub async fn worker(
+	mut working: watch::Receiver<WorkingData>,
+	errors: mpsc::Sender<RuntimeError>,
+	events: priority::Sender<Event, Priority>,
+) -> Result<(), CriticalError> {
+	let mut timers: Vec<(Schedule, Next)> = Vec::new();
+	loop {
+		let wait = timers
+			.iter()
+			.map(|(_, next)| next.remaining().unwrap_or_default())
+			.min();
+
+		if let Some(wait) = wait {
+			trace!(?wait, "waiting for next timer");
+			select! {
+				_ = sleep_until(Instant::now() + wait.min(MAX_SLEEP)) => {}
+				changed = working.changed() => {
+					if changed.is_err() {
+						break;
+					}
+
+					timers = start(&working.borrow());
+					continue;
+				}
+			}
+		} else {
+			if working.changed().await.is_err() {
+				break;
+			}
+
+			timers = start(&working.borrow());
+			continue;
+		}
+
+		for (schedule, next) in &mut timers {
+			if next.remaining().is_some() {
+				continue;
+			}
+
+			debug!(%schedule, "timer fired");
+			*next = next.after(schedule);
+
+			let event = Event {
+				tags: vec![
+					Tag::Source(Source::Time),
+					Tag::Schedule(schedule.description.clone()),
+				],
+				metadata: Default::default(),
+			};
+
+			trace!(?event, "processed timer event");
+			if let Err(err) = events.send(event, Priority::Normal).await {
+				errors
+					.send(RuntimeError::EventChannelSend { ctx: "timer", err })
+					.await?;
+			}
+		}
+	}
+
+	debug!("ending timer worker");
+	Ok(())
+}
+
+fn start(working: &WorkingData) -> Vec<(Schedule, Next)> {
+	debug!(schedules=?working.schedules, "starting timers");
+	working
+		.schedules
+		.iter()
+		.map(|schedule| (schedule.clone(), Next::first(schedule)))
+		.collect()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn cron_takes_five_fields() {
+		let schedule = Schedule::cron(" */5 * * * MON-FRI ").expect("valid");
+		assert_eq!(schedule.description(), "*/5 * * * MON-FRI");
+		match &schedule.kind {
+			ScheduleKind::Cron(cron) => assert_eq!(cron.to_string(), "0 */5 * * * MON-FRI"),
+			ScheduleKind::Interval(_) => panic!("not a cron schedule"),
+		}
+	}
+
+	fn weekdays(expression: &str) -> Vec<chrono::Weekday> {
+		use chrono::{Datelike, TimeZone, Utc};
+		let schedule = Schedule::cron(expression).expect("valid");
+		let cron = match schedule.kind {
+			ScheduleKind::Cron(cron) => cron,
+			ScheduleKind::Interval(_) => panic!("not a cron schedule"),
+		};
+
+		// a Monday
+		let start = Utc.with_ymd_and_hms(2023, 5, 1, 0, 0, 0).unwrap();
+		cron.after(&start).take(7).map(|at| at.weekday()).collect()
+	}
+
+	#[test]
+	fn cron_five_fields_number_weekdays_like_crontab() {
+		use chrono::Weekday::*;
+		assert_eq!(
+			weekdays("0 9 * * 1-5"),
+			vec![Mon, Tue, Wed, Thu, Fri, Mon, Tue]
+		);
+		assert_eq!(weekdays("0 9 * * 0")[..2], [Sun, Sun]);
+		assert_eq!(weekdays("0 9 * * 7")[..2], [Sun, Sun]);
+		assert_eq!(weekdays("0 9 * * 5-7")[..4], [Fri, Sat, Sun, Fri]);
+		assert_eq!(weekdays("0 9 * * 0-6/2")[..4], [Tue, Thu, Sat, Sun]);
+		assert_eq!(weekdays("0 9 * * MON,3")[..3], [Mon, Wed, Mon]);
+		assert_eq!(weekdays("0 0 9 * * 2 *")[..2], [Mon, Mon]);
+		assert!(Schedule::cron("0 9 * * 8").is_err());
+	}
+
+	#[test]
+	fn cron_takes_shorthands() {
+		assert!(Schedule::cron("@hourly").is_ok());
+		assert!("0 30 9 * * * 2030".parse::<Schedule>().is_ok());
+		assert!(Schedule::cron("every tuesday").is_err());
+	}
+
+	#[test]
+	fn interval_description() {
+		assert_eq!(
+			Schedule::interval(Duration::from_secs(90)).to_string(),
+			"every 90s"
+		);
+	}
+}
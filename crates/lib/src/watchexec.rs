@@ -10,6 +10,7 @@ use std::{
 
 use async_priority_channel as priority;
 use atomic_take::AtomicTake;
+use futures::future::try_join_all;
 use miette::Diagnostic;
 use once_cell::sync::OnceCell;
 use tokio::{
@@ -28,6 +29,8 @@ use crate::{
 	fs,
 	handler::{rte, Handler},
 	keyboard, signal,
+	source::{SourceConfig, SourceContext},
+	timer,
 };
 
 /// The main watchexec runtime.
@@ -44,8 +47,11 @@ pub struct Watchexec {
 	action_watch: watch::Sender<action::WorkingData>,
 	fs_watch: watch::Sender<fs::WorkingData>,
 	keyboard_watch: watch::Sender<keyboard::WorkingData>,
+	timer_watch: watch::Sender<timer::WorkingData>,
+	source_watches: Vec<(String, watch::Sender<SourceConfig>)>,
 
 	event_input: priority::Sender<Event, Priority>,
+	paused: Arc<watch::Sender<bool>>,
 }
 
 impl fmt::Debug for Watchexec {
@@ -80,8 +86,11 @@ impl Watchexec {
 		let (ac_s, ac_r) = watch::channel(take(&mut runtime.action));
 		let (fs_s, fs_r) = watch::channel(fs::WorkingData::default());
 		let (keyboard_s, keyboard_r) = watch::channel(keyboard::WorkingData::default());
+		let (timer_s, timer_r) = watch::channel(timer::WorkingData::default());
 
 		let event_input = ev_s.clone();
+		let paused = Arc::new(watch::channel(false).0);
+		let action_paused = paused.clone();
 
 		// TODO: figure out how to do this (aka start the fs work) after the main task start lock
 		trace!("sending initial config to fs worker");
@@ -93,6 +102,22 @@ impl Watchexec {
 			.send(take(&mut runtime.keyboard))
 			.expect("cannot send to just-created keyboard watch (bug)");
 
+		trace!("sending initial config to timer worker");
+		timer_s
+			.send(take(&mut runtime.timer))
+			.expect("cannot send to just-created timer watch (bug)");
+
+		trace!("sending initial config to custom sources");
+		let mut source_watches = Vec::with_capacity(init.sources.len());
+		let mut sources = Vec::with_capacity(init.sources.len());
+		for source in take(&mut init.sources) {
+			let name = source.name().to_owned();
+			let (config_s, config_r) = watch::channel(SourceConfig::default());
+			config_s.send_replace(runtime.sources.remove(&name).unwrap_or_default());
+			source_watches.push((name, config_s));
+			sources.push((source, config_r));
+		}
+
 		trace!("creating main task");
 		let notify = Arc::new(Notify::new());
 		let start_lock = notify.clone();
@@ -107,7 +132,7 @@ impl Watchexec {
 
 			let action = SubTask::spawn(
 				"action",
-				action::worker(ac_r, er_s.clone(), ev_s.clone(), ev_r),
+				action::worker(ac_r, er_s.clone(), ev_s.clone(), ev_r, action_paused),
 			);
 			let fs = SubTask::spawn("fs", fs::worker(fs_r, er_s.clone(), ev_s.clone()));
 			let signal =
@@ -116,11 +141,34 @@ impl Watchexec {
 				"keyboard",
 				keyboard::worker(keyboard_r, er_s.clone(), ev_s.clone()),
 			);
+			let timer = SubTask::spawn("timer", timer::worker(timer_r, er_s.clone(), ev_s.clone()));
+
+			let custom = sources
+				.into_iter()
+				.map(|(source, config)| {
+					let name = source.name().to_owned();
+					debug!(source=%name, "starting custom source");
+					let run = source.run(SourceContext {
+						config,
+						events: ev_s.clone(),
+						errors: er_s.clone(),
+					});
+					async move {
+						let res = run.await;
+						debug!(source=%name, ?res, "custom source finished");
+						res
+					}
+				})
+				.collect::<Vec<_>>();
+			let sources = SubTask::spawn(
+				"sources",
+				async move { try_join_all(custom).await.map(drop) },
+			);
 
 			let error_hook = SubTask::spawn("error_hook", error_hook(er_r, eh));
 
 			// Use Tokio TaskSet when that lands
-			try_join!(action, error_hook, fs, signal, keyboard)
+			try_join!(action, error_hook, fs, signal, keyboard, timer, sources)
 				.map(drop)
 				.or_else(|e| {
 					// Close event channel to signal worker task to stop
@@ -146,8 +194,11 @@ impl Watchexec {
 			action_watch: ac_s,
 			fs_watch: fs_s,
 			keyboard_watch: keyboard_s,
+			timer_watch: timer_s,
+			source_watches,
 
 			event_input,
+			paused,
 		}))
 	}
 
@@ -157,6 +208,11 @@ impl Watchexec {
 		self.action_watch.send(config.action)?;
 		self.fs_watch.send(config.fs)?;
 		self.keyboard_watch.send(config.keyboard)?;
+		self.timer_watch.send(config.timer)?;
+		for (name, source_watch) in &self.source_watches {
+			// a source which has stopped listening for config is fine
+			source_watch.send_replace(config.sources.get(name).cloned().unwrap_or_default());
+		}
 		Ok(())
 	}
 
@@ -171,6 +227,44 @@ impl Watchexec {
 		Ok(())
 	}
 
+	/// Pauses reacting to changes.
+	///
+	/// While paused, events about changes are dropped or held until resuming, as configured with
+	/// [`RuntimeConfig::paused_events()`]. Other events, like signals, are actioned as usual, and
+	/// running commands are left alone.
+	///
+	/// This can also be done from the action handler with [`Outcome::Pause`][crate::action::Outcome::Pause].
+	pub fn pause(&self) {
+		debug!("pausing");
+		self.paused.send_replace(true);
+	}
+
+	/// Resumes reacting to changes.
+	///
+	/// If changes were held while paused, they're actioned now.
+	///
+	/// This can also be done from the action handler with [`Outcome::Resume`][crate::action::Outcome::Resume].
+	pub fn resume(&self) {
+		debug!("resuming");
+		self.paused.send_replace(false);
+	}
+
+	/// Pauses if running, resumes if paused, and returns whether it's now paused.
+	pub fn toggle_pause(&self) -> bool {
+		let mut now = false;
+		self.paused.send_modify(|paused| {
+			*paused = !*paused;
+			now = *paused;
+		});
+		debug!(paused=%now, "toggled pause");
+		now
+	}
+
+	/// Returns whether reacting to changes is paused.
+	pub fn is_paused(&self) -> bool {
+		*self.paused.borrow()
+	}
+
 	/// Start watchexec and obtain the handle to its main task.
 	///
 	/// This must only be called once.
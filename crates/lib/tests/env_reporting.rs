@@ -371,3 +371,53 @@ fn multipath_is_deduped() {
 		])
 	);
 }
+
+fn renamed(from: &str, to: &str) -> Event {
+	Event {
+		tags: vec![
+			Tag::Path {
+				path: ospath(from).into(),
+				file_type: None,
+			},
+			Tag::Path {
+				path: ospath(to).into(),
+				file_type: None,
+			},
+			Tag::FileEventKind(FileEventKind::Modify(ModifyKind::Name(RenameMode::Both))),
+			Tag::Rename {
+				from: ospath(from).into(),
+				to: ospath(to).into(),
+			},
+		],
+		metadata: Default::default(),
+	}
+}
+
+#[test]
+fn renames_are_paired_in_order() {
+	let events = vec![renamed("z.txt", "a.txt"), renamed("b.txt", "y.txt")];
+	assert_eq!(
+		summarise_events_to_env(&events),
+		HashMap::from([
+			(
+				"RENAMED",
+				OsString::from(
+					String::new()
+						+ "a.txt" + ENV_SEP
+						+ "b.txt" + ENV_SEP
+						+ "y.txt" + ENV_SEP
+						+ "z.txt"
+				)
+			),
+			(
+				"RENAMED_FROM",
+				OsString::from(String::new() + "z.txt" + ENV_SEP + "b.txt")
+			),
+			(
+				"RENAMED_TO",
+				OsString::from(String::new() + "a.txt" + ENV_SEP + "y.txt")
+			),
+			("COMMON", ospath("")),
+		])
+	);
+}
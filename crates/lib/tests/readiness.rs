@@ -0,0 +1,83 @@
+#![cfg(unix)]
+
+use std::{
+	convert::Infallible,
+	sync::atomic::{AtomicBool, Ordering},
+	time::Duration,
+};
+
+use tokio::{sync::mpsc, time::timeout};
+use watchexec::{
+	action::{Action, Outcome},
+	command::{Command, Readiness},
+	config::{InitConfig, RuntimeConfig},
+	event::{Event, Priority, Source, Tag},
+	ErrorHook, Watchexec,
+};
+
+fn keyboard() -> Event {
+	Event {
+		tags: vec![Tag::Source(Source::Keyboard)],
+		metadata: Default::default(),
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn events_are_released_when_never_ready() {
+	let (actions_s, mut actions_r) = mpsc::unbounded_channel();
+
+	let mut runtime = RuntimeConfig::default();
+	runtime.command(Command::Exec {
+		prog: "sleep".into(),
+		args: vec!["10".into()],
+	});
+	runtime.command_readiness(Some(Readiness::File(
+		"/nonexistent/watchexec-never-ready".into(),
+	)));
+	runtime.command_ready_timeout(Some(Duration::from_millis(500)));
+	let started = AtomicBool::new(false);
+	runtime.on_action(move |action: Action| {
+		let first = !started.swap(true, Ordering::SeqCst);
+		actions_s.send(()).ok();
+		action.outcome(if first {
+			Outcome::Start
+		} else {
+			Outcome::both(Outcome::Stop, Outcome::Exit)
+		});
+		async { Ok::<(), Infallible>(()) }
+	});
+
+	let mut init = InitConfig::default();
+	init.on_error(|_: ErrorHook| async { Ok::<(), Infallible>(()) });
+
+	let wx = Watchexec::new(init, runtime).expect("watchexec");
+	let main = wx.main();
+
+	wx.send_event(keyboard(), Priority::Normal)
+		.await
+		.expect("send first event");
+	timeout(Duration::from_secs(5), actions_r.recv())
+		.await
+		.expect("first action")
+		.expect("first action");
+
+	wx.send_event(keyboard(), Priority::Normal)
+		.await
+		.expect("send second event");
+	assert!(
+		timeout(Duration::from_millis(200), actions_r.recv())
+			.await
+			.is_err(),
+		"second event should be held while the command isn't ready"
+	);
+	timeout(Duration::from_secs(5), actions_r.recv())
+		.await
+		.expect("second event should be actioned once readiness times out")
+		.expect("second action");
+
+	timeout(Duration::from_secs(5), main)
+		.await
+		.expect("exit")
+		.expect("join")
+		.expect("main");
+}